@@ -2,10 +2,12 @@ function CardSelector({
   selectedVote,
   cardSet,
   onVoteChange,
+  onVoteRetracted,
 }: {
   selectedVote?: number | null;
   cardSet: { [key: string]: number };
   onVoteChange: (vote: number) => void;
+  onVoteRetracted: () => void;
 }) {
   return (
     <div className="flex justify-center flex-nowrap md:flex-wrap gap-2 md:gap-4 max-w-4xl pb-4">
@@ -20,7 +22,12 @@ function CardSelector({
                                 }
                                 `}
           onClick={() => {
-            onVoteChange(value);
+            // picking the selected card again takes the vote back
+            if (selectedVote === value) {
+              onVoteRetracted();
+            } else {
+              onVoteChange(value);
+            }
           }}
         >
           <span className="text-3xl font-bold text-zinc-600">{label}</span>
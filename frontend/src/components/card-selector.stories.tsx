@@ -32,6 +32,7 @@ export const NoVote: Story = {
     selectedVote: null,
     cardSet: CardSet.fibonacci,
     onVoteChange: fn(),
+    onVoteRetracted: fn(),
   },
 };
 
@@ -40,19 +41,21 @@ export const VoteSelected: Story = {
     selectedVote: 1,
     cardSet: CardSet.fibonacci,
     onVoteChange: fn(),
+    onVoteRetracted: fn(),
   },
 };
 
 export const ControlledComponent: Story = {
   decorators: [
     (Story) => {
-      const [selectedVote, setSelectedVote] = useState(1);
+      const [selectedVote, setSelectedVote] = useState<number | null>(1);
       return (
         <Story
           args={{
             selectedVote,
             cardSet: CardSet.fibonacci,
             onVoteChange: setSelectedVote,
+            onVoteRetracted: () => setSelectedVote(null),
           }}
         />
       );
@@ -4,20 +4,24 @@ function CentralCard({
   isRevealed,
   showHostControls,
   votes,
-  hasSomeVoted,
+  quorumMet,
   cardSet,
   classes,
   onVotesRevealed,
   onVotesReset,
+  onRevote,
+  onEstimateAccepted,
 }: {
   isRevealed: boolean;
   showHostControls: boolean;
   votes: number[];
-  hasSomeVoted: boolean;
+  quorumMet: boolean;
   cardSet: { [key: string]: number };
   classes?: string;
   onVotesRevealed: () => void;
   onVotesReset: () => void;
+  onRevote: () => void;
+  onEstimateAccepted: () => void;
 }) {
   const voteCounts = votes.reduce(
     (acc, vote) => {
@@ -72,10 +76,24 @@ function CentralCard({
           <button
             onClick={onVotesRevealed}
             className="btn preset-filled-secondary-500 shadow-md"
-            disabled={!hasSomeVoted || isRevealed}
+            disabled={!quorumMet || isRevealed}
           >
             Reveal Cards
           </button>
+          <button
+            onClick={onEstimateAccepted}
+            className="btn preset-filled-success-500 shadow-md"
+            disabled={!isRevealed || modeVote.vote === -1}
+          >
+            Accept
+          </button>
+          <button
+            onClick={onRevote}
+            className="btn preset-filled-tertiary-500 shadow-md"
+            disabled={!isRevealed}
+          >
+            Revote
+          </button>
           <button
             onClick={onVotesReset}
             className="btn preset-filled-error-500 shadow-md"
@@ -38,10 +38,12 @@ export const VotesHidden: Story = {
     isRevealed: false,
     showHostControls: false,
     votes: [],
-    hasSomeVoted: false,
+    quorumMet: false,
     cardSet: CardSet.fibonacci,
     onVotesRevealed: fn(),
     onVotesReset: fn(),
+    onRevote: fn(),
+    onEstimateAccepted: fn(),
   },
 };
 
@@ -50,10 +52,12 @@ export const HiddenWithHostControls: Story = {
     isRevealed: false,
     showHostControls: true,
     votes: [],
-    hasSomeVoted: false,
+    quorumMet: false,
     cardSet: CardSet.fibonacci,
     onVotesRevealed: fn(),
     onVotesReset: fn(),
+    onRevote: fn(),
+    onEstimateAccepted: fn(),
   },
 };
 
@@ -62,10 +66,12 @@ export const HostControlsSomeVoted: Story = {
     isRevealed: false,
     showHostControls: true,
     votes: [5],
-    hasSomeVoted: true,
+    quorumMet: true,
     cardSet: CardSet.fibonacci,
     onVotesRevealed: fn(),
     onVotesReset: fn(),
+    onRevote: fn(),
+    onEstimateAccepted: fn(),
   },
 };
 
@@ -74,10 +80,12 @@ export const VotesRevealed: Story = {
     isRevealed: true,
     showHostControls: false,
     votes: [5, 3, 5, 5, 1, 5],
-    hasSomeVoted: true,
+    quorumMet: true,
     cardSet: CardSet.fibonacci,
     onVotesRevealed: fn(),
     onVotesReset: fn(),
+    onRevote: fn(),
+    onEstimateAccepted: fn(),
   },
 };
 
@@ -86,10 +94,12 @@ export const RevealedWithHostControls: Story = {
     isRevealed: true,
     showHostControls: true,
     votes: [5, 3, 5, 5, 1, 5],
-    hasSomeVoted: true,
+    quorumMet: true,
     cardSet: CardSet.fibonacci,
     onVotesRevealed: fn(),
     onVotesReset: fn(),
+    onRevote: fn(),
+    onEstimateAccepted: fn(),
   },
 };
 
@@ -98,7 +108,7 @@ export const AllPlayersPass: Story = {
     isRevealed: true,
     showHostControls: false,
     votes: [0, 0, 0],
-    hasSomeVoted: true,
+    quorumMet: true,
 
     cardSet: {
       '1': 1,
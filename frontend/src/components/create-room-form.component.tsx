@@ -5,28 +5,40 @@ import { z } from 'zod';
 
 import { Fibonacci } from './fibonacci.component';
 
+import type { Account } from '../types';
+
 const createRoomSchema = z.object({
   name: z.string().min(3).max(100).trim().nonempty(),
   playerType: z.enum(['player', 'spectator']),
   cardSet: z.enum(['fibonacci', 'tshirt']),
+  requireLogin: z.boolean(),
 });
 
 function CreateRoomForm({
   onCreate,
+  account,
 }: {
-  onCreate: (name: string, isSpectator: boolean, cardSet: string) => void;
+  onCreate: (
+    name: string,
+    isSpectator: boolean,
+    cardSet: string,
+    requireLogin: boolean,
+  ) => void;
+  account?: Account;
 }) {
   const createForm = useForm({
     defaultValues: {
-      name: '',
+      name: account?.user?.name ?? '',
       playerType: 'player',
       cardSet: 'fibonacci',
+      requireLogin: false,
     },
     onSubmit: (values) => {
       onCreate(
         values.value.name,
         values.value.playerType === 'spectator',
         values.value.cardSet,
+        values.value.requireLogin,
       );
     },
     validators: {
@@ -54,12 +66,30 @@ function CreateRoomForm({
                 type="text"
                 className="input"
                 name={field.name}
-                value={field.state.value}
+                value={account?.user?.name ?? field.state.value}
+                disabled={!!account?.user}
                 onChange={(e) => field.handleChange(e.target.value)}
               />
             </label>
           )}
         />
+        {account?.user && (
+          <createForm.Field
+            name="requireLogin"
+            children={(field) => (
+              <label className="flex items-center space-x-2">
+                <input
+                  type="checkbox"
+                  className="checkbox"
+                  name={field.name}
+                  checked={field.state.value}
+                  onChange={(e) => field.handleChange(e.target.checked)}
+                />
+                <span>Only allow logged in players</span>
+              </label>
+            )}
+          />
+        )}
         <div className="grid grid-cols-[1fr_auto_1fr] place-items-center">
           <createForm.Field
             name="playerType"
@@ -143,8 +173,9 @@ function CreateRoomForm({
             <button
               type="submit"
               className="btn preset-filled-primary-500"
-              aria-disabled={!canSubmit || isPristine}
-              disabled={!canSubmit || isPristine}
+              // logged in players' names are filled in already
+              aria-disabled={!canSubmit || (isPristine && !account?.user)}
+              disabled={!canSubmit || (isPristine && !account?.user)}
             >
               <span>Create Room</span>
               <ArrowRight size={18} />
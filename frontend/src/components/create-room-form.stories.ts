@@ -46,6 +46,7 @@ export const CreateRoomForm: Story = {
         'Bob',
         false,
         'fibonacci',
+        false,
       );
     });
     await step('Test alternate options', async () => {
@@ -54,7 +55,12 @@ export const CreateRoomForm: Story = {
       await userEvent.click(canvas.getByTitle('spectator'));
       await userEvent.click(canvas.getByTitle('tshirt'));
       await userEvent.click(canvas.getByText('Create Room'));
-      await expect(args.onCreate).toHaveBeenCalledWith('Alice', true, 'tshirt');
+      await expect(args.onCreate).toHaveBeenCalledWith(
+        'Alice',
+        true,
+        'tshirt',
+        false,
+      );
     });
   },
 };
@@ -1,4 +1,10 @@
-import { Binoculars, Check, MessageCircleMore, Sparkles } from 'lucide-react';
+import {
+  BadgeCheck,
+  Binoculars,
+  Check,
+  MessageCircleMore,
+  Sparkles,
+} from 'lucide-react';
 
 function PlayerCard({
   name,
@@ -8,6 +14,10 @@ function PlayerCard({
   isRevealed,
   color = 'preset-filled-primary-700-300',
   isSpectator,
+  verified = false,
+  avatarUrl,
+  emoji,
+  role,
   classes,
   style,
 }: {
@@ -18,6 +28,10 @@ function PlayerCard({
   isRevealed: boolean;
   color: string;
   isSpectator: boolean;
+  verified?: boolean;
+  avatarUrl?: string | null;
+  emoji?: string | null;
+  role?: string | null;
   classes?: string;
   style?: React.CSSProperties;
 }) {
@@ -39,10 +53,24 @@ function PlayerCard({
         </span>
       )}
       <span
-        className={`font-bold text-base truncate max-w-28 ${isHost ? 'underline md:no-underline' : ''}`}
+        className={`font-bold text-base truncate max-w-28 flex items-center gap-1 ${isHost ? 'underline md:no-underline' : ''}`}
+        title={verified ? 'Verified login' : undefined}
       >
+        {avatarUrl && (
+          <img
+            src={avatarUrl}
+            alt=""
+            className="w-5 h-5 rounded-full"
+            referrerPolicy="no-referrer"
+          />
+        )}
+        {!avatarUrl && emoji && <span aria-hidden="true">{emoji}</span>}
         {name}
+        {verified && <BadgeCheck size={14} />}
       </span>
+      {role && (
+        <span className="badge preset-tonal text-xs uppercase">{role}</span>
+      )}
       {isRevealed && !isSpectator && (
         <span className="text-2xl font-bold line-clamp-1">{vote ?? '?'}</span>
       )}
@@ -1,5 +1,5 @@
 import { AppBar } from '@skeletonlabs/skeleton-react';
-import { ArrowLeft, ClipboardCopy } from 'lucide-react';
+import { ArrowLeft, ClipboardCopy, Eye, EyeOff } from 'lucide-react';
 
 import { toaster } from '../contexts/toaster.context';
 import { Lightswitch } from './lightswitch.component';
@@ -11,12 +11,14 @@ function RoomHeadline({
   isHost,
   isSpectator,
   exitRoom,
+  toggleSpectator,
 }: {
   roomId: string;
   playerName?: string;
   isHost: boolean;
   isSpectator: boolean;
   exitRoom: () => void;
+  toggleSpectator: () => void;
 }) {
   return (
     <AppBar className="preset-glass-neutral py-2! px-1! md:py-4! md:px-2!">
@@ -57,6 +59,20 @@ function RoomHeadline({
         </AppBar.Headline>
         <AppBar.Trail>
           <Lightswitch className="btn pl-2 pr-0 py-1 md:pl-4 md:py-2" />
+          <Tooltip>
+            <TooltipTrigger asChild>
+              <button
+                aria-label={isSpectator ? 'Start voting' : 'Start spectating'}
+                className="btn px-2 py-1 md:px-4 md:py-2"
+                onClick={toggleSpectator}
+              >
+                {isSpectator ? <EyeOff size={20} /> : <Eye size={20} />}
+              </button>
+            </TooltipTrigger>
+            <TooltipContent>
+              {isSpectator ? 'Start Voting' : 'Start Spectating'}
+            </TooltipContent>
+          </Tooltip>
           <Tooltip>
             <TooltipTrigger asChild>
               <button
@@ -52,6 +52,7 @@ export const IsPlayer: Story = {
     isSpectator: false,
     playerName: 'Bob',
     exitRoom: fn(),
+    toggleSpectator: fn(),
   },
 };
 
@@ -62,6 +63,7 @@ export const IsHost: Story = {
     isSpectator: false,
     playerName: 'Alice',
     exitRoom: fn(),
+    toggleSpectator: fn(),
   },
 };
 
@@ -0,0 +1,315 @@
+// Generated by `cargo run -- typescript`, do not edit by hand.
+
+export const PROTOCOL_VERSION = 3;
+
+export interface AcceptEstimateEvent {
+  room_id: string;
+  estimate: EstimateChoice;
+  advance?: boolean;
+}
+
+export type ActionRejected =
+  | { reason: 'tooManyRooms'; max: number }
+  | { reason: 'roomFull'; max: number }
+  | { reason: 'invalidName'; max_length: number }
+  | { reason: 'serverDraining' }
+  | { reason: 'invalidWebhook'; message: string }
+  | { reason: 'tooManyWebhooks'; max: number }
+  | { reason: 'tooManyStories'; max: number }
+  | { reason: 'integrationFailed'; message: string }
+  | { reason: 'loginRequired' }
+  | { reason: 'invalidSettings'; message: string }
+  | { reason: 'spectatorsNotAllowed' }
+  | { reason: 'votingClosed' }
+  | { reason: 'quorumNotMet'; votes: number; required: number }
+  | { reason: 'notRevealed' }
+  | { reason: 'alreadyRevealed' }
+  | { reason: 'noEstimate' }
+  | { reason: 'invalidEstimate'; value: number }
+  | { reason: 'invalidProfile'; message: string }
+  | { reason: 'invalidVote'; value: number }
+  | { reason: 'spectatorCannotVote' };
+
+export interface AddWebhookEvent {
+  room_id: string;
+  url: string;
+}
+
+export interface AllVoted {
+  room_id: string;
+  reveal_in_secs: number | null;
+}
+
+export type AutoReveal =
+  | { mode: 'immediate' }
+  | { mode: 'afterGrace'; grace_secs: number }
+  | { mode: 'manual' };
+
+export type ChatFormat = 'slack' | 'teams';
+
+export interface ChatWebhook {
+  format: ChatFormat;
+  url: string;
+}
+
+export interface Convergence {
+  rounds: number;
+  spreads: (number | null)[];
+  narrowing: boolean;
+  consensus_round: number | null;
+}
+
+export interface CreateRoomEvent {
+  name: string;
+  is_spectator: boolean;
+  card_set: string;
+  require_login?: boolean;
+}
+
+export type EstimateChoice =
+  | { from: 'consensus' }
+  | { from: 'median' }
+  | { from: 'card'; value: number };
+
+export interface ImportGitHubIssuesEvent {
+  room_id: string;
+  repo: string;
+  milestone?: number | null;
+}
+
+export interface ImportJiraIssuesEvent {
+  room_id: string;
+  jql: string;
+}
+
+export interface JoinRoomEvent {
+  name: string;
+  room_id: string;
+  is_spectator: boolean;
+}
+
+export interface JoinTeamRoomEvent {
+  name: string;
+  team_id: string;
+  is_spectator: boolean;
+}
+
+export interface Player {
+  has_voted: boolean;
+  id: string;
+  name: string;
+  vote: number | null;
+  is_spectator: boolean;
+  verified: boolean;
+  avatar_url: string | null;
+  profile: Profile;
+}
+
+export interface PlayerExitEvent {
+  room_id: string;
+}
+
+export type PlayerRole = 'dev' | 'qa' | 'design';
+
+export interface Profile {
+  emoji: string | null;
+  color: number | null;
+  role: PlayerRole | null;
+}
+
+export interface RemoveWebhookEvent {
+  room_id: string;
+  url: string;
+}
+
+export interface ResetVotesEvent {
+  room_id: string;
+}
+
+export interface ResyncEvent {
+  room_id: string;
+}
+
+export interface RetractVoteEvent {
+  room_id: string;
+}
+
+export interface RevealCardsEvent {
+  room_id: string;
+}
+
+export type RevealQuorum =
+  | { rule: 'minVotes'; votes: number }
+  | { rule: 'minPercent'; percent: number }
+  | { rule: 'hostOverride' };
+
+export interface RevoteEvent {
+  room_id: string;
+}
+
+export interface Room {
+  cards_revealed: boolean;
+  host_id: string;
+  id: string;
+  players: { [key: string]: Player };
+  settings: RoomSettings;
+  results: VoteStatistics | null;
+  estimate: number | null;
+  rounds: VoteStatistics[];
+  convergence: Convergence;
+  stories: Story[];
+  current_story: string | null;
+  require_login: boolean;
+  seq: number;
+}
+
+export type RoomChange =
+  | { op: 'playerAdded'; player: Player }
+  | { op: 'playerUpdated'; player: Player }
+  | { op: 'playerRemoved'; player_id: string }
+  | { op: 'roomUpdated'; cards_revealed: boolean; host_id: string; current_story: string | null; estimate: number | null }
+  | { op: 'settingsUpdated'; settings: RoomSettings }
+  | { op: 'resultsUpdated'; results: VoteStatistics | null }
+  | { op: 'storiesUpdated'; stories: Story[] }
+  | { op: 'roundsUpdated'; rounds: VoteStatistics[]; convergence: Convergence };
+
+export interface RoomDelta {
+  room_id: string;
+  seq: number;
+  changes: RoomChange[];
+}
+
+export interface RoomSettings {
+  card_set: string;
+  auto_reveal: AutoReveal;
+  reveal_quorum: RevealQuorum;
+  allow_spectators: boolean;
+  allow_vote_change_after_reveal: boolean;
+  anonymous: boolean;
+  keep_attribution: boolean;
+  timer_secs: number | null;
+}
+
+export interface RoomWebhooks {
+  secret: string;
+  urls: string[];
+  chat: ChatWebhook | null;
+}
+
+export interface SelectStoryEvent {
+  room_id: string;
+  story_id?: string | null;
+}
+
+export interface ServerRestarting {
+  reconnect_delay_secs: number;
+}
+
+export interface Session {
+  participant_id: string;
+  token: string;
+}
+
+export interface SetChatWebhookEvent {
+  room_id: string;
+  chat?: ChatWebhook | null;
+}
+
+export interface SetSpectatorEvent {
+  room_id: string;
+  is_spectator: boolean;
+}
+
+export interface Story {
+  id: string;
+  title: string;
+  url: string | null;
+  source: StorySource | null;
+  estimate: number | null;
+  rounds: VoteStatistics[];
+  convergence: Convergence;
+}
+
+export type StorySource =
+  | { kind: 'jira'; key: string }
+  | { kind: 'github'; repo: string; number: number; node_id: string };
+
+export interface UpdateProfileEvent {
+  room_id: string;
+  name: string;
+  profile?: Profile;
+}
+
+export interface UpdateSettingsEvent {
+  room_id: string;
+  settings: RoomSettings;
+}
+
+export interface VoteEvent {
+  room_id: string;
+  vote: number;
+}
+
+export interface VoteStatistics {
+  votes: number;
+  abstentions: number;
+  average: number | null;
+  median: number | null;
+  min: number | null;
+  max: number | null;
+  consensus: boolean;
+  distribution: { [key: string]: number };
+  vote_changes: number;
+  estimate: number | null;
+}
+
+export interface ServerToClientEvents {
+  roomCreated: (room: Room) => void;
+  playerJoined: (roomDelta: RoomDelta) => void;
+  playerVoted: (roomDelta: RoomDelta) => void;
+  voteRetracted: (roomDelta: RoomDelta) => void;
+  spectatorChanged: (roomDelta: RoomDelta) => void;
+  profileUpdated: (roomDelta: RoomDelta) => void;
+  cardsRevealed: (roomDelta: RoomDelta) => void;
+  votesReset: (roomDelta: RoomDelta) => void;
+  playerDisconnected: (roomDelta: RoomDelta) => void;
+  newHostElected: (data: string) => void;
+  roomNotFound: () => void;
+  moveToRoom: (data: string) => void;
+  roomClosed: (data: string) => void;
+  maintenanceNotice: (data: string) => void;
+  serverRestarting: (serverRestarting: ServerRestarting) => void;
+  storiesUpdated: (roomDelta: RoomDelta) => void;
+  settingsUpdated: (roomDelta: RoomDelta) => void;
+  revoteStarted: (roomDelta: RoomDelta) => void;
+  estimateAccepted: (roomDelta: RoomDelta) => void;
+  allVoted: (allVoted: AllVoted) => void;
+  webhooksUpdated: (roomWebhooks: RoomWebhooks) => void;
+  roomSnapshot: (room: Room) => void;
+  invalidMessage: (data: string) => void;
+  actionRejected: (actionRejected: ActionRejected) => void;
+  session: (session: Session) => void;
+}
+
+export interface ClientToServerEvents {
+  createRoom: (payload: CreateRoomEvent) => void;
+  joinRoom: (payload: JoinRoomEvent) => void;
+  joinTeamRoom: (payload: JoinTeamRoomEvent) => void;
+  vote: (payload: VoteEvent) => void;
+  retractVote: (payload: RetractVoteEvent) => void;
+  setSpectator: (payload: SetSpectatorEvent) => void;
+  updateProfile: (payload: UpdateProfileEvent) => void;
+  revealCards: (payload: RevealCardsEvent) => void;
+  resetVotes: (payload: ResetVotesEvent) => void;
+  exitRoom: (payload: PlayerExitEvent) => void;
+  resync: (payload: ResyncEvent) => void;
+  importJiraIssues: (payload: ImportJiraIssuesEvent) => void;
+  importGitHubIssues: (payload: ImportGitHubIssuesEvent) => void;
+  selectStory: (payload: SelectStoryEvent) => void;
+  addWebhook: (payload: AddWebhookEvent) => void;
+  removeWebhook: (payload: RemoveWebhookEvent) => void;
+  setChatWebhook: (payload: SetChatWebhookEvent) => void;
+  updateSettings: (payload: UpdateSettingsEvent) => void;
+  revote: (payload: RevoteEvent) => void;
+  acceptEstimate: (payload: AcceptEstimateEvent) => void;
+}
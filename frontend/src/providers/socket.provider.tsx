@@ -1,15 +1,64 @@
-import { useCallback, useEffect, useMemo, useState } from 'react';
+import { useCallback, useEffect, useMemo, useRef, useState } from 'react';
 
 import { useNavigate } from '@tanstack/react-router';
 import { io, type Socket } from 'socket.io-client';
 
 import type {
+  Account,
   ClientToServerEvents,
+  EstimateChoice,
   Player,
+  Profile,
   Room,
+  RoomDelta,
   ServerToClientEvents,
 } from '../types';
 import { SocketContext } from '../contexts/socket.context';
+import { toaster } from '../contexts/toaster.context';
+import { PROTOCOL_VERSION } from '../protocol';
+
+/** Where the resume token is kept, per tab so each tab is its own participant */
+const SESSION_KEY = 'storypoint-session';
+
+/** Applies a delta's changes to the room it follows on from */
+function applyDelta(room: Room, delta: RoomDelta): Room {
+  const next: Room = { ...room, players: { ...room.players }, seq: delta.seq };
+  for (const change of delta.changes) {
+    switch (change.op) {
+      case 'playerAdded':
+      case 'playerUpdated':
+        next.players[change.player.id] = change.player;
+        break;
+      case 'playerRemoved':
+        next.players = Object.fromEntries(
+          Object.entries(next.players).filter(
+            ([id]) => id !== change.player_id,
+          ),
+        );
+        break;
+      case 'roomUpdated':
+        next.cards_revealed = change.cards_revealed;
+        next.host_id = change.host_id;
+        next.current_story = change.current_story;
+        next.estimate = change.estimate;
+        break;
+      case 'settingsUpdated':
+        next.settings = change.settings;
+        break;
+      case 'resultsUpdated':
+        next.results = change.results;
+        break;
+      case 'storiesUpdated':
+        next.stories = change.stories;
+        break;
+      case 'roundsUpdated':
+        next.rounds = change.rounds;
+        next.convergence = change.convergence;
+        break;
+    }
+  }
+  return next;
+}
 
 function SocketProvider({ children }: Readonly<{ children: React.ReactNode }>) {
   const navigate = useNavigate();
@@ -19,18 +68,41 @@ function SocketProvider({ children }: Readonly<{ children: React.ReactNode }>) {
   > | null>(null);
 
   const [room, setRoom] = useState<Room | undefined>(undefined);
+  // deltas arrive faster than renders, so the latest room is kept in a ref
+  const roomRef = useRef<Room | undefined>(undefined);
+  // the participant ID the server knows this client by, stable across reconnects
+  const participantRef = useRef<string | undefined>(undefined);
+  const updateRoom = useCallback((room: Room | undefined) => {
+    roomRef.current = room;
+    setRoom(room);
+  }, []);
   const [me, setMe] = useState<Player | undefined>(undefined);
   const [error, setError] = useState<string | undefined>(undefined);
+  const [account, setAccount] = useState<Account | undefined>(undefined);
+  // logged in players join under their verified name whatever they typed
+  const newMe = useCallback(
+    (name: string, isSpectator: boolean): Player => ({
+      id: participantRef.current ?? '',
+      name: account?.user?.name ?? name,
+      vote: null,
+      has_voted: false,
+      is_spectator: isSpectator,
+      verified: !!account?.user,
+      avatar_url: account?.user?.picture ?? null,
+      profile: { emoji: null, color: null, role: null },
+    }),
+    [account],
+  );
+  const logout = useCallback(() => {
+    // reconnecting is what drops the login from the socket, so reload
+    fetch('/auth/logout', { method: 'POST' }).finally(() =>
+      window.location.reload(),
+    );
+  }, []);
   const joinRoom = useCallback(
     (roomId: string, name: string, isSpectator: boolean) => {
       if (socket) {
-        setMe({
-          id: socket.id ?? '',
-          name,
-          vote: null,
-          has_voted: false,
-          is_spectator: isSpectator,
-        });
+        setMe(newMe(name, isSpectator));
         socket.emit('joinRoom', {
           room_id: roomId,
           name,
@@ -38,26 +110,40 @@ function SocketProvider({ children }: Readonly<{ children: React.ReactNode }>) {
         });
       }
     },
-    [socket],
+    [socket, newMe],
   );
-  const createRoom = useCallback(
-    (name: string, isSpectator: boolean, cardSet: string) => {
+
+  const joinTeamRoom = useCallback(
+    (teamId: string, name: string, isSpectator: boolean) => {
       if (socket) {
-        setMe({
-          id: socket.id ?? '',
+        setMe(newMe(name, isSpectator));
+        socket.emit('joinTeamRoom', {
+          team_id: teamId,
           name,
-          vote: null,
-          has_voted: false,
           is_spectator: isSpectator,
         });
+      }
+    },
+    [socket, newMe],
+  );
+  const createRoom = useCallback(
+    (
+      name: string,
+      isSpectator: boolean,
+      cardSet: string,
+      requireLogin: boolean,
+    ) => {
+      if (socket) {
+        setMe(newMe(name, isSpectator));
         socket.emit('createRoom', {
           name,
           is_spectator: isSpectator,
           card_set: cardSet,
+          require_login: requireLogin,
         });
       }
     },
-    [socket],
+    [socket, newMe],
   );
   const revealCards = useCallback(
     (roomId: string) => {
@@ -75,6 +161,49 @@ function SocketProvider({ children }: Readonly<{ children: React.ReactNode }>) {
     },
     [socket],
   );
+  const revote = useCallback(
+    (roomId: string) => {
+      if (socket) {
+        socket.emit('revote', { room_id: roomId });
+      }
+    },
+    [socket],
+  );
+  const retractVote = useCallback(
+    (roomId: string) => {
+      if (socket) {
+        socket.emit('retractVote', { room_id: roomId });
+      }
+    },
+    [socket],
+  );
+  const setSpectator = useCallback(
+    (roomId: string, isSpectator: boolean) => {
+      if (socket) {
+        socket.emit('setSpectator', {
+          room_id: roomId,
+          is_spectator: isSpectator,
+        });
+      }
+    },
+    [socket],
+  );
+  const updateProfile = useCallback(
+    (roomId: string, name: string, profile: Profile) => {
+      if (socket) {
+        socket.emit('updateProfile', { room_id: roomId, name, profile });
+      }
+    },
+    [socket],
+  );
+  const acceptEstimate = useCallback(
+    (roomId: string, estimate: EstimateChoice, advance: boolean) => {
+      if (socket) {
+        socket.emit('acceptEstimate', { room_id: roomId, estimate, advance });
+      }
+    },
+    [socket],
+  );
   const vote = useCallback(
     (roomId: string, vote: number) => {
       if (socket) {
@@ -97,33 +226,64 @@ function SocketProvider({ children }: Readonly<{ children: React.ReactNode }>) {
       room,
       me,
       setMe,
+      account,
+      logout,
       error,
       setError,
       joinRoom,
+      joinTeamRoom,
       exitRoom,
       createRoom,
       revealCards,
       resetVotes,
+      revote,
+      acceptEstimate,
       vote,
+      retractVote,
+      setSpectator,
+      updateProfile,
     }),
     [
       room,
       me,
       setMe,
+      account,
+      logout,
       error,
       setError,
       joinRoom,
+      joinTeamRoom,
       exitRoom,
       createRoom,
       revealCards,
       resetVotes,
+      revote,
+      acceptEstimate,
       vote,
+      retractVote,
+      setSpectator,
+      updateProfile,
     ],
   );
 
+  useEffect(() => {
+    // the login cookie is only sent to the server the page was loaded from
+    fetch('/auth/me')
+      .then((response) => (response.ok ? response.json() : undefined))
+      .then(setAccount)
+      .catch(() => setAccount(undefined));
+  }, []);
+
   useEffect(() => {
     const socket: Socket<ServerToClientEvents, ClientToServerEvents> = io(
       import.meta.env.VITE_SOCKET_URL,
+      {
+        auth: (cb) =>
+          cb({
+            protocol: PROTOCOL_VERSION,
+            session: sessionStorage.getItem(SESSION_KEY),
+          }),
+      },
     );
     setSocket(socket);
 
@@ -131,43 +291,110 @@ function SocketProvider({ children }: Readonly<{ children: React.ReactNode }>) {
       console.log('Connected to server with ID:', socket.id);
     });
 
-    socket.on('roomCreated', (room) => {
-      setRoom(room);
-      navigate({
-        to: '/room/$roomId',
-        params: { roomId: room.id },
-      });
+    socket.on('session', ({ participant_id, token }) => {
+      sessionStorage.setItem(SESSION_KEY, token);
+      participantRef.current = participant_id;
+      setMe((prevMe) => (prevMe ? { ...prevMe, id: participant_id } : prevMe));
     });
 
-    socket.on('playerJoined', (room) => {
-      setRoom(room);
+    socket.on('connect_error', (err) => {
+      // the server refused the connection, e.g. an unsupported protocol version
+      if (!socket.active) {
+        setError(err.message);
+      }
     });
 
-    socket.on('roomState', (room) => {
-      setRoom(room);
+    socket.on('roomCreated', (room) => {
+      updateRoom(room);
       navigate({
         to: '/room/$roomId',
         params: { roomId: room.id },
       });
     });
 
-    socket.on('playerVoted', (room) => {
-      setRoom(room);
+    // deltas must be applied in order, on a gap ask for the whole room
+    const onDelta = (delta: RoomDelta) => {
+      const current = roomRef.current;
+      if (
+        !current ||
+        current.id !== delta.room_id ||
+        delta.seq <= current.seq
+      ) {
+        return;
+      }
+      if (delta.seq !== current.seq + 1) {
+        socket.emit('resync', { room_id: delta.room_id });
+        return;
+      }
+      updateRoom(applyDelta(current, delta));
+    };
+
+    socket.on('roomSnapshot', (room) => {
+      updateRoom(room);
+    });
+
+    socket.on('playerJoined', onDelta);
+
+    socket.on('playerVoted', onDelta);
+
+    socket.on('voteRetracted', onDelta);
+
+    socket.on('profileUpdated', (delta) => {
+      for (const change of delta.changes) {
+        if (
+          change.op === 'playerUpdated' &&
+          change.player.id === participantRef.current
+        ) {
+          const { name, profile } = change.player;
+          setMe((prevMe) => (prevMe ? { ...prevMe, name, profile } : prevMe));
+        }
+      }
+      onDelta(delta);
     });
 
-    socket.on('cardsRevealed', (room) => {
-      setRoom(room);
+    socket.on('spectatorChanged', (delta) => {
+      for (const change of delta.changes) {
+        if (
+          change.op === 'playerUpdated' &&
+          change.player.id === participantRef.current
+        ) {
+          const { is_spectator, has_voted } = change.player;
+          setMe((prevMe) =>
+            prevMe
+              ? {
+                  ...prevMe,
+                  is_spectator,
+                  has_voted,
+                  vote: is_spectator ? null : prevMe.vote,
+                }
+              : prevMe,
+          );
+        }
+      }
+      onDelta(delta);
     });
 
-    socket.on('votesReset', (room) => {
+    socket.on('cardsRevealed', onDelta);
+
+    socket.on('storiesUpdated', onDelta);
+
+    socket.on('settingsUpdated', onDelta);
+
+    const onNewRound = (delta: RoomDelta) => {
       setMe((prevMe) => {
         if (prevMe) {
           return { ...prevMe, vote: null, has_voted: false };
         }
         return prevMe;
       });
-      setRoom(room);
-    });
+      onDelta(delta);
+    };
+
+    socket.on('votesReset', onNewRound);
+
+    socket.on('revoteStarted', onNewRound);
+
+    socket.on('estimateAccepted', onDelta);
 
     socket.on('newHostElected', (newHostId) => {
       setMe((prevMe) => {
@@ -176,12 +403,9 @@ function SocketProvider({ children }: Readonly<{ children: React.ReactNode }>) {
         }
         return prevMe;
       });
-      setRoom((prevRoom) => {
-        if (prevRoom) {
-          return { ...prevRoom, host_id: newHostId };
-        }
-        return prevRoom;
-      });
+      if (roomRef.current) {
+        updateRoom({ ...roomRef.current, host_id: newHostId });
+      }
     });
 
     socket.on('roomNotFound', () => {
@@ -189,9 +413,7 @@ function SocketProvider({ children }: Readonly<{ children: React.ReactNode }>) {
       navigate({ to: '/' });
     });
 
-    socket.on('playerDisconnected', (room) => {
-      setRoom(room);
-    });
+    socket.on('playerDisconnected', onDelta);
 
     socket.on('moveToRoom', (roomId) => {
       navigate({
@@ -200,10 +422,107 @@ function SocketProvider({ children }: Readonly<{ children: React.ReactNode }>) {
       });
     });
 
+    socket.on('roomClosed', (reason) => {
+      updateRoom(undefined);
+      setError(reason);
+      navigate({ to: '/' });
+    });
+
+    socket.on('allVoted', ({ reveal_in_secs }) => {
+      toaster.info({
+        title: 'Everyone has voted',
+        description:
+          reveal_in_secs === null
+            ? 'Waiting for the host to reveal the cards'
+            : `Revealing the cards in ${reveal_in_secs} seconds`,
+      });
+    });
+
+    socket.on('maintenanceNotice', (message) => {
+      setError(message);
+    });
+
+    socket.on('serverRestarting', ({ reconnect_delay_secs }) => {
+      setError(
+        `The server is restarting, please rejoin in ${reconnect_delay_secs} seconds`,
+      );
+      // once reconnected the player needs to rejoin the room
+      socket.once('connect', () => updateRoom(undefined));
+    });
+
+    socket.on('actionRejected', (rejection) => {
+      switch (rejection.reason) {
+        case 'tooManyRooms':
+          setError('The server is busy, please try again later');
+          break;
+        case 'roomFull':
+          setError(`Sorry, that room is full (${rejection.max} players)`);
+          break;
+        case 'invalidName':
+          setError(
+            `Names must be between 1 and ${rejection.max_length} characters`,
+          );
+          break;
+        case 'serverDraining':
+          setError('The server is restarting, please try again shortly');
+          break;
+        case 'invalidWebhook':
+          setError(rejection.message);
+          break;
+        case 'tooManyWebhooks':
+          setError(`Rooms can have at most ${rejection.max} webhooks`);
+          break;
+        case 'tooManyStories':
+          setError(`Rooms can have at most ${rejection.max} stories`);
+          break;
+        case 'integrationFailed':
+          setError(rejection.message);
+          break;
+        case 'loginRequired':
+          setError('This room is only open to logged in players');
+          break;
+        case 'invalidSettings':
+          setError(rejection.message);
+          break;
+        case 'spectatorsNotAllowed':
+          setError('This room does not allow spectators');
+          break;
+        case 'votingClosed':
+          setError('The cards are revealed, votes can no longer change');
+          break;
+        case 'quorumNotMet':
+          setError(
+            `${rejection.votes} of ${rejection.required} votes are needed to reveal`,
+          );
+          break;
+        case 'notRevealed':
+          setError('The cards have not been revealed yet');
+          break;
+        case 'alreadyRevealed':
+          setError('The cards are already revealed');
+          break;
+        case 'noEstimate':
+          setError('There is no agreed estimate to accept');
+          break;
+        case 'invalidEstimate':
+          setError('That card is not an estimate');
+          break;
+        case 'invalidProfile':
+          setError(rejection.message);
+          break;
+        case 'invalidVote':
+          setError('That card is not in this room');
+          break;
+        case 'spectatorCannotVote':
+          setError('Switch to voting to pick a card');
+          break;
+      }
+    });
+
     return () => {
       socket?.disconnect();
     };
-  }, [navigate]);
+  }, [navigate, updateRoom]);
 
   return (
     <SocketContext.Provider value={value}>{children}</SocketContext.Provider>
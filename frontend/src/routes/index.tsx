@@ -23,7 +23,8 @@ export const Route = createFileRoute('/')({
 });
 
 function Index() {
-  const { error, joinRoom, createRoom, setError } = useSocket();
+  const { error, joinRoom, createRoom, setError, account, logout } =
+    useSocket();
 
   const [createJoin, setCreateJoin] = useState('create');
 
@@ -40,6 +41,16 @@ function Index() {
     <>
       <div className="absolute w-full top-4 px-4 flex flex-row-reverse">
         <Lightswitch className="btn pl-2 pr-0 py-1 md:pl-4 md:py-2" />
+        {account?.enabled &&
+          (account.user ? (
+            <button type="button" className="mr-4" onClick={logout}>
+              Log out {account.user.name}
+            </button>
+          ) : (
+            <a href="/auth/login?return_to=/" className="mr-4">
+              Log in
+            </a>
+          ))}
         <Tooltip>
           <TooltipTrigger asChild>
             <a
@@ -92,7 +103,11 @@ function Index() {
           </Tabs.Content>
 
           <Tabs.Content value="create" className="flex justify-center">
-            <CreateRoomForm onCreate={createRoom} />
+            <CreateRoomForm
+              key={account?.user?.sub}
+              onCreate={createRoom}
+              account={account}
+            />
           </Tabs.Content>
         </Tabs>
       </div>
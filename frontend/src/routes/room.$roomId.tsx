@@ -35,36 +35,65 @@ export const Route = createFileRoute('/room/$roomId')({
 });
 
 function Room() {
-  const { room, me, setMe, revealCards, resetVotes, vote, joinRoom, exitRoom } =
-    useSocket();
+  const {
+    room,
+    me,
+    setMe,
+    revealCards,
+    resetVotes,
+    revote,
+    acceptEstimate,
+    vote,
+    retractVote,
+    setSpectator,
+    joinRoom,
+    exitRoom,
+  } = useSocket();
   const { roomId } = Route.useParams();
   const navigate = useNavigate();
   const isHost = useMemo(() => me?.id === room?.host_id, [me, room]);
   const [showConfetti, setShowConfetti] = useState(false);
+  // revealed votes come from the results, anonymous rooms never show them per player
   const votes = useMemo(() => {
-    return room?.players
-      ? Object.values(room.players)
-          .filter((p) => !p.is_spectator)
-          .map((p) => p.vote ?? 0)
+    return room?.results
+      ? Object.entries(room.results.distribution).flatMap(([vote, count]) =>
+          Array<number>(count).fill(Number(vote)),
+        )
       : [];
-  }, [room]);
+  }, [room?.results]);
   const isRevealed = useMemo(
     () => room?.cards_revealed ?? false,
     [room?.cards_revealed],
   );
-  const hasSomeVoted = useMemo(
-    () =>
-      room?.players
-        ? Object.values(room.players)
-            .filter((p) => !p.is_spectator)
-            .some((p) => p.has_voted)
-        : false,
-    [room?.players],
-  );
+  // mirrors the server's reveal quorum so the host only offers a reveal it accepts
+  const quorumMet = useMemo(() => {
+    if (!room) {
+      return false;
+    }
+    const voters = Object.values(room.players).filter((p) => !p.is_spectator);
+    const votes = voters.filter((p) => p.has_voted).length;
+    const quorum = room.settings.reveal_quorum;
+    const required =
+      quorum.rule === 'hostOverride'
+        ? 0
+        : Math.max(
+            1,
+            Math.min(
+              voters.length,
+              quorum.rule === 'minVotes'
+                ? quorum.votes
+                : Math.ceil((voters.length * quorum.percent) / 100),
+            ),
+          );
+    return votes >= required;
+  }, [room]);
 
   const cardSet = useMemo(
-    () => (room?.card_set ? CardSet[room.card_set] : CardSet.fibonacci),
-    [room?.card_set],
+    () =>
+      room?.settings.card_set
+        ? CardSet[room.settings.card_set]
+        : CardSet.fibonacci,
+    [room?.settings.card_set],
   );
 
   const size = useBreakpoints();
@@ -72,22 +101,8 @@ function Room() {
   const [containerRef, { x: xOffset, y: yOffset }] = useElementCenter();
 
   useEffect(() => {
-    const filteredPlayers = Object.values(room?.players ?? {}).filter(
-      (p) => !p.is_spectator && p.vote !== 0,
-    );
-    const agreement =
-      isRevealed && room
-        ? filteredPlayers.length > 0 &&
-          filteredPlayers.every(
-            (p) =>
-              p.vote ===
-              Object.values(room.players).find(
-                (p) => !p.is_spectator && p.vote !== 0,
-              )?.vote,
-          )
-        : false;
-    setShowConfetti(agreement);
-  }, [room, setShowConfetti, isRevealed]);
+    setShowConfetti(isRevealed && (room?.results?.consensus ?? false));
+  }, [room?.results, setShowConfetti, isRevealed]);
 
   const players = room ? Object.values(room.players) : [];
   const numPlayers = players.length;
@@ -110,6 +125,11 @@ function Room() {
               navigate({ to: '/' });
             }
           }}
+          toggleSpectator={() => {
+            if (room && me) {
+              setSpectator(room.id, !me.is_spectator);
+            }
+          }}
         />
 
         {/* Circular Player Card Layout */}
@@ -123,10 +143,14 @@ function Room() {
             isRevealed={isRevealed}
             showHostControls={isHost}
             votes={votes}
-            hasSomeVoted={hasSomeVoted}
+            quorumMet={quorumMet}
             cardSet={cardSet}
             onVotesRevealed={() => revealCards(room?.id ?? '')}
             onVotesReset={() => resetVotes(room?.id ?? '')}
+            onRevote={() => revote(room?.id ?? '')}
+            onEstimateAccepted={() =>
+              acceptEstimate(room?.id ?? '', { from: 'median' }, true)
+            }
           />
           {/* Player Cards positioned in a circle */}
           {players.map((player, index) => {
@@ -151,8 +175,16 @@ function Room() {
                 vote={Object.keys(cardSet).find(
                   (key) => cardSet[key] === player.vote,
                 )}
-                color={COLORS[Math.abs(hash(player.id)) % COLORS.length]}
+                color={
+                  player.profile.color
+                    ? COLORS[(player.profile.color - 1) % COLORS.length]
+                    : COLORS[Math.abs(hash(player.id)) % COLORS.length]
+                }
                 isSpectator={player.is_spectator}
+                verified={player.verified}
+                avatarUrl={player.avatar_url}
+                emoji={player.profile.emoji}
+                role={player.profile.role}
               />
             );
           })}
@@ -172,6 +204,15 @@ function Room() {
               });
               vote(room?.id ?? '', selectedVote);
             }}
+            onVoteRetracted={() => {
+              setMe((prevMe) => {
+                if (prevMe) {
+                  return { ...prevMe, vote: null, has_voted: false };
+                }
+                return prevMe;
+              });
+              retractVote(room?.id ?? '');
+            }}
           />
         ) : (
           <div className="hidden md:block h-24" />
@@ -0,0 +1,19 @@
+import { createFileRoute } from '@tanstack/react-router';
+
+import { useSocket } from '../hooks/socket.hook';
+import { JoinRoomDialog } from '../components/join-room-dialog.component';
+
+export const Route = createFileRoute('/team/$teamId')({
+  component: Team,
+});
+
+/**
+ * A team's stable link, joining opens the team's room if it has none
+ * and the server then moves the player into it
+ */
+function Team() {
+  const { joinTeamRoom } = useSocket();
+  const { teamId } = Route.useParams();
+
+  return <JoinRoomDialog open roomId={teamId} onJoin={joinTeamRoom} />;
+}
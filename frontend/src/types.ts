@@ -2,6 +2,8 @@ import { type Dispatch, type SetStateAction } from 'react';
 
 import type { Placement } from '@floating-ui/react';
 
+import type { EstimateChoice, Player, Profile, Room } from './protocol';
+
 export interface DialogOptions {
   initialOpen?: boolean;
   open?: boolean;
@@ -15,74 +17,48 @@ export interface TooltipOptions {
   onOpenChange?: (open: boolean) => void;
 }
 
-export interface Player {
-  id: string;
-  name: string;
-  vote: number | null;
-  has_voted: boolean;
-  is_spectator: boolean;
-}
-
-export interface Room {
-  id: string;
-  host_id: string;
-  players: { [key: string]: Player };
-  cards_revealed: boolean;
-  card_set: string;
-}
+export type * from './protocol';
 
-export interface ServerToClientEvents {
-  connect: () => void;
-  disconnect: () => void;
-  roomCreated: (room: Room) => void;
-  playerJoined: (room: Room) => void;
-  roomState: (room: Room) => void;
-  playerVoted: (room: Room) => void;
-  cardsRevealed: (room: Room) => void;
-  votesReset: (room: Room) => void;
-  newHostElected: (newHostId: string) => void;
-  roomNotFound: () => void;
-  playerDisconnected: (room: Room) => void;
-  moveToRoom: (roomId: string) => void;
-}
-
-export interface ClientToServerEvents {
-  createRoom: ({
-    name,
-    is_spectator,
-    card_set,
-  }: {
-    name: string;
-    is_spectator: boolean;
-    card_set: string;
-  }) => void;
-  joinRoom: ({
-    room_id,
-    name,
-    is_spectator,
-  }: {
-    room_id: string;
+/** Whether the server has login enabled and who is logged in, from `/auth/me` */
+export interface Account {
+  enabled: boolean;
+  user: {
+    sub: string;
     name: string;
-    is_spectator: boolean;
-  }) => void;
-  exitRoom: ({ room_id }: { room_id: string }) => void;
-  vote: ({ room_id, vote }: { room_id: string; vote: number }) => void;
-  revealCards: ({ room_id }: { room_id: string }) => void;
-  resetVotes: ({ room_id }: { room_id: string }) => void;
+    picture: string | null;
+    exp: number;
+  } | null;
 }
 
 export interface AppState {
   room?: Room;
   me?: Player;
   setMe: Dispatch<SetStateAction<Player | undefined>>;
+  account?: Account;
+  logout: () => void;
   error?: string;
   setError: Dispatch<SetStateAction<string | undefined>>;
   joinRoom: (roomId: string, name: string, isSpectator: boolean) => void;
+  joinTeamRoom: (teamId: string, name: string, isSpectator: boolean) => void;
   exitRoom: (roomId: string) => void;
-  createRoom: (name: string, isSpectator: boolean, cardSet: string) => void;
+  createRoom: (
+    name: string,
+    isSpectator: boolean,
+    cardSet: string,
+    requireLogin: boolean,
+  ) => void;
   revealCards: (roomId: string) => void;
   resetVotes: (roomId: string) => void;
+  revote: (roomId: string) => void;
+  acceptEstimate: (
+    roomId: string,
+    estimate: EstimateChoice,
+    advance: boolean,
+  ) => void;
   vote: (roomId: string, vote: number) => void;
+  retractVote: (roomId: string) => void;
+  setSpectator: (roomId: string, isSpectator: boolean) => void;
+  updateProfile: (roomId: string, name: string, profile: Profile) => void;
 }
 
 export const CardSet: Record<string, Record<string, number>> = {
@@ -0,0 +1,269 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+use std::sync::Arc;
+
+use axum::{
+    Extension, Json,
+    body::Body,
+    extract::{Path, State},
+    http::{Request, StatusCode, header::AUTHORIZATION},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use socketioxide::SocketIo;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    handlers::{emit_event_global, notify_room_closed},
+    types::{AppState, MaintenanceNoticeEvent},
+};
+
+/// Summary of a live room for operators
+#[derive(Debug, Serialize)]
+struct RoomInfo {
+    id: Uuid,
+    players: usize,
+    spectators: usize,
+    host_name: Option<String>,
+    created_at: DateTime<Utc>,
+    age_secs: i64,
+    last_activity: DateTime<Utc>,
+}
+
+/// Body for closing a room
+#[derive(Debug, Default, Deserialize)]
+pub struct CloseRoomRequest {
+    /// Message shown to the players in the room
+    pub reason: Option<String>,
+}
+
+/// Body for broadcasting a maintenance message
+#[derive(Debug, Deserialize)]
+pub struct BroadcastRequest {
+    /// Message shown to every connected player
+    pub message: String,
+}
+
+/// Checks a bearer token against the expected token.
+/// Compares in constant time so the token cannot be guessed byte by byte.
+pub fn token_matches(provided: &str, expected: &str) -> bool {
+    provided.len() == expected.len()
+        && provided
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+/// Middleware guarding operator endpoints with the admin bearer token.
+/// - Responds 404 when no token is configured so the endpoints stay hidden.
+/// - Responds 401 when the token is missing or wrong.
+pub async fn require_admin(
+    State(app_state): State<Arc<AppState>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let Some(expected) = app_state.config.admin_token.as_deref() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let authorized = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .is_some_and(|provided| token_matches(provided, expected));
+
+    if authorized {
+        next.run(req).await
+    } else {
+        error!("Unauthorized admin request to {}", req.uri());
+        StatusCode::UNAUTHORIZED.into_response()
+    }
+}
+
+/// Lists every live room with its size, host and activity
+pub async fn list_rooms(State(app_state): State<Arc<AppState>>) -> impl IntoResponse {
+    let now = Utc::now();
+    let mut rooms: Vec<RoomInfo> = app_state
+        .rooms
+        .lock()
+        .await
+        .values()
+        .map(|room| {
+            let spectators = room.players.values().filter(|p| p.is_spectator).count();
+            RoomInfo {
+                id: room.id,
+                players: room.players.len() - spectators,
+                spectators,
+                host_name: room.players.get(&room.host_id).map(|p| p.name.clone()),
+                created_at: room.created_at,
+                age_secs: (now - room.created_at).num_seconds(),
+                last_activity: room.last_activity,
+            }
+        })
+        .collect();
+    rooms.sort_by_key(|room| room.created_at);
+
+    Json(rooms)
+}
+
+/// Force closes a room.
+/// - Removes the room from the state.
+/// - Notifies its players with a "roomClosed" event.
+pub async fn close_room(
+    State(app_state): State<Arc<AppState>>,
+    Extension(io): Extension<SocketIo>,
+    Path(room_id): Path<Uuid>,
+    body: Option<Json<CloseRoomRequest>>,
+) -> StatusCode {
+    let Json(body) = body.unwrap_or_default();
+
+    if app_state.rooms.lock().await.remove(&room_id).is_none() {
+        return StatusCode::NOT_FOUND;
+    }
+    info!("Room {} closed by an operator", room_id);
+
+    let reason = body
+        .reason
+        .unwrap_or_else(|| "Room closed by an administrator".to_owned());
+    notify_room_closed(&io, &app_state.hub, room_id, &reason).await;
+
+    StatusCode::NO_CONTENT
+}
+
+/// Sends a maintenance message to every connected socket
+pub async fn broadcast(
+    State(app_state): State<Arc<AppState>>,
+    Extension(io): Extension<SocketIo>,
+    Json(body): Json<BroadcastRequest>,
+) -> StatusCode {
+    if body.message.trim().is_empty() {
+        return StatusCode::UNPROCESSABLE_ENTITY;
+    }
+    info!("Broadcasting maintenance notice: {}", body.message);
+    emit_event_global::<MaintenanceNoticeEvent>(&io, &app_state.hub, &body.message).await;
+
+    StatusCode::NO_CONTENT
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{Router, routing::get};
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{
+        config::Config,
+        test_support::{app_state, io, room},
+    };
+
+    #[test]
+    fn token_matches_only_the_exact_token() {
+        assert!(token_matches("operator-token-1", "operator-token-1"));
+        assert!(!token_matches("operator-token-2", "operator-token-1"));
+        assert!(!token_matches("operator-token", "operator-token-1"));
+        assert!(!token_matches("", "operator-token-1"));
+    }
+
+    /// The status of a request to a route behind `require_admin`
+    async fn guarded_status(admin_token: Option<&str>, authorization: Option<&str>) -> StatusCode {
+        let app_state = app_state(Config {
+            admin_token: admin_token.map(str::to_owned),
+            ..Config::default()
+        });
+        let routes = Router::new()
+            .route("/admin/rooms", get(list_rooms))
+            .layer(axum::middleware::from_fn_with_state(
+                Arc::clone(&app_state),
+                require_admin,
+            ))
+            .with_state(app_state);
+        let mut req = Request::get("/admin/rooms");
+        if let Some(authorization) = authorization {
+            req = req.header(AUTHORIZATION, authorization);
+        }
+        let req = req.body(Body::empty()).expect("request builds");
+        routes
+            .oneshot(req)
+            .await
+            .expect("routes are infallible")
+            .status()
+    }
+
+    #[tokio::test]
+    async fn require_admin_checks_the_bearer_token() {
+        let token = Some("operator-token-1");
+
+        assert_eq!(
+            guarded_status(None, Some("Bearer operator-token-1")).await,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(guarded_status(token, None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            guarded_status(token, Some("operator-token-1")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            guarded_status(token, Some("Bearer wrong-token-xx")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            guarded_status(token, Some("Bearer operator-token-1")).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn close_room_removes_the_room_and_tells_its_players() {
+        let app_state = app_state(Config::default());
+        let room = room(&["host"]);
+        let room_id = room.id;
+        app_state.rooms.lock().await.insert(room_id, room);
+        let mut receiver = app_state.hub.subscribe(room_id);
+
+        let body = CloseRoomRequest {
+            reason: Some("Moving to a new server".to_owned()),
+        };
+        let status = close_room(
+            State(Arc::clone(&app_state)),
+            Extension(io()),
+            Path(room_id),
+            Some(Json(body)),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert!(app_state.rooms.lock().await.is_empty());
+        let message = receiver.recv().await.expect("room is told");
+        assert_eq!(message.event, "roomClosed");
+        assert_eq!(message.payload, json!("Moving to a new server"));
+
+        let status = close_room(State(app_state), Extension(io()), Path(room_id), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn broadcast_sends_non_empty_notices_to_every_room() {
+        let app_state = app_state(Config::default());
+        let mut receiver = app_state.hub.subscribe(Uuid::new_v4());
+
+        let body = BroadcastRequest {
+            message: "  ".to_owned(),
+        };
+        let status = broadcast(State(Arc::clone(&app_state)), Extension(io()), Json(body)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let body = BroadcastRequest {
+            message: "Restarting at noon".to_owned(),
+        };
+        let status = broadcast(State(app_state), Extension(io()), Json(body)).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let message = receiver.recv().await.expect("room is told");
+        assert_eq!(message.event, "maintenanceNotice");
+        assert_eq!(message.payload, json!("Restarting at noon"));
+    }
+}
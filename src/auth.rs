@@ -0,0 +1,670 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::{
+        HeaderMap, HeaderValue, StatusCode,
+        header::{COOKIE, SET_COOKIE},
+    },
+    response::{AppendHeaders, IntoResponse, Redirect, Response},
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use sha2::{Digest, Sha256};
+use tokio::sync::OnceCell;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    config::Config,
+    integrations::{IntegrationError, check_status},
+    types::AppState,
+};
+
+/// Cookie holding a user's signed login
+pub const SESSION_COOKIE: &str = "sps_session";
+/// Cookie holding the state of a login in progress
+const LOGIN_COOKIE: &str = "sps_login";
+/// How long a user has to complete a login at the provider
+const LOGIN_TTL: Duration = Duration::from_mins(10);
+
+/// A user who logged in through the OIDC provider
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AuthUser {
+    /// Subject identifier, unique for the user at the provider
+    pub sub: String,
+    /// Display name from the provider
+    pub name: String,
+    /// Avatar picture URL from the provider, if any
+    pub picture: Option<String>,
+    /// When the login expires, as a unix timestamp
+    pub exp: i64,
+}
+
+/// A login in progress, kept in a signed cookie until the provider redirects back
+#[derive(Debug, Deserialize, Serialize)]
+struct PendingLogin {
+    state: String,
+    verifier: String,
+    return_to: String,
+    exp: i64,
+}
+
+/// The provider endpoints from its discovery document
+#[derive(Debug, Deserialize)]
+#[allow(
+    clippy::struct_field_names,
+    reason = "field names come from the OIDC discovery document"
+)]
+struct Discovery {
+    authorization_endpoint: String,
+    token_endpoint: String,
+    userinfo_endpoint: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct UserInfo {
+    sub: String,
+    name: Option<String>,
+    preferred_username: Option<String>,
+    email: Option<String>,
+    picture: Option<String>,
+}
+
+/// `Login` signs users in through a generic OIDC provider
+/// with the authorization code flow and PKCE.
+/// Users are read from the provider's userinfo endpoint with the access
+/// token fetched straight from the issuer, so the ID token is not needed.
+#[derive(Debug)]
+pub struct Login {
+    http: Client,
+    issuer: Url,
+    client_id: String,
+    client_secret: Option<String>,
+    redirect_url: Url,
+    scopes: String,
+    /// Secret cookies are signed with
+    key: String,
+    session_ttl: Duration,
+    /// Fetched on first use so the server starts while the provider is down
+    discovery: OnceCell<Discovery>,
+}
+
+impl Login {
+    /// Creates the login client if an issuer is configured
+    pub fn from_config(config: &Config) -> Result<Option<Self>, reqwest::Error> {
+        let settings = &config.oidc;
+        let (Some(issuer), Some(client_id), Some(redirect_url)) = (
+            settings.issuer.clone(),
+            settings.client_id.clone(),
+            settings.redirect_url.clone(),
+        ) else {
+            return Ok(None);
+        };
+
+        let key = settings.session_secret.clone().unwrap_or_else(|| {
+            warn!("SESSION_SECRET is not set, logins will not survive a restart");
+            format!("{}{}", random_token(), random_token())
+        });
+        let http = Client::builder()
+            .timeout(config.integration_timeout)
+            .build()?;
+
+        info!("Login enabled with issuer {}", issuer);
+        Ok(Some(Self {
+            http,
+            issuer,
+            client_id,
+            client_secret: settings.client_secret.clone(),
+            redirect_url,
+            scopes: settings.scopes.clone(),
+            key,
+            session_ttl: settings.session_ttl,
+            discovery: OnceCell::new(),
+        }))
+    }
+
+    /// The logged in user a request's session cookie belongs to.
+    /// Missing, tampered with and expired cookies all mean no user
+    pub fn user(&self, headers: &HeaderMap) -> Option<AuthUser> {
+        let user: AuthUser = self.verify(cookie(headers, SESSION_COOKIE)?)?;
+        (user.exp > Utc::now().timestamp()).then_some(user)
+    }
+
+    async fn discovery(&self) -> Result<&Discovery, IntegrationError> {
+        self.discovery
+            .get_or_try_init(|| async {
+                let url = format!(
+                    "{}/.well-known/openid-configuration",
+                    self.issuer.as_str().trim_end_matches('/')
+                );
+                let response = check_status(self.http.get(url).send().await?).await?;
+                Ok(response.json().await?)
+            })
+            .await
+    }
+
+    /// Exchanges an authorization code for the user it was issued to
+    async fn authenticate(&self, code: &str, verifier: &str) -> Result<AuthUser, IntegrationError> {
+        let discovery = self.discovery().await?;
+
+        let mut request = self.http.post(&discovery.token_endpoint).form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.redirect_url.as_str()),
+            ("client_id", &self.client_id),
+            ("code_verifier", verifier),
+        ]);
+        if let Some(secret) = &self.client_secret {
+            request = request.basic_auth(&self.client_id, Some(secret));
+        }
+        let tokens: TokenResponse = check_status(request.send().await?).await?.json().await?;
+
+        let request = self
+            .http
+            .get(&discovery.userinfo_endpoint)
+            .bearer_auth(&tokens.access_token);
+        let info: UserInfo = check_status(request.send().await?).await?.json().await?;
+
+        let name = info
+            .name
+            .or(info.preferred_username)
+            .or(info.email)
+            .unwrap_or_else(|| info.sub.clone());
+        // the picture is shown to other players, so only web URLs are kept
+        let picture = info.picture.filter(|picture| {
+            Url::parse(picture).is_ok_and(|url| matches!(url.scheme(), "http" | "https"))
+        });
+        Ok(AuthUser {
+            sub: info.sub,
+            name,
+            picture,
+            exp: expires_in(self.session_ttl),
+        })
+    }
+
+    /// The signed value of a user's session cookie
+    pub fn session(&self, user: &AuthUser) -> String {
+        self.sign(user)
+    }
+
+    /// Signs a cookie value, formatted as `<hex payload>.<hex HMAC-SHA256>`
+    fn sign(&self, value: &impl Serialize) -> String {
+        let payload = hex::encode(serde_json::to_vec(value).expect("cookie values serialize"));
+        let signature = self.mac(payload.as_bytes()).finalize().into_bytes();
+        format!("{payload}.{}", hex::encode(signature))
+    }
+
+    /// Reads a cookie value signed by `sign`, comparing signatures in constant time
+    fn verify<T: DeserializeOwned>(&self, value: &str) -> Option<T> {
+        let (payload, signature) = value.split_once('.')?;
+        self.mac(payload.as_bytes())
+            .verify_slice(&hex::decode(signature).ok()?)
+            .ok()?;
+        serde_json::from_slice(&hex::decode(payload).ok()?).ok()
+    }
+
+    fn mac(&self, payload: &[u8]) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.key.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(payload);
+        mac
+    }
+
+    /// Builds a `Set-Cookie` value, marked `Secure` when the server is reached over https
+    fn set_cookie(&self, name: &str, value: &str, max_age: Duration) -> HeaderValue {
+        let secure = if self.redirect_url.scheme() == "https" {
+            "; Secure"
+        } else {
+            ""
+        };
+        HeaderValue::from_str(&format!(
+            "{name}={value}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}{secure}",
+            max_age.as_secs()
+        ))
+        .expect("cookie values are hex")
+    }
+}
+
+/// Finds a cookie in a request's `Cookie` headers
+fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|pair| {
+            let (key, value) = pair.trim().split_once('=')?;
+            (key == name).then_some(value)
+        })
+}
+
+fn random_token() -> String {
+    Uuid::new_v4().simple().to_string()
+}
+
+/// Unix timestamp `ttl` from now
+fn expires_in(ttl: Duration) -> i64 {
+    Utc::now().timestamp() + i64::try_from(ttl.as_secs()).unwrap_or(i64::MAX / 2)
+}
+
+/// Only paths on this server are returned to after a login
+fn local_path(return_to: Option<String>) -> String {
+    return_to
+        .filter(|path| path.starts_with('/') && !path.starts_with("//") && !path.contains('\\'))
+        .unwrap_or_else(|| "/".to_owned())
+}
+
+/// Query for starting a login
+#[derive(Debug, Deserialize)]
+pub struct LoginQuery {
+    /// Path to send the user back to once logged in
+    pub return_to: Option<String>,
+}
+
+/// Query the provider redirects back with
+#[derive(Debug, Deserialize)]
+pub struct CallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Whether login is enabled and who is logged in
+#[derive(Debug, Serialize)]
+pub struct AuthStatus {
+    pub enabled: bool,
+    pub user: Option<AuthUser>,
+}
+
+/// Starts a login at the provider.
+/// - Redirects to the provider's authorization endpoint with a PKCE challenge.
+/// - Keeps the state, verifier and page to return to in a short lived signed cookie.
+/// - Responds 404 when login is not configured.
+pub async fn login(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<LoginQuery>,
+) -> Response {
+    let Some(login) = &app_state.login else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let discovery = match login.discovery().await {
+        Ok(discovery) => discovery,
+        Err(err) => {
+            error!("OIDC discovery failed: {}", err);
+            return StatusCode::BAD_GATEWAY.into_response();
+        }
+    };
+
+    let pending = PendingLogin {
+        state: random_token(),
+        verifier: format!("{}{}", random_token(), random_token()),
+        return_to: local_path(query.return_to),
+        exp: expires_in(LOGIN_TTL),
+    };
+    let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(pending.verifier.as_bytes()));
+
+    let mut url = match Url::parse(&discovery.authorization_endpoint) {
+        Ok(url) => url,
+        Err(err) => {
+            error!("Invalid OIDC authorization endpoint: {}", err);
+            return StatusCode::BAD_GATEWAY.into_response();
+        }
+    };
+    url.query_pairs_mut()
+        .append_pair("response_type", "code")
+        .append_pair("client_id", &login.client_id)
+        .append_pair("redirect_uri", login.redirect_url.as_str())
+        .append_pair("scope", &login.scopes)
+        .append_pair("state", &pending.state)
+        .append_pair("code_challenge", &challenge)
+        .append_pair("code_challenge_method", "S256");
+
+    (
+        AppendHeaders([(
+            SET_COOKIE,
+            login.set_cookie(LOGIN_COOKIE, &login.sign(&pending), LOGIN_TTL),
+        )]),
+        Redirect::to(url.as_str()),
+    )
+        .into_response()
+}
+
+/// Completes a login when the provider redirects back.
+/// - Checks the state against the login in progress.
+/// - Exchanges the code and fetches the user's name and picture.
+/// - Sets the signed session cookie checked during socket handshakes.
+pub async fn callback(
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<CallbackQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(login) = &app_state.login else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if let Some(error) = query.error {
+        warn!("Login refused by the provider: {}", error);
+        return (StatusCode::UNAUTHORIZED, format!("Login failed: {error}")).into_response();
+    }
+
+    let pending = cookie(&headers, LOGIN_COOKIE)
+        .and_then(|value| login.verify::<PendingLogin>(value))
+        .filter(|pending| pending.exp > Utc::now().timestamp());
+    let (Some(pending), Some(code)) = (pending, query.code) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Login expired, please try again".to_owned(),
+        )
+            .into_response();
+    };
+    if query.state.as_deref() != Some(pending.state.as_str()) {
+        warn!("Login state mismatch");
+        return (StatusCode::BAD_REQUEST, "Login state mismatch".to_owned()).into_response();
+    }
+
+    let user = match login.authenticate(&code, &pending.verifier).await {
+        Ok(user) => user,
+        Err(err) => {
+            error!("Login failed: {}", err);
+            return StatusCode::BAD_GATEWAY.into_response();
+        }
+    };
+    info!("User {} logged in as {}", user.sub, user.name);
+
+    (
+        AppendHeaders([
+            (
+                SET_COOKIE,
+                login.set_cookie(LOGIN_COOKIE, "", Duration::ZERO),
+            ),
+            (
+                SET_COOKIE,
+                login.set_cookie(SESSION_COOKIE, &login.session(&user), login.session_ttl),
+            ),
+        ]),
+        Redirect::to(&pending.return_to),
+    )
+        .into_response()
+}
+
+/// Reports whether login is enabled and who the session cookie belongs to
+pub async fn me(State(app_state): State<Arc<AppState>>, headers: HeaderMap) -> Json<AuthStatus> {
+    Json(AuthStatus {
+        enabled: app_state.login.is_some(),
+        user: app_state
+            .login
+            .as_ref()
+            .and_then(|login| login.user(&headers)),
+    })
+}
+
+/// Logs out by clearing the session cookie, rooms the user is in
+/// keep their verified player until they reconnect
+pub async fn logout(State(app_state): State<Arc<AppState>>) -> Response {
+    let Some(login) = &app_state.login else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    (
+        StatusCode::NO_CONTENT,
+        AppendHeaders([(
+            SET_COOKIE,
+            login.set_cookie(SESSION_COOKIE, "", Duration::ZERO),
+        )]),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        Router,
+        http::header::{HOST, LOCATION},
+        routing::{get, post},
+    };
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_support::{StandIn, app_state};
+
+    /// A provider answering discovery, token and userinfo requests
+    async fn provider() -> StandIn {
+        StandIn::start(
+            Router::new()
+                .route(
+                    "/.well-known/openid-configuration",
+                    get(|headers: HeaderMap| async move {
+                        let host = headers[HOST].to_str().expect("host is text").to_owned();
+                        Json(json!({
+                            "authorization_endpoint": format!("http://{host}/authorize"),
+                            "token_endpoint": format!("http://{host}/token"),
+                            "userinfo_endpoint": format!("http://{host}/userinfo"),
+                        }))
+                    }),
+                )
+                .route(
+                    "/token",
+                    post(|| async { Json(json!({ "access_token": "access-1" })) }),
+                )
+                .route(
+                    "/userinfo",
+                    get(|| async {
+                        Json(json!({
+                            "sub": "user-1",
+                            "preferred_username": "ada",
+                            "picture": "javascript:alert(1)",
+                        }))
+                    }),
+                ),
+        )
+        .await
+    }
+
+    fn config(issuer: &str) -> Config {
+        let mut config = Config::default();
+        config.oidc.issuer = Some(Url::parse(issuer).expect("valid URL"));
+        config.oidc.client_id = Some("storypoint".to_owned());
+        config.oidc.client_secret = Some("client-secret".to_owned());
+        config.oidc.redirect_url =
+            Some(Url::parse("http://localhost:3333/auth/callback").expect("valid URL"));
+        config.oidc.session_secret = Some("s".repeat(32));
+        config
+    }
+
+    fn login_of(app_state: &AppState) -> &Login {
+        app_state.login.as_ref().expect("login is configured")
+    }
+
+    /// The value a response sets a cookie to
+    fn cookie_set(response: &Response, name: &str) -> Option<String> {
+        response
+            .headers()
+            .get_all(SET_COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .find_map(|value| {
+                let (key, value) = value.split(';').next()?.split_once('=')?;
+                (key == name).then(|| value.to_owned())
+            })
+    }
+
+    fn cookies(name: &str, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            COOKIE,
+            HeaderValue::from_str(&format!("theme=dark; {name}={value}")).expect("cookie is text"),
+        );
+        headers
+    }
+
+    fn query(url: &Url, name: &str) -> String {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+            .unwrap_or_default()
+    }
+
+    /// Starts a login, returning the authorization URL and the login cookie
+    async fn start_login(app_state: &Arc<AppState>) -> (Url, String) {
+        let response = login(
+            State(Arc::clone(app_state)),
+            Query(LoginQuery {
+                return_to: Some("/room/123".to_owned()),
+            }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let location = response.headers()[LOCATION]
+            .to_str()
+            .expect("location is text");
+        let cookie = cookie_set(&response, LOGIN_COOKIE).expect("login cookie is set");
+        (Url::parse(location).expect("valid URL"), cookie)
+    }
+
+    #[tokio::test]
+    async fn callback_completes_a_pkce_login() {
+        let provider = provider().await;
+        let app_state = app_state(config(&provider.url));
+
+        let (authorize, login_cookie) = start_login(&app_state).await;
+        assert_eq!(authorize.path(), "/authorize");
+        assert_eq!(query(&authorize, "client_id"), "storypoint");
+        assert_eq!(query(&authorize, "code_challenge_method"), "S256");
+
+        let response = callback(
+            State(Arc::clone(&app_state)),
+            Query(CallbackQuery {
+                code: Some("code-1".to_owned()),
+                state: Some(query(&authorize, "state")),
+                error: None,
+            }),
+            cookies(LOGIN_COOKIE, &login_cookie),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(response.headers()[LOCATION], "/room/123");
+        assert_eq!(cookie_set(&response, LOGIN_COOKIE).as_deref(), Some(""));
+        let session = cookie_set(&response, SESSION_COOKIE).expect("session cookie is set");
+        let user = login_of(&app_state)
+            .user(&cookies(SESSION_COOKIE, &session))
+            .expect("session is valid");
+        assert_eq!(user.sub, "user-1");
+        assert_eq!(user.name, "ada");
+        assert_eq!(user.picture, None);
+
+        // the code is exchanged with the verifier behind the challenge
+        let requests = provider.requests();
+        let token = requests
+            .iter()
+            .find(|request| request.uri == "/token")
+            .expect("code is exchanged");
+        assert_eq!(token.form("code").as_deref(), Some("code-1"));
+        let verifier = token.form("code_verifier").expect("verifier is sent");
+        assert_eq!(
+            URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes())),
+            query(&authorize, "code_challenge")
+        );
+        assert_eq!(
+            token.header("authorization"),
+            Some("Basic c3Rvcnlwb2ludDpjbGllbnQtc2VjcmV0")
+        );
+    }
+
+    #[tokio::test]
+    async fn callback_rejects_a_state_mismatch() {
+        let provider = provider().await;
+        let app_state = app_state(config(&provider.url));
+        let (_, login_cookie) = start_login(&app_state).await;
+
+        let response = callback(
+            State(app_state),
+            Query(CallbackQuery {
+                code: Some("code-1".to_owned()),
+                state: Some("forged".to_owned()),
+                error: None,
+            }),
+            cookies(LOGIN_COOKIE, &login_cookie),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(cookie_set(&response, SESSION_COOKIE), None);
+        assert!(
+            provider
+                .requests()
+                .iter()
+                .all(|request| request.uri != "/token")
+        );
+    }
+
+    #[tokio::test]
+    async fn callback_rejects_a_tampered_login_cookie() {
+        let provider = provider().await;
+        let app_state = app_state(config(&provider.url));
+        let (authorize, login_cookie) = start_login(&app_state).await;
+        let (payload, signature) = login_cookie.split_once('.').expect("cookie is signed");
+        let mut pending: serde_json::Value =
+            serde_json::from_slice(&hex::decode(payload).expect("hex")).expect("payload is JSON");
+        pending["return_to"] = json!("/elsewhere");
+        let tampered = format!(
+            "{}.{signature}",
+            hex::encode(serde_json::to_vec(&pending).expect("serializes"))
+        );
+
+        let response = callback(
+            State(app_state),
+            Query(CallbackQuery {
+                code: Some("code-1".to_owned()),
+                state: Some(query(&authorize, "state")),
+                error: None,
+            }),
+            cookies(LOGIN_COOKIE, &tampered),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(cookie_set(&response, SESSION_COOKIE), None);
+    }
+
+    #[test]
+    fn session_cookies_must_be_signed_and_current() {
+        let login = Login::from_config(&config("http://idp.test"))
+            .expect("client builds")
+            .expect("login is configured");
+        let user = |exp: i64| AuthUser {
+            sub: "user-1".to_owned(),
+            name: "ada".to_owned(),
+            picture: None,
+            exp,
+        };
+        let valid = login.sign(&user(expires_in(Duration::from_mins(5))));
+
+        assert!(login.user(&cookies(SESSION_COOKIE, &valid)).is_some());
+        assert!(login.user(&HeaderMap::new()).is_none());
+
+        let expired = login.sign(&user(Utc::now().timestamp() - 1));
+        assert!(login.user(&cookies(SESSION_COOKIE, &expired)).is_none());
+
+        let mut other_config = config("http://idp.test");
+        other_config.oidc.session_secret = Some("o".repeat(32));
+        let other = Login::from_config(&other_config)
+            .expect("client builds")
+            .expect("login is configured");
+        let foreign = other.sign(&user(expires_in(Duration::from_mins(5))));
+        assert!(login.user(&cookies(SESSION_COOKIE, &foreign)).is_none());
+
+        let (payload, _) = valid.split_once('.').expect("cookie is signed");
+        let unsigned = format!("{payload}.{}", "0".repeat(64));
+        assert!(login.user(&cookies(SESSION_COOKIE, &unsigned)).is_none());
+    }
+}
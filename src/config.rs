@@ -0,0 +1,1054 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+
+use std::{
+    env::{self, VarError},
+    error::Error,
+    fmt, fs,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
+};
+
+use reqwest::Url;
+use serde::Deserialize;
+
+use crate::origins::{self, HostPattern, OriginPattern, PatternError};
+
+/// Limits applied to rooms and players
+#[derive(Clone, Debug)]
+pub struct Limits {
+    /// Maximum number of rooms that can exist at once
+    pub rooms: usize,
+    /// Maximum number of players (including spectators) in a single room
+    pub players_per_room: usize,
+    /// Maximum length of a player's name in characters
+    pub name_length: usize,
+    /// Maximum number of webhooks a host can register on a room
+    pub webhooks_per_room: usize,
+    /// Maximum number of stories in a room's queue
+    pub stories_per_room: usize,
+    /// Maximum number of teams that can exist at once
+    pub teams: usize,
+}
+
+/// Time to live settings for server side state
+#[derive(Clone, Debug)]
+pub struct Ttls {
+    /// How long a room may go without any activity before it is closed
+    pub room_idle: Duration,
+    /// How often idle rooms are checked for
+    pub sweep_interval: Duration,
+}
+
+/// Settings for draining the server before it exits
+#[derive(Clone, Debug)]
+pub struct Shutdown {
+    /// How long to keep serving existing rooms after a shutdown signal
+    pub drain: Duration,
+    /// How long clients are told to wait before reconnecting
+    pub reconnect_delay: Duration,
+}
+
+/// Settings for outgoing webhooks
+#[derive(Clone, Debug)]
+pub struct WebhookSettings {
+    /// URLs that receive the events of every room
+    pub urls: Vec<String>,
+    /// Secret used to sign deliveries to `urls`, unsigned when unset
+    pub secret: Option<String>,
+    /// How many times a delivery is attempted before giving up
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for each further retry
+    pub backoff: Duration,
+    /// How long to wait for a response to each attempt
+    pub timeout: Duration,
+}
+
+/// Settings for the Jira compatible issue tracker integration
+#[derive(Clone, Debug)]
+pub struct JiraSettings {
+    /// Base URL of the tracker, the integration is disabled when unset
+    pub base_url: Option<Url>,
+    /// API token, sent as a bearer token or with `email` as basic auth
+    pub token: Option<String>,
+    /// Account email, used with the token for Jira Cloud basic auth
+    pub email: Option<String>,
+    /// Custom field the agreed estimate is written to
+    pub story_points_field: String,
+    /// Maximum number of issues imported by one query
+    pub max_results: u32,
+    /// Keys of the projects hosts may import issues from,
+    /// searches are narrowed to these projects
+    pub projects: Vec<String>,
+}
+
+/// Settings for the GitHub Issues and Projects integration
+#[derive(Clone, Debug)]
+pub struct GitHubSettings {
+    /// Base URL of the REST API, e.g. `https://github.example.com/api/v3`
+    /// for GitHub Enterprise Server
+    pub api_url: Url,
+    /// Token used for every request, the integration is disabled when unset
+    pub token: Option<String>,
+    /// Prefix of the label applied with the agreed estimate
+    pub estimate_label_prefix: String,
+    /// Node ID of the project holding the estimate field, if any
+    pub project_id: Option<String>,
+    /// Node ID of the numeric project field the estimate is written to.
+    /// When set with `project_id` it is used instead of labels
+    pub project_field_id: Option<String>,
+    /// Maximum number of issues imported at once, GitHub allows up to 100
+    pub max_results: u32,
+    /// Repositories hosts may import issues from, as `owner/name`
+    pub repos: Vec<String>,
+}
+
+/// Settings for optional login through an OIDC provider
+#[derive(Clone, Debug)]
+pub struct OidcSettings {
+    /// Issuer URL of the provider, login is disabled when unset
+    pub issuer: Option<Url>,
+    /// Client ID registered with the provider
+    pub client_id: Option<String>,
+    /// Client secret, public clients relying on PKCE alone leave it unset
+    pub client_secret: Option<String>,
+    /// Where the provider sends users back to, the server's `/auth/callback`
+    pub redirect_url: Option<Url>,
+    /// Space separated scopes requested at login
+    pub scopes: String,
+    /// Secret session cookies are signed with. When unset a random secret
+    /// is used and logins do not survive a restart
+    pub session_secret: Option<String>,
+    /// How long a login lasts before the user has to log in again
+    pub session_ttl: Duration,
+}
+
+/// `Config` holds the server configuration
+/// It is loaded once at startup from an optional TOML file
+/// with environment variables taking precedence
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// The address the server listens on
+    pub bind_address: IpAddr,
+    /// The port the server listens on
+    pub port: u16,
+    /// Directory containing the built frontend
+    pub static_dir: PathBuf,
+    /// Hosts the server will answer on, other hosts are redirected
+    /// to the first exact host in the list
+    pub allowed_hosts: Vec<HostPattern>,
+    /// Disables host enforcement entirely
+    pub skip_host_enforcement: bool,
+    /// Origins allowed to make cross origin requests and socket
+    /// connections in addition to the allowed hosts themselves
+    pub cors_origins: Vec<OriginPattern>,
+    /// Room and player limits
+    pub limits: Limits,
+    /// Time to live settings
+    pub ttls: Ttls,
+    /// File rooms are persisted to across restarts, if any
+    pub state_file: Option<PathBuf>,
+    /// Graceful shutdown settings
+    pub shutdown: Shutdown,
+    /// Bearer token for operator endpoints, they are disabled when unset
+    pub admin_token: Option<String>,
+    /// Outgoing webhook settings
+    pub webhooks: WebhookSettings,
+    /// How long to wait for issue tracker and chat integrations
+    pub integration_timeout: Duration,
+    /// Jira integration settings
+    pub jira: JiraSettings,
+    /// GitHub integration settings
+    pub github: GitHubSettings,
+    /// Login settings
+    pub oidc: OidcSettings,
+}
+
+/// Shape of the optional TOML configuration file, every
+/// value is optional and falls back to the defaults
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileConfig {
+    bind_address: Option<IpAddr>,
+    port: Option<u16>,
+    static_dir: Option<PathBuf>,
+    allowed_hosts: Option<Vec<String>>,
+    skip_host_enforcement: Option<bool>,
+    cors_origins: Option<Vec<String>>,
+    limits: FileLimits,
+    ttls: FileTtls,
+    state_file: Option<PathBuf>,
+    shutdown: FileShutdown,
+    admin_token: Option<String>,
+    webhooks: FileWebhooks,
+    integration_timeout_secs: Option<u64>,
+    jira: FileJira,
+    github: FileGitHub,
+    oidc: FileOidc,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileLimits {
+    rooms: Option<usize>,
+    players_per_room: Option<usize>,
+    name_length: Option<usize>,
+    webhooks_per_room: Option<usize>,
+    stories_per_room: Option<usize>,
+    teams: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileTtls {
+    room_idle_secs: Option<u64>,
+    sweep_interval_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileShutdown {
+    drain_secs: Option<u64>,
+    reconnect_delay_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileWebhooks {
+    urls: Option<Vec<String>>,
+    secret: Option<String>,
+    max_attempts: Option<u32>,
+    backoff_secs: Option<u64>,
+    timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileJira {
+    base_url: Option<String>,
+    token: Option<String>,
+    email: Option<String>,
+    story_points_field: Option<String>,
+    max_results: Option<u32>,
+    projects: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileGitHub {
+    api_url: Option<String>,
+    token: Option<String>,
+    estimate_label_prefix: Option<String>,
+    project_id: Option<String>,
+    project_field_id: Option<String>,
+    max_results: Option<u32>,
+    repos: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileOidc {
+    issuer: Option<String>,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    redirect_url: Option<String>,
+    scopes: Option<String>,
+    session_secret: Option<String>,
+    session_ttl_secs: Option<u64>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind_address: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            port: 3333,
+            static_dir: PathBuf::from("dist"),
+            allowed_hosts: Vec::new(),
+            skip_host_enforcement: false,
+            cors_origins: Vec::new(),
+            limits: Limits {
+                rooms: 1000,
+                players_per_room: 50,
+                name_length: 32,
+                webhooks_per_room: 5,
+                stories_per_room: 100,
+                teams: 1000,
+            },
+            ttls: Ttls {
+                room_idle: Duration::from_hours(24),
+                sweep_interval: Duration::from_mins(1),
+            },
+            state_file: None,
+            shutdown: Shutdown {
+                drain: Duration::from_secs(10),
+                reconnect_delay: Duration::from_secs(5),
+            },
+            admin_token: None,
+            webhooks: WebhookSettings {
+                urls: Vec::new(),
+                secret: None,
+                max_attempts: 5,
+                backoff: Duration::from_secs(1),
+                timeout: Duration::from_secs(10),
+            },
+            integration_timeout: Duration::from_secs(10),
+            jira: JiraSettings {
+                base_url: None,
+                token: None,
+                email: None,
+                story_points_field: "customfield_10016".to_owned(),
+                max_results: 50,
+                projects: Vec::new(),
+            },
+            github: GitHubSettings {
+                api_url: Url::parse("https://api.github.com").expect("default URL is valid"),
+                token: None,
+                estimate_label_prefix: "estimate: ".to_owned(),
+                project_id: None,
+                project_field_id: None,
+                max_results: 50,
+                repos: Vec::new(),
+            },
+            oidc: OidcSettings {
+                issuer: None,
+                client_id: None,
+                client_secret: None,
+                redirect_url: None,
+                scopes: "openid profile email".to_owned(),
+                session_secret: None,
+                session_ttl: Duration::from_hours(24 * 7),
+            },
+        }
+    }
+}
+
+impl Config {
+    /// Loads the configuration, reading the TOML file named by `CONFIG_FILE`
+    /// if set and then applying any environment variable overrides
+    pub fn load() -> Result<Self, ConfigError> {
+        let file = match env_var("CONFIG_FILE")? {
+            Some(path) => read_file(PathBuf::from(path))?,
+            None => FileConfig::default(),
+        };
+
+        let mut config = Self::default();
+        config.apply_file(file)?;
+        config.apply_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// The socket address to bind the listener to
+    pub const fn socket_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.bind_address, self.port)
+    }
+
+    /// The host unknown hosts are redirected to
+    pub fn canonical_host(&self) -> Option<&str> {
+        self.allowed_hosts.iter().find_map(HostPattern::exact)
+    }
+
+    /// Checks a `Host` header value against the allowed hosts
+    pub fn host_allowed(&self, host: &str) -> bool {
+        origins::host_name(host)
+            .is_some_and(|name| self.allowed_hosts.iter().any(|p| p.matches(name)))
+    }
+
+    /// Checks an `Origin` header value against the configured CORS origins,
+    /// pages served from any of the allowed hosts are always allowed
+    pub fn origin_allowed(&self, origin: &str) -> bool {
+        self.cors_origins.iter().any(|p| p.matches(origin))
+            || origins::origin_host(origin)
+                .is_some_and(|name| self.allowed_hosts.iter().any(|p| p.matches(name)))
+    }
+
+    fn apply_file(&mut self, file: FileConfig) -> Result<(), ConfigError> {
+        let FileConfig {
+            bind_address,
+            port,
+            static_dir,
+            allowed_hosts,
+            skip_host_enforcement,
+            cors_origins,
+            limits,
+            ttls,
+            state_file,
+            shutdown,
+            admin_token,
+            webhooks,
+            integration_timeout_secs,
+            jira,
+            github,
+            oidc,
+        } = file;
+
+        set_if_some(&mut self.bind_address, bind_address);
+        set_if_some(&mut self.port, port);
+        set_if_some(&mut self.static_dir, static_dir);
+        if let Some(hosts) = allowed_hosts {
+            self.allowed_hosts = parse_list("allowed_hosts", &hosts)?;
+        }
+        set_if_some(&mut self.skip_host_enforcement, skip_host_enforcement);
+        if let Some(origins) = cors_origins {
+            self.cors_origins = parse_list("cors_origins", &origins)?;
+        }
+        set_if_some(&mut self.limits.rooms, limits.rooms);
+        set_if_some(&mut self.limits.players_per_room, limits.players_per_room);
+        set_if_some(&mut self.limits.name_length, limits.name_length);
+        set_if_some(&mut self.limits.webhooks_per_room, limits.webhooks_per_room);
+        set_if_some(&mut self.limits.stories_per_room, limits.stories_per_room);
+        set_if_some(&mut self.limits.teams, limits.teams);
+        set_if_some(
+            &mut self.ttls.room_idle,
+            ttls.room_idle_secs.map(Duration::from_secs),
+        );
+        set_if_some(
+            &mut self.ttls.sweep_interval,
+            ttls.sweep_interval_secs.map(Duration::from_secs),
+        );
+        set_if_some(&mut self.state_file, state_file.map(Some));
+        set_if_some(
+            &mut self.shutdown.drain,
+            shutdown.drain_secs.map(Duration::from_secs),
+        );
+        set_if_some(
+            &mut self.shutdown.reconnect_delay,
+            shutdown.reconnect_delay_secs.map(Duration::from_secs),
+        );
+        set_if_some(&mut self.admin_token, admin_token.map(Some));
+        set_if_some(&mut self.webhooks.urls, webhooks.urls);
+        set_if_some(&mut self.webhooks.secret, webhooks.secret.map(Some));
+        set_if_some(&mut self.webhooks.max_attempts, webhooks.max_attempts);
+        set_if_some(
+            &mut self.webhooks.backoff,
+            webhooks.backoff_secs.map(Duration::from_secs),
+        );
+        set_if_some(
+            &mut self.webhooks.timeout,
+            webhooks.timeout_secs.map(Duration::from_secs),
+        );
+        set_if_some(
+            &mut self.integration_timeout,
+            integration_timeout_secs.map(Duration::from_secs),
+        );
+        if let Some(base_url) = jira.base_url {
+            self.jira.base_url = Some(parse_url("jira.base_url", &base_url)?);
+        }
+        set_if_some(&mut self.jira.token, jira.token.map(Some));
+        set_if_some(&mut self.jira.email, jira.email.map(Some));
+        set_if_some(&mut self.jira.story_points_field, jira.story_points_field);
+        set_if_some(&mut self.jira.max_results, jira.max_results);
+        set_if_some(&mut self.jira.projects, jira.projects);
+        if let Some(api_url) = github.api_url {
+            self.github.api_url = parse_url("github.api_url", &api_url)?;
+        }
+        set_if_some(&mut self.github.token, github.token.map(Some));
+        set_if_some(
+            &mut self.github.estimate_label_prefix,
+            github.estimate_label_prefix,
+        );
+        set_if_some(&mut self.github.project_id, github.project_id.map(Some));
+        set_if_some(
+            &mut self.github.project_field_id,
+            github.project_field_id.map(Some),
+        );
+        set_if_some(&mut self.github.max_results, github.max_results);
+        set_if_some(&mut self.github.repos, github.repos);
+        self.apply_oidc_file(oidc)
+    }
+
+    /// Applies the login settings from the `[oidc]` table
+    fn apply_oidc_file(&mut self, oidc: FileOidc) -> Result<(), ConfigError> {
+        if let Some(issuer) = oidc.issuer {
+            self.oidc.issuer = Some(parse_url("oidc.issuer", &issuer)?);
+        }
+        set_if_some(&mut self.oidc.client_id, oidc.client_id.map(Some));
+        set_if_some(&mut self.oidc.client_secret, oidc.client_secret.map(Some));
+        if let Some(redirect_url) = oidc.redirect_url {
+            self.oidc.redirect_url = Some(parse_url("oidc.redirect_url", &redirect_url)?);
+        }
+        set_if_some(&mut self.oidc.scopes, oidc.scopes);
+        set_if_some(&mut self.oidc.session_secret, oidc.session_secret.map(Some));
+        set_if_some(
+            &mut self.oidc.session_ttl,
+            oidc.session_ttl_secs.map(Duration::from_secs),
+        );
+        Ok(())
+    }
+
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        set_if_some(&mut self.bind_address, env_parse("BIND_ADDRESS")?);
+        set_if_some(&mut self.port, env_parse("PORT")?);
+        set_if_some(
+            &mut self.static_dir,
+            env_var("STATIC_DIR")?.map(PathBuf::from),
+        );
+
+        // ALLOWED_HOST is the original single host variable, keep accepting it
+        if let Some(hosts) = env_var("ALLOWED_HOSTS")?.or(env_var("ALLOWED_HOST")?) {
+            self.allowed_hosts = parse_list("ALLOWED_HOSTS", &split_list(&hosts))?;
+        }
+        if let Some(origins) = env_var("CORS_ORIGINS")? {
+            self.cors_origins = parse_list("CORS_ORIGINS", &split_list(&origins))?;
+        }
+        // presence of the variable has always been enough to skip enforcement
+        if env_var("SKIP_HOST_ENFORCEMENT")?.is_some() {
+            self.skip_host_enforcement = true;
+        }
+
+        set_if_some(&mut self.limits.rooms, env_parse("MAX_ROOMS")?);
+        set_if_some(
+            &mut self.limits.players_per_room,
+            env_parse("MAX_PLAYERS_PER_ROOM")?,
+        );
+        set_if_some(&mut self.limits.name_length, env_parse("MAX_NAME_LENGTH")?);
+        set_if_some(
+            &mut self.ttls.room_idle,
+            env_parse("ROOM_IDLE_TTL_SECS")?.map(Duration::from_secs),
+        );
+        set_if_some(
+            &mut self.ttls.sweep_interval,
+            env_parse("ROOM_SWEEP_INTERVAL_SECS")?.map(Duration::from_secs),
+        );
+        set_if_some(
+            &mut self.state_file,
+            env_var("STATE_FILE")?.map(|path| Some(PathBuf::from(path))),
+        );
+        set_if_some(
+            &mut self.shutdown.drain,
+            env_parse("SHUTDOWN_DRAIN_SECS")?.map(Duration::from_secs),
+        );
+        set_if_some(
+            &mut self.shutdown.reconnect_delay,
+            env_parse("RECONNECT_DELAY_SECS")?.map(Duration::from_secs),
+        );
+        set_if_some(&mut self.admin_token, env_var("ADMIN_TOKEN")?.map(Some));
+        set_if_some(
+            &mut self.limits.webhooks_per_room,
+            env_parse("MAX_WEBHOOKS_PER_ROOM")?,
+        );
+        if let Some(urls) = env_var("WEBHOOK_URLS")? {
+            self.webhooks.urls = split_list(&urls);
+        }
+        set_if_some(
+            &mut self.webhooks.secret,
+            env_var("WEBHOOK_SECRET")?.map(Some),
+        );
+        set_if_some(
+            &mut self.webhooks.max_attempts,
+            env_parse("WEBHOOK_MAX_ATTEMPTS")?,
+        );
+        set_if_some(
+            &mut self.webhooks.backoff,
+            env_parse("WEBHOOK_BACKOFF_SECS")?.map(Duration::from_secs),
+        );
+        set_if_some(
+            &mut self.webhooks.timeout,
+            env_parse("WEBHOOK_TIMEOUT_SECS")?.map(Duration::from_secs),
+        );
+        set_if_some(
+            &mut self.limits.stories_per_room,
+            env_parse("MAX_STORIES_PER_ROOM")?,
+        );
+        set_if_some(&mut self.limits.teams, env_parse("MAX_TEAMS")?);
+        self.apply_integrations_env()?;
+        self.apply_oidc_env()
+    }
+
+    /// Applies the issue tracker and chat integration environment variables
+    fn apply_integrations_env(&mut self) -> Result<(), ConfigError> {
+        set_if_some(
+            &mut self.integration_timeout,
+            env_parse("INTEGRATION_TIMEOUT_SECS")?.map(Duration::from_secs),
+        );
+        if let Some(base_url) = env_var("JIRA_BASE_URL")? {
+            self.jira.base_url = Some(parse_url("JIRA_BASE_URL", &base_url)?);
+        }
+        set_if_some(&mut self.jira.token, env_var("JIRA_TOKEN")?.map(Some));
+        set_if_some(&mut self.jira.email, env_var("JIRA_EMAIL")?.map(Some));
+        set_if_some(
+            &mut self.jira.story_points_field,
+            env_var("JIRA_STORY_POINTS_FIELD")?,
+        );
+        set_if_some(&mut self.jira.max_results, env_parse("JIRA_MAX_RESULTS")?);
+        if let Some(projects) = env_var("JIRA_PROJECTS")? {
+            self.jira.projects = split_list(&projects);
+        }
+        if let Some(api_url) = env_var("GITHUB_API_URL")? {
+            self.github.api_url = parse_url("GITHUB_API_URL", &api_url)?;
+        }
+        set_if_some(&mut self.github.token, env_var("GITHUB_TOKEN")?.map(Some));
+        set_if_some(
+            &mut self.github.estimate_label_prefix,
+            env_var("GITHUB_ESTIMATE_LABEL_PREFIX")?,
+        );
+        set_if_some(
+            &mut self.github.project_id,
+            env_var("GITHUB_PROJECT_ID")?.map(Some),
+        );
+        set_if_some(
+            &mut self.github.project_field_id,
+            env_var("GITHUB_PROJECT_FIELD_ID")?.map(Some),
+        );
+        set_if_some(
+            &mut self.github.max_results,
+            env_parse("GITHUB_MAX_RESULTS")?,
+        );
+        if let Some(repos) = env_var("GITHUB_REPOS")? {
+            self.github.repos = split_list(&repos);
+        }
+        Ok(())
+    }
+
+    /// Applies the login environment variables
+    fn apply_oidc_env(&mut self) -> Result<(), ConfigError> {
+        if let Some(issuer) = env_var("OIDC_ISSUER")? {
+            self.oidc.issuer = Some(parse_url("OIDC_ISSUER", &issuer)?);
+        }
+        set_if_some(
+            &mut self.oidc.client_id,
+            env_var("OIDC_CLIENT_ID")?.map(Some),
+        );
+        set_if_some(
+            &mut self.oidc.client_secret,
+            env_var("OIDC_CLIENT_SECRET")?.map(Some),
+        );
+        if let Some(redirect_url) = env_var("OIDC_REDIRECT_URL")? {
+            self.oidc.redirect_url = Some(parse_url("OIDC_REDIRECT_URL", &redirect_url)?);
+        }
+        set_if_some(&mut self.oidc.scopes, env_var("OIDC_SCOPES")?);
+        set_if_some(
+            &mut self.oidc.session_secret,
+            env_var("SESSION_SECRET")?.map(Some),
+        );
+        set_if_some(
+            &mut self.oidc.session_ttl,
+            env_parse("SESSION_TTL_SECS")?.map(Duration::from_secs),
+        );
+        Ok(())
+    }
+
+    /// Checks the login settings are complete when login is enabled
+    fn validate_oidc(&self) -> Result<(), ConfigError> {
+        if self.oidc.issuer.is_some() {
+            let required = [
+                ("OIDC_CLIENT_ID", self.oidc.client_id.is_some()),
+                ("OIDC_REDIRECT_URL", self.oidc.redirect_url.is_some()),
+            ];
+            for (key, set) in required {
+                if !set {
+                    return Err(ConfigError::Invalid {
+                        key,
+                        reason: "must be set when OIDC_ISSUER is set".to_owned(),
+                    });
+                }
+            }
+        }
+        if self
+            .oidc
+            .session_secret
+            .as_ref()
+            .is_some_and(|secret| secret.trim().len() < 32)
+        {
+            return Err(ConfigError::Invalid {
+                key: "SESSION_SECRET",
+                reason: "must be at least 32 characters".to_owned(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks the projects and repositories hosts may import from are set
+    /// whenever an issue tracker is, as imports use the operator's credentials
+    fn validate_import_scopes(&self) -> Result<(), ConfigError> {
+        if self.jira.base_url.is_some() && self.jira.projects.is_empty() {
+            return Err(ConfigError::Invalid {
+                key: "JIRA_PROJECTS",
+                reason: "must be set when JIRA_BASE_URL is set".to_owned(),
+            });
+        }
+        let project_key = |key: &String| {
+            key.starts_with(|c: char| c.is_ascii_uppercase())
+                && key
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+        };
+        if let Some(key) = self.jira.projects.iter().find(|key| !project_key(key)) {
+            return Err(ConfigError::Invalid {
+                key: "JIRA_PROJECTS",
+                reason: format!("{key:?} is not a project key"),
+            });
+        }
+
+        if self.github.token.is_some() && self.github.repos.is_empty() {
+            return Err(ConfigError::Invalid {
+                key: "GITHUB_REPOS",
+                reason: "must be set when GITHUB_TOKEN is set".to_owned(),
+            });
+        }
+        let repo = |repo: &String| {
+            repo.split_once('/').is_some_and(|(owner, name)| {
+                !owner.is_empty() && !name.is_empty() && !name.contains('/')
+            })
+        };
+        if let Some(invalid) = self.github.repos.iter().find(|r| !repo(r)) {
+            return Err(ConfigError::Invalid {
+                key: "GITHUB_REPOS",
+                reason: format!("{invalid:?} is not a repository in owner/name form"),
+            });
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if !self.skip_host_enforcement && self.canonical_host().is_none() {
+            return Err(ConfigError::Invalid {
+                key: "ALLOWED_HOSTS",
+                reason:
+                    "at least one non wildcard host must be set unless SKIP_HOST_ENFORCEMENT is set"
+                        .to_owned(),
+            });
+        }
+
+        if self
+            .admin_token
+            .as_ref()
+            .is_some_and(|token| token.trim().len() < 16)
+        {
+            return Err(ConfigError::Invalid {
+                key: "ADMIN_TOKEN",
+                reason: "must be at least 16 characters".to_owned(),
+            });
+        }
+
+        let non_zero = [
+            ("MAX_ROOMS", self.limits.rooms),
+            ("MAX_PLAYERS_PER_ROOM", self.limits.players_per_room),
+            ("MAX_NAME_LENGTH", self.limits.name_length),
+            ("MAX_TEAMS", self.limits.teams),
+            ("JIRA_MAX_RESULTS", self.jira.max_results as usize),
+            ("GITHUB_MAX_RESULTS", self.github.max_results as usize),
+        ];
+        for (key, value) in non_zero {
+            if value == 0 {
+                return Err(ConfigError::Invalid {
+                    key,
+                    reason: "must be greater than zero".to_owned(),
+                });
+            }
+        }
+
+        for url in &self.webhooks.urls {
+            parse_url("WEBHOOK_URLS", url)?;
+        }
+        if self.jira.base_url.is_some() && self.jira.token.is_none() {
+            return Err(ConfigError::Invalid {
+                key: "JIRA_TOKEN",
+                reason: "must be set when JIRA_BASE_URL is set".to_owned(),
+            });
+        }
+        self.validate_import_scopes()?;
+        if self.github.max_results > 100 {
+            return Err(ConfigError::Invalid {
+                key: "GITHUB_MAX_RESULTS",
+                reason: "must be at most 100".to_owned(),
+            });
+        }
+        if self.github.project_id.is_some() != self.github.project_field_id.is_some() {
+            return Err(ConfigError::Invalid {
+                key: "GITHUB_PROJECT_FIELD_ID",
+                reason: "GITHUB_PROJECT_ID and GITHUB_PROJECT_FIELD_ID must be set together"
+                    .to_owned(),
+            });
+        }
+        self.validate_oidc()?;
+        if self.webhooks.max_attempts == 0 {
+            return Err(ConfigError::Invalid {
+                key: "WEBHOOK_MAX_ATTEMPTS",
+                reason: "must be greater than zero".to_owned(),
+            });
+        }
+
+        let non_zero_durations = [
+            ("ROOM_IDLE_TTL_SECS", self.ttls.room_idle),
+            ("ROOM_SWEEP_INTERVAL_SECS", self.ttls.sweep_interval),
+            ("WEBHOOK_TIMEOUT_SECS", self.webhooks.timeout),
+            ("INTEGRATION_TIMEOUT_SECS", self.integration_timeout),
+            ("SESSION_TTL_SECS", self.oidc.session_ttl),
+        ];
+        for (key, value) in non_zero_durations {
+            if value.is_zero() {
+                return Err(ConfigError::Invalid {
+                    key,
+                    reason: "must be greater than zero".to_owned(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Overwrites the target only when a value was provided
+fn set_if_some<T>(target: &mut T, value: Option<T>) {
+    if let Some(value) = value {
+        *target = value;
+    }
+}
+
+/// Splits a comma separated list, dropping empty entries
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Parses each entry of a list, reporting the first invalid entry
+fn parse_list<T>(key: &'static str, values: &[String]) -> Result<Vec<T>, ConfigError>
+where
+    T: FromStr<Err = PatternError>,
+{
+    values
+        .iter()
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|err: PatternError| ConfigError::Invalid {
+                    key,
+                    reason: err.to_string(),
+                })
+        })
+        .collect()
+}
+
+/// Parses an http or https URL
+fn parse_url(key: &'static str, value: &str) -> Result<Url, ConfigError> {
+    Url::parse(value.trim())
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https") && url.has_host())
+        .ok_or_else(|| ConfigError::Invalid {
+            key,
+            reason: format!("{value:?} is not an http or https URL"),
+        })
+}
+
+fn env_var(key: &'static str) -> Result<Option<String>, ConfigError> {
+    match env::var(key) {
+        Ok(value) => Ok(Some(value)),
+        Err(VarError::NotPresent) => Ok(None),
+        Err(VarError::NotUnicode(_)) => Err(ConfigError::Invalid {
+            key,
+            reason: "value is not valid unicode".to_owned(),
+        }),
+    }
+}
+
+fn env_parse<T>(key: &'static str) -> Result<Option<T>, ConfigError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    env_var(key)?
+        .map(|value| {
+            value
+                .trim()
+                .parse()
+                .map_err(|err: T::Err| ConfigError::Invalid {
+                    key,
+                    reason: format!("could not parse {value:?}: {err}"),
+                })
+        })
+        .transpose()
+}
+
+fn read_file(path: PathBuf) -> Result<FileConfig, ConfigError> {
+    let contents = fs::read_to_string(&path).map_err(|source| ConfigError::Io {
+        path: path.clone(),
+        source,
+    })?;
+    toml::from_str(&contents).map_err(|source| ConfigError::Parse { path, source })
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The configuration file could not be read
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The configuration file is not valid TOML or has unknown keys
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    /// A configuration value failed validation
+    Invalid { key: &'static str, reason: String },
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Parse { source, .. } => Some(source),
+            Self::Invalid { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => {
+                write!(f, "Could not read config file {}: {source}", path.display())
+            }
+            Self::Parse { path, source } => {
+                write!(f, "Invalid config file {}: {source}", path.display())
+            }
+            Self::Invalid { key, reason } => write!(f, "Invalid config value {key}: {reason}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses a TOML config file's contents
+    fn file(contents: &str) -> FileConfig {
+        toml::from_str(contents).expect("file parses")
+    }
+
+    /// The key a validation error names
+    fn invalid_key(config: &Config) -> Option<&'static str> {
+        match config.validate() {
+            Err(ConfigError::Invalid { key, .. }) => Some(key),
+            _ => None,
+        }
+    }
+
+    /// Defaults that pass validation
+    fn valid() -> Config {
+        Config {
+            allowed_hosts: vec!["storypoint.example".parse().expect("valid pattern")],
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn file_values_override_the_defaults() {
+        let mut config = Config::default();
+        config
+            .apply_file(file(
+                r#"
+                port = 4000
+                allowed_hosts = ["storypoint.example", "*.preview.example"]
+
+                [limits]
+                rooms = 10
+                "#,
+            ))
+            .expect("file applies");
+
+        assert_eq!(config.port, 4000);
+        assert_eq!(config.limits.rooms, 10);
+        assert_eq!(config.limits.players_per_room, 50);
+        assert_eq!(config.canonical_host(), Some("storypoint.example"));
+        assert!(config.host_allowed("pr-12.preview.example:443"));
+    }
+
+    #[test]
+    fn environment_variables_override_the_file() {
+        let mut config = Config::default();
+        config
+            .apply_file(file("port = 4000\nadmin_token = \"from-the-file-token\""))
+            .expect("file applies");
+
+        // SAFETY: no other test reads or writes these variables
+        unsafe { env::set_var("PORT", "5000") };
+        let applied = config.apply_env();
+        unsafe { env::remove_var("PORT") };
+
+        applied.expect("environment applies");
+        assert_eq!(config.port, 5000);
+        assert_eq!(config.admin_token.as_deref(), Some("from-the-file-token"));
+    }
+
+    #[test]
+    fn unknown_and_invalid_file_values_are_rejected() {
+        assert!(toml::from_str::<FileConfig>("prot = 4000").is_err());
+        assert!(toml::from_str::<FileConfig>("[limits]\nroom = 10").is_err());
+
+        let mut config = Config::default();
+        let applied = config.apply_file(file(r#"allowed_hosts = ["https://storypoint.example"]"#));
+        assert!(matches!(
+            applied,
+            Err(ConfigError::Invalid {
+                key: "allowed_hosts",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn validate_needs_a_canonical_host_unless_enforcement_is_skipped() {
+        assert_eq!(invalid_key(&Config::default()), Some("ALLOWED_HOSTS"));
+
+        let wildcard_only = Config {
+            allowed_hosts: vec!["*.preview.example".parse().expect("valid pattern")],
+            ..Config::default()
+        };
+        assert_eq!(invalid_key(&wildcard_only), Some("ALLOWED_HOSTS"));
+
+        let skipped = Config {
+            skip_host_enforcement: true,
+            ..Config::default()
+        };
+        assert_eq!(invalid_key(&skipped), None);
+        assert_eq!(invalid_key(&valid()), None);
+    }
+
+    #[test]
+    fn validate_rejects_weak_secrets_and_zero_limits() {
+        let mut config = valid();
+        config.admin_token = Some("short".to_owned());
+        assert_eq!(invalid_key(&config), Some("ADMIN_TOKEN"));
+
+        let mut config = valid();
+        config.limits.rooms = 0;
+        assert_eq!(invalid_key(&config), Some("MAX_ROOMS"));
+
+        let mut config = valid();
+        config.webhooks.max_attempts = 0;
+        assert_eq!(invalid_key(&config), Some("WEBHOOK_MAX_ATTEMPTS"));
+
+        let mut config = valid();
+        config.oidc.session_secret = Some("s".repeat(31));
+        assert_eq!(invalid_key(&config), Some("SESSION_SECRET"));
+    }
+
+    #[test]
+    fn validate_needs_complete_integration_and_login_settings() {
+        let mut config = valid();
+        config.oidc.issuer = Some(Url::parse("https://idp.example").expect("valid URL"));
+        assert_eq!(invalid_key(&config), Some("OIDC_CLIENT_ID"));
+
+        let mut config = valid();
+        config.jira.base_url = Some(Url::parse("https://jira.example").expect("valid URL"));
+        assert_eq!(invalid_key(&config), Some("JIRA_TOKEN"));
+        config.jira.token = Some("token".to_owned());
+        assert_eq!(invalid_key(&config), Some("JIRA_PROJECTS"));
+        config.jira.projects = vec!["plat".to_owned()];
+        assert_eq!(invalid_key(&config), Some("JIRA_PROJECTS"));
+        config.jira.projects = vec!["PLAT".to_owned()];
+        assert_eq!(invalid_key(&config), None);
+
+        let mut config = valid();
+        config.github.token = Some("token".to_owned());
+        config.github.repos = vec!["storypoint".to_owned()];
+        assert_eq!(invalid_key(&config), Some("GITHUB_REPOS"));
+    }
+}
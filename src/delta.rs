@@ -0,0 +1,240 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+
+use std::sync::Arc;
+
+use crate::types::{Room, RoomChange, RoomDelta};
+
+/// Deltas broadcast to a room between full snapshots,
+/// a client that missed one resyncs at the latest by the next snapshot
+pub const SNAPSHOT_INTERVAL: u64 = 50;
+
+impl Room {
+    /// Records the room as seen by players as broadcast and returns
+    /// what changed since the last broadcast as the next delta.
+    /// - `visible` is the room with votes hidden as players may see it.
+    /// - A room with nothing broadcast yet is sent in full.
+    /// - Returns `None` without using a sequence number if nothing changed.
+    pub fn next_delta(&mut self, mut visible: Self) -> Option<RoomDelta> {
+        let changes = self
+            .published
+            .as_deref()
+            .map_or_else(|| everything(&visible), |before| changes(before, &visible));
+        if changes.is_empty() {
+            return None;
+        }
+
+        self.seq += 1;
+        visible.seq = self.seq;
+        visible.published = None;
+        self.published = Some(Arc::new(visible));
+
+        Some(RoomDelta {
+            room_id: self.id,
+            seq: self.seq,
+            changes,
+        })
+    }
+
+    /// Makes the room as players see it now the baseline for the next delta,
+    /// for rooms no one has been sent yet
+    pub fn set_published(&mut self, mut visible: Self) {
+        visible.published = None;
+        self.published = Some(Arc::new(visible));
+    }
+
+    /// Whether a full snapshot is due after the latest delta
+    pub const fn snapshot_due(&self) -> bool {
+        self.seq.is_multiple_of(SNAPSHOT_INTERVAL)
+    }
+}
+
+/// The changes that rebuild a room from nothing
+fn everything(room: &Room) -> Vec<RoomChange> {
+    let mut changes = vec![
+        room_updated(room),
+        settings_updated(room),
+        results_updated(room),
+        stories_updated(room),
+        rounds_updated(room),
+    ];
+    changes.extend(room.players.values().map(|player| RoomChange::PlayerAdded {
+        player: player.clone(),
+    }));
+    changes
+}
+
+/// The changes that turn one view of a room into another
+fn changes(before: &Room, after: &Room) -> Vec<RoomChange> {
+    let mut changes = Vec::new();
+
+    if before.cards_revealed != after.cards_revealed
+        || before.host_id != after.host_id
+        || before.current_story != after.current_story
+        || before.estimate != after.estimate
+    {
+        changes.push(room_updated(after));
+    }
+    if before.settings != after.settings {
+        changes.push(settings_updated(after));
+    }
+    if before.results != after.results {
+        changes.push(results_updated(after));
+    }
+    if before.stories != after.stories {
+        changes.push(stories_updated(after));
+    }
+    if before.rounds != after.rounds {
+        changes.push(rounds_updated(after));
+    }
+
+    for player_id in before.players.keys() {
+        if !after.players.contains_key(player_id) {
+            changes.push(RoomChange::PlayerRemoved {
+                player_id: player_id.clone(),
+            });
+        }
+    }
+    for (player_id, player) in &after.players {
+        match before.players.get(player_id) {
+            None => changes.push(RoomChange::PlayerAdded {
+                player: player.clone(),
+            }),
+            Some(previous) if previous != player => changes.push(RoomChange::PlayerUpdated {
+                player: player.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+
+    changes
+}
+
+fn room_updated(room: &Room) -> RoomChange {
+    RoomChange::RoomUpdated {
+        cards_revealed: room.cards_revealed,
+        host_id: room.host_id.clone(),
+        current_story: room.current_story,
+        estimate: room.estimate,
+    }
+}
+
+fn settings_updated(room: &Room) -> RoomChange {
+    RoomChange::SettingsUpdated {
+        settings: room.settings.clone(),
+    }
+}
+
+fn results_updated(room: &Room) -> RoomChange {
+    RoomChange::ResultsUpdated {
+        results: room.results.clone(),
+    }
+}
+
+fn stories_updated(room: &Room) -> RoomChange {
+    RoomChange::StoriesUpdated {
+        stories: room.stories.clone(),
+    }
+}
+
+fn rounds_updated(room: &Room) -> RoomChange {
+    RoomChange::RoundsUpdated {
+        rounds: room.rounds.clone(),
+        convergence: room.convergence.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::test_support::room;
+
+    /// The op of each change in a delta
+    fn ops(delta: &RoomDelta) -> Vec<String> {
+        delta
+            .changes
+            .iter()
+            .map(
+                |change| match serde_json::to_value(change).expect("change serializes") {
+                    Value::Object(change) => change["op"].as_str().unwrap_or_default().to_owned(),
+                    _ => String::new(),
+                },
+            )
+            .collect()
+    }
+
+    #[test]
+    fn the_first_delta_carries_the_whole_room() {
+        let mut room = room(&["host", "ada"]);
+
+        let delta = room.next_delta(room.clone()).expect("room is new");
+
+        assert_eq!(delta.seq, 1);
+        assert_eq!(delta.room_id, room.id);
+        assert_eq!(
+            ops(&delta),
+            [
+                "roomUpdated",
+                "settingsUpdated",
+                "resultsUpdated",
+                "storiesUpdated",
+                "roundsUpdated",
+                "playerAdded",
+                "playerAdded"
+            ]
+        );
+    }
+
+    #[test]
+    fn deltas_carry_only_what_changed() {
+        let mut room = room(&["host", "ada"]);
+        room.set_published(room.clone());
+
+        room.players
+            .get_mut("ada")
+            .expect("ada is in the room")
+            .has_voted = true;
+        let delta = room.next_delta(room.clone()).expect("a player changed");
+        assert_eq!(delta.seq, 1);
+        assert_eq!(ops(&delta), ["playerUpdated"]);
+
+        room.players.remove("ada");
+        room.cards_revealed = true;
+        let delta = room.next_delta(room.clone()).expect("the room changed");
+        assert_eq!(delta.seq, 2);
+        assert_eq!(ops(&delta), ["roomUpdated", "playerRemoved"]);
+    }
+
+    #[test]
+    fn unchanged_rooms_use_no_sequence_number() {
+        let mut room = room(&["host"]);
+        room.next_delta(room.clone()).expect("room is new");
+
+        assert!(room.next_delta(room.clone()).is_none());
+        assert_eq!(room.seq, 1);
+
+        room.estimate = Some(5);
+        assert_eq!(
+            room.next_delta(room.clone()).map(|delta| delta.seq),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn snapshots_are_due_every_interval() {
+        let mut room = room(&["host"]);
+        room.set_published(room.clone());
+
+        let mut due = Vec::new();
+        for _ in 0..SNAPSHOT_INTERVAL * 2 {
+            room.cards_revealed = !room.cards_revealed;
+            room.next_delta(room.clone()).expect("the room changed");
+            if room.snapshot_due() {
+                due.push(room.seq);
+            }
+        }
+
+        assert_eq!(due, [SNAPSHOT_INTERVAL, SNAPSHOT_INTERVAL * 2]);
+    }
+}
@@ -8,15 +8,40 @@ use std::{
     sync::Arc,
 };
 
-use socketioxide::extract::{Data, SocketRef, State as SocketState};
+use axum::http::{HeaderMap, header::ORIGIN};
+use chrono::{TimeDelta, Utc};
+use socketioxide::{
+    SocketIo,
+    extract::{Data, SocketRef, State as SocketState, TryData},
+};
 use tracing::{error, info};
 use uuid::Uuid;
 
-use crate::types::{
-    AppState, CardsRevealedEvent, CreateRoomEvent, JoinRoomEvent, MoveToRoomEvent,
-    NewHostElectedEvent, Player, PlayerDisconnectedEvent, PlayerExitEvent, PlayerJoinedEvent,
-    PlayerVotedEvent, ResetVotesEvent, RevealCardsEvent, Room, RoomCreatedEvent, RoomEmptyError,
-    RoomNotFoundError, RoomNotFoundEvent, SocketEvent, VoteEvent, VotesResetEvent,
+use crate::{
+    auth::AuthUser,
+    hub::RoomHub,
+    integrations::{IntegrationError, chat::RoundSummary},
+    participants::SessionAuth,
+    profile::Profile,
+    protocol::{self, ProtocolAuth, ProtocolError},
+    settings::{AutoReveal, RoomSettings},
+    stats::{Convergence, UNSURE_VOTE, VoteStatistics, is_estimate},
+    types::{
+        AcceptEstimateEvent, ActionRejected, ActionRejectedEvent, AddWebhookEvent, AllVoted,
+        AllVotedEvent, AppState, CardsRevealedEvent, CreateRoomEvent, EstimateAcceptedEvent,
+        EstimateChoice, ImportGitHubIssuesEvent, ImportJiraIssuesEvent, JoinRoomEvent,
+        JoinTeamRoomEvent, MoveToRoomEvent, NUMERIC_CARD_SET, NewHostElectedEvent,
+        OriginNotAllowedError, Player, PlayerDisconnectedEvent, PlayerExitEvent, PlayerJoinedEvent,
+        PlayerVotedEvent, ProfileUpdatedEvent, RemoveWebhookEvent, ResetVotesEvent, ResyncEvent,
+        RetractVoteEvent, RevealCardsEvent, RevoteEvent, RevoteStartedEvent, Room, RoomClosedEvent,
+        RoomCreatedEvent, RoomDelta, RoomEmptyError, RoomNotFoundError, RoomNotFoundEvent,
+        RoomSnapshotEvent, SelectStoryEvent, SessionEvent, SetChatWebhookEvent, SetSpectatorEvent,
+        SettingsUpdatedEvent, SocketEvent, SpectatorChangedEvent, StoriesUpdatedEvent, Story,
+        UpdateProfileEvent, UpdateSettingsEvent, VoteEvent, VoteRetractedEvent, VotesResetEvent,
+        WebhooksUpdatedEvent,
+    },
+    webhooks::{self, RoomWebhooks, WebhookEvent},
+    ws::WsPeer,
 };
 
 /// Cleans the votes from the room by setting each player's vote to None.
@@ -30,6 +55,105 @@ fn clean_votes(room: &Room) -> Room {
     cloned_room
 }
 
+/// The room as players may see it, votes are hidden until revealed
+/// and anonymous rooms only ever show the counts in the results
+pub fn visible_room(room: &Room) -> Room {
+    if room.cards_revealed && !room.settings.anonymous {
+        room.clone()
+    } else {
+        clean_votes(room)
+    }
+}
+
+/// Trims a player's name and checks it against the configured length limit
+pub fn validate_name(name: &str, max_length: usize) -> Result<String, ActionRejected> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > max_length {
+        Err(ActionRejected::InvalidName { max_length })
+    } else {
+        Ok(name.to_owned())
+    }
+}
+
+/// Builds the player a peer joins a room as.
+/// - Logged in peers are named after their verified login, with its avatar.
+/// - Other peers use the name they asked for, which must be valid.
+fn new_player(
+    peer: &Peer,
+    app_state: &AppState,
+    name: &str,
+    is_spectator: bool,
+) -> Result<Player, ActionRejected> {
+    let max_length = app_state.config.limits.name_length;
+    let user = peer.user(app_state);
+    let name = match &user {
+        Some(user) => user.name.trim().chars().take(max_length).collect(),
+        None => validate_name(name, max_length)?,
+    };
+
+    Ok(Player {
+        id: peer.participant_id(app_state),
+        name,
+        vote: None,
+        has_voted: false,
+        is_spectator,
+        verified: user.is_some(),
+        avatar_url: user.and_then(|user| user.picture),
+        profile: Profile::default(),
+    })
+}
+
+/// A client connected over Socket.IO or the plain WebSocket protocol
+#[derive(Clone)]
+pub enum Peer {
+    SocketIo(SocketRef),
+    WebSocket(Arc<WsPeer>),
+}
+
+impl Peer {
+    /// The ID of the peer's connection, only ever used server side
+    pub fn id(&self) -> String {
+        match self {
+            Self::SocketIo(socket) => socket.id.to_string(),
+            Self::WebSocket(peer) => peer.id.clone(),
+        }
+    }
+
+    /// The participant ID the peer is known by in rooms
+    pub fn participant_id(&self, app_state: &AppState) -> String {
+        app_state.participants.participant_id(&self.id())
+    }
+
+    /// The user the peer logged in as, if any
+    pub fn user(&self, app_state: &AppState) -> Option<AuthUser> {
+        app_state.participants.user(&self.id())
+    }
+
+    /// Emits an event to the peer alone
+    pub fn emit<E: SocketEvent>(&self, data: &E::Data) {
+        match self {
+            Self::SocketIo(socket) => emit_event_direct::<E>(socket, data),
+            Self::WebSocket(peer) => peer.emit::<E>(data),
+        }
+    }
+
+    /// Adds the peer to a room's broadcasts
+    pub fn join(&self, hub: &RoomHub, room_id: Uuid) {
+        match self {
+            Self::SocketIo(socket) => socket.join(room_id.to_string()),
+            Self::WebSocket(peer) => peer.join(hub, room_id),
+        }
+    }
+
+    /// Removes the peer from a room's broadcasts
+    pub fn leave(&self, room_id: Uuid) {
+        match self {
+            Self::SocketIo(socket) => socket.leave(room_id.to_string()),
+            Self::WebSocket(peer) => peer.leave(room_id),
+        }
+    }
+}
+
 /// Emits an event directly to a single socket.
 /// Enforces type safety for event data and name
 fn emit_event_direct<E: SocketEvent>(socket: &SocketRef, data: &E::Data) {
@@ -38,15 +162,62 @@ fn emit_event_direct<E: SocketEvent>(socket: &SocketRef, data: &E::Data) {
     }
 }
 
-/// Emits an event to all sockets in a room.
+/// Emits an event to every member of a room, whether they are connected
+/// over Socket.IO or subscribed through the room hub.
+/// Enforces type safety for event data and name
+pub async fn emit_event_broadcast<E: SocketEvent>(
+    io: &SocketIo,
+    hub: &RoomHub,
+    room_id: Uuid,
+    data: &E::Data,
+) where
+    E::Data: Sync + Send,
+{
+    if let Err(err) = io.to(room_id.to_string()).emit(E::EVENT, data).await {
+        error!("Failed to emit {}: {}", E::EVENT, err);
+    }
+    hub.publish::<E>(room_id, data);
+}
+
+/// Broadcasts what changed in a room since it was last broadcast as a sequenced delta.
+/// - Votes stay hidden until the cards are revealed.
+/// - Nothing is sent if nothing players can see has changed.
+/// - Every `SNAPSHOT_INTERVAL` deltas the whole room follows as a "roomSnapshot",
+///   so clients that missed a delta recover even without resyncing.
+pub async fn broadcast_changes<E>(io: &SocketIo, hub: &RoomHub, room: &mut Room)
+where
+    E: SocketEvent<Data = RoomDelta>,
+{
+    let Some(delta) = room.next_delta(visible_room(room)) else {
+        return;
+    };
+    emit_event_broadcast::<E>(io, hub, room.id, &delta).await;
+    if room.snapshot_due() {
+        emit_event_broadcast::<RoomSnapshotEvent>(io, hub, room.id, &visible_room(room)).await;
+    }
+}
+
+/// Emits an event to every connected client.
 /// Enforces type safety for event data and name
-async fn emit_event_broadcast<E: SocketEvent>(socket: &SocketRef, room: String, data: &E::Data)
+pub async fn emit_event_global<E: SocketEvent>(io: &SocketIo, hub: &RoomHub, data: &E::Data)
 where
     E::Data: Sync + Send,
 {
-    if let Err(err) = socket.within(room).emit(E::EVENT, data).await {
+    if let Err(err) = io.emit(E::EVENT, data).await {
         error!("Failed to emit {}: {}", E::EVENT, err);
     }
+    hub.publish_all::<E>(data);
+}
+
+/// Notifies a room that has been removed from the state that it is closed,
+/// removes its sockets from the Socket.IO room and closes its hub channel.
+pub async fn notify_room_closed(io: &SocketIo, hub: &RoomHub, room_id: Uuid, reason: &str) {
+    emit_event_broadcast::<RoomClosedEvent>(io, hub, room_id, &reason.to_owned()).await;
+    io.to(room_id.to_string())
+        .leave(room_id.to_string())
+        .await
+        .ok();
+    hub.close(room_id);
 }
 
 /// Get a mutable reference to a room from it's ID return an error if
@@ -66,287 +237,2270 @@ fn get_room_mut<'a>(
     }
 }
 
-async fn elect_new_host(room: &mut Room, socket: &SocketRef) -> Result<(), RoomEmptyError> {
+/// Records an accepted estimate on the current story.
+/// - Writes it back to the story's tracker when the card set is numeric.
+fn record_estimate(room: &mut Room, points: u8, app_state: &AppState) {
+    let numeric = room.settings.card_set == NUMERIC_CARD_SET;
+    let Some(story) = room.current_story_mut() else {
+        return;
+    };
+    story.estimate = Some(points);
+    info!("Story {} estimated at {}", story.id, points);
+
+    if numeric && let Some(source) = &story.source {
+        app_state.integrations.write_estimate(source, points);
+    }
+}
+
+/// Reveals the cards in a room.
+/// - Emits "cardsRevealed" to the room.
+/// - Records the round on the room and the current story, if one is selected.
+/// - Notifies webhooks and chat.
+pub async fn reveal_cards(room: &mut Room, io: &SocketIo, app_state: &AppState) {
+    room.cards_revealed = true;
+    room.reveal_at = None;
+    let results = VoteStatistics::for_room(room);
+    room.add_round(results.clone());
+    room.results = Some(results);
+    if let Some(chat) = &room.webhooks.chat {
+        app_state
+            .integrations
+            .chat
+            .notify(chat, &RoundSummary::for_room(room));
+    }
+
+    broadcast_changes::<CardsRevealedEvent>(io, &app_state.hub, room).await;
+    app_state
+        .webhooks
+        .dispatch(room, &WebhookEvent::cards_revealed(room));
+}
+
+/// Applies the room's auto-reveal policy once every player has voted.
+/// - Reveals the cards straight away, or once the grace period is over.
+/// - Emits "allVoted" to the room when the reveal waits for the grace
+///   period or the host.
+async fn apply_auto_reveal(room: &mut Room, io: &SocketIo, app_state: &AppState) {
+    if room.cards_revealed || !room.all_voted() {
+        return;
+    }
+    info!("All players voted in room {}", room.id);
+
+    let reveal_in_secs = match room.settings.auto_reveal {
+        AutoReveal::Immediate => {
+            reveal_cards(room, io, app_state).await;
+            return;
+        }
+        // changing a vote does not restart a grace period already running
+        AutoReveal::AfterGrace { .. } if room.reveal_at.is_some() => return,
+        AutoReveal::AfterGrace { grace_secs } => {
+            room.reveal_at = Some(Utc::now() + TimeDelta::seconds(grace_secs.into()));
+            Some(grace_secs)
+        }
+        AutoReveal::Manual => None,
+    };
+
+    let all_voted = AllVoted {
+        room_id: room.id,
+        reveal_in_secs,
+    };
+    emit_event_broadcast::<AllVotedEvent>(io, &app_state.hub, room.id, &all_voted).await;
+}
+
+async fn elect_new_host(
+    room: &mut Room,
+    io: &SocketIo,
+    hub: &RoomHub,
+) -> Result<(), RoomEmptyError> {
     if let Some(new_host) = room.players.values().next() {
         room.host_id.clone_from(&new_host.id);
         info!("New host elected: {} for room {}", new_host.id, room.id);
         // Notify all players in the room about the new host
-        emit_event_broadcast::<NewHostElectedEvent>(socket, room.id.to_string(), &new_host.id)
-            .await;
+        emit_event_broadcast::<NewHostElectedEvent>(io, hub, room.id, &new_host.id).await;
         Ok(())
     } else {
         Err(RoomEmptyError)
     }
 }
 
+/// Starts the session for a peer on either transport that just connected.
+/// - Resumes the participant ID for a resume token from an earlier connection.
+/// - Checks the login session cookie sent with the handshake, if login is enabled.
+/// - Emits "session" with the participant ID and resume token to the peer.
+pub fn start_session(peer: &Peer, app_state: &AppState, auth: &SessionAuth, headers: &HeaderMap) {
+    let user = app_state
+        .login
+        .as_ref()
+        .and_then(|login| login.user(headers));
+    if let Some(user) = &user {
+        info!("Connection {} is logged in as {}", peer.id(), user.sub);
+    }
+    let session = app_state
+        .participants
+        .connect(&peer.id(), auth.session.as_deref(), user);
+    info!(
+        "Connection {} is participant {}",
+        peer.id(),
+        session.participant_id
+    );
+    peer.emit::<SessionEvent>(&session);
+}
+
+/// Middleware checking the client speaks a supported protocol version.
+/// - Clients send `{ protocol }` as their Socket.IO auth payload.
+/// - Clients that send no version are treated as version 1.
+pub async fn check_protocol(TryData(auth): TryData<ProtocolAuth>) -> Result<(), ProtocolError> {
+    auth.map_err(|err| ProtocolError::Malformed(err.to_string()))
+        .and_then(|auth| protocol::check_version(auth.protocol))
+        .inspect_err(|err| error!("Rejecting connection: {}", err))
+}
+
+/// Connect middleware rejecting Socket.IO handshakes from disallowed origins.
+/// - Clients that send no `Origin` header (CLIs, bots) are not browsers
+///   and are let through.
+pub async fn check_origin(
+    socket: SocketRef,
+    app_state: SocketState<Arc<AppState>>,
+) -> Result<(), OriginNotAllowedError> {
+    let origin = socket
+        .req_parts()
+        .headers
+        .get(ORIGIN)
+        .map(|origin| origin.to_str().unwrap_or_default());
+
+    match origin {
+        Some(origin) if !app_state.config.origin_allowed(origin) => {
+            error!("Rejecting connection from origin {}", origin);
+            Err(OriginNotAllowedError {
+                origin: origin.to_owned(),
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
 /// Handles the creation of a new room.
 /// - Generates a new room ID and host player.
 /// - Adds the room to the shared state.
+/// - Only logged in players can create rooms that require login.
 /// - Joins the socket to the room and emits a "roomCreated" event.
 pub async fn handle_create_room(
     socket: SocketRef,
     Data(payload): Data<CreateRoomEvent>,
     app_state: SocketState<Arc<AppState>>,
 ) {
-    let room_id = Uuid::new_v4();
-    let player = Player {
-        id: socket.id.to_string(),
-        name: payload.name.clone(),
-        vote: None,
-        has_voted: false,
-        is_spectator: payload.is_spectator,
+    create_room(&Peer::SocketIo(socket), &app_state, payload).await;
+}
+
+/// Creates a room for a peer on either transport, see `handle_create_room`.
+pub async fn create_room(peer: &Peer, app_state: &AppState, payload: CreateRoomEvent) {
+    let peer_id = peer.participant_id(app_state);
+    let limits = &app_state.config.limits;
+    let player = match new_player(peer, app_state, &payload.name, payload.is_spectator) {
+        Ok(player) => player,
+        Err(err) => {
+            peer.emit::<ActionRejectedEvent>(&err);
+            return;
+        }
     };
 
-    let mut players = HashMap::new();
-    players.insert(socket.id.to_string(), player.clone());
+    let settings = RoomSettings::with_card_set(payload.card_set);
+    if let Err(err) = settings.validate() {
+        peer.emit::<ActionRejectedEvent>(&err);
+        return;
+    }
 
-    let room = Room {
-        id: room_id,
-        host_id: socket.id.to_string(),
-        players,
-        cards_revealed: false,
-        card_set: payload.card_set.clone(),
-    };
+    // a host who is not logged in could never get into their own room
+    if payload.require_login && !player.verified {
+        peer.emit::<ActionRejectedEvent>(&ActionRejected::LoginRequired);
+        return;
+    }
+
+    if app_state.is_draining() {
+        info!("Server draining, refusing room for {}", peer_id);
+        peer.emit::<ActionRejectedEvent>(&ActionRejected::ServerDraining);
+        return;
+    }
+
+    let mut rooms = app_state.rooms.lock().await;
+    if rooms.len() >= limits.rooms {
+        error!("Room limit reached, refusing room for {}", peer_id);
+        peer.emit::<ActionRejectedEvent>(&ActionRejected::TooManyRooms { max: limits.rooms });
+        return;
+    }
+
+    let mut players = HashMap::new();
+    players.insert(peer_id.clone(), player.clone());
 
-    app_state.rooms.lock().await.insert(room_id, room.clone());
+    let room = new_room(peer_id, players, settings, payload.require_login);
+    let room_id = room.id;
+    rooms.insert(room_id, room.clone());
+    drop(rooms);
 
-    socket.join(room_id.to_string());
+    peer.join(&app_state.hub, room_id);
     info!("Room created: {}, host: {}", room_id, player.name);
 
-    emit_event_direct::<RoomCreatedEvent>(&socket, &room);
+    app_state.webhooks.dispatch(
+        &room,
+        &WebhookEvent::RoomCreated {
+            host_name: player.name,
+            card_set: room.settings.card_set.clone(),
+        },
+    );
+
+    peer.emit::<RoomCreatedEvent>(&room);
+}
+
+/// Builds an empty round for a new room with the given host and players
+fn new_room(
+    host_id: String,
+    players: HashMap<String, Player>,
+    settings: RoomSettings,
+    require_login: bool,
+) -> Room {
+    let mut room = Room {
+        id: Uuid::new_v4(),
+        host_id,
+        players,
+        cards_revealed: false,
+        settings,
+        results: None,
+        estimate: None,
+        rounds: Vec::new(),
+        convergence: Convergence::default(),
+        reveal_at: None,
+        vote_changes: 0,
+        created_at: Utc::now(),
+        last_activity: Utc::now(),
+        stories: Vec::new(),
+        current_story: None,
+        require_login,
+        seq: 0,
+        published: None,
+        webhooks: RoomWebhooks::new(),
+    };
+    room.set_published(visible_room(&room));
+    room
 }
 
 /// Handles a player joining a room.
 /// - Validates the room ID.
 /// - Adds the player to the room if not already present.
+/// - Refuses players who are not logged in when the room requires login.
+/// - Holds back any reveal waiting for the grace period when a voter joins.
 /// - Emits "playerJoined" and "roomState" events.
 /// - Emits "roomNotFound" if the room does not exist or the ID is invalid.
 pub async fn handle_join_room(
     socket: SocketRef,
+    io: SocketIo,
     Data(payload): Data<JoinRoomEvent>,
     app_state: SocketState<Arc<AppState>>,
 ) {
-    info!("Recieved join room from {}", socket.id);
+    join_room(&Peer::SocketIo(socket), &io, &app_state, payload).await;
+}
+
+/// Joins a peer on either transport to a room, see `handle_join_room`.
+pub async fn join_room(peer: &Peer, io: &SocketIo, app_state: &AppState, payload: JoinRoomEvent) {
+    let peer_id = peer.participant_id(app_state);
+    info!("Recieved join room from {}", peer_id);
+
+    let limits = &app_state.config.limits;
+    let player = match new_player(peer, app_state, &payload.name, payload.is_spectator) {
+        Ok(player) => player,
+        Err(err) => {
+            peer.emit::<ActionRejectedEvent>(&err);
+            return;
+        }
+    };
 
     let mut rooms = app_state.rooms.lock().await;
 
     match get_room_mut(&payload.room_id, &mut rooms) {
         Ok(room) => {
-            match room.players.entry(socket.id.to_string()) {
+            let room_full = room.players.len() >= limits.players_per_room;
+            match room.players.entry(peer_id.clone()) {
                 Entry::Occupied(_) => {
-                    info!("Player {} already in room {}", socket.id, room.id);
+                    info!("Player {} already in room {}", peer_id, room.id);
                 }
-                Entry::Vacant(vacant) => {
-                    vacant.insert(Player {
-                        id: socket.id.to_string(),
-                        name: payload.name.clone(),
-                        vote: None,
-                        has_voted: false,
-                        is_spectator: payload.is_spectator,
+                Entry::Vacant(_) if room.require_login && !player.verified => {
+                    info!("Room {} requires login, refusing {}", room.id, peer_id);
+                    peer.emit::<ActionRejectedEvent>(&ActionRejected::LoginRequired);
+                    return;
+                }
+                Entry::Vacant(_) if payload.is_spectator && !room.settings.allow_spectators => {
+                    info!("Room {} has no spectators, refusing {}", room.id, peer_id);
+                    peer.emit::<ActionRejectedEvent>(&ActionRejected::SpectatorsNotAllowed);
+                    return;
+                }
+                Entry::Vacant(_) if room_full => {
+                    info!("Room {} is full, refusing {}", room.id, peer_id);
+                    peer.emit::<ActionRejectedEvent>(&ActionRejected::RoomFull {
+                        max: limits.players_per_room,
                     });
-                    info!("Player {} joined room {}", socket.id, room.id);
-                    socket.join(room.id.to_string());
+                    return;
+                }
+                Entry::Vacant(vacant) => {
+                    let name = player.name.clone();
+                    vacant.insert(player);
+                    if !payload.is_spectator {
+                        room.reveal_at = None;
+                    }
+                    info!("Player {} joined room {}", peer_id, room.id);
+                    peer.join(&app_state.hub, room.id);
+                    room.touch();
+
+                    app_state.webhooks.dispatch(
+                        room,
+                        &WebhookEvent::PlayerJoined {
+                            player_name: name,
+                            is_spectator: payload.is_spectator,
+                            players: room.players.len(),
+                        },
+                    );
                 }
             }
 
+            // rooms restored from the store have no host until someone rejoins
+            if !room.players.contains_key(&room.host_id) {
+                room.host_id.clone_from(&peer_id);
+                info!("Player {} is now host of room {}", peer_id, room.id);
+            }
+
             // emit the moveToRoomEvent to player joining
-            emit_event_direct::<MoveToRoomEvent>(&socket, &room.id.to_string());
+            peer.emit::<MoveToRoomEvent>(&room.id.to_string());
 
-            // emit the updated room state to all players in the room
-            emit_event_broadcast::<PlayerJoinedEvent>(&socket, room.id.to_string(), room).await;
+            // emit the changes to all players in the room, then the whole
+            // room to the player joining
+            broadcast_changes::<PlayerJoinedEvent>(io, &app_state.hub, room).await;
+            peer.emit::<RoomSnapshotEvent>(&visible_room(room));
         }
         Err(_) => {
-            emit_event_direct::<RoomNotFoundEvent>(&socket, &());
+            peer.emit::<RoomNotFoundEvent>(&());
         }
     }
 }
 
+/// Handles a player arriving at a team's room link.
+/// - Opens a room with the team's card set and login setting if the
+///   team has no open room, the first member to arrive becomes host.
+/// - Emits "actionRejected" for invalid names before any room is opened.
+/// - Joins the player to the team's room as with "joinRoom".
+/// - Emits "roomNotFound" if the team does not exist or the ID is invalid.
+pub async fn handle_join_team_room(
+    socket: SocketRef,
+    io: SocketIo,
+    Data(payload): Data<JoinTeamRoomEvent>,
+    app_state: SocketState<Arc<AppState>>,
+) {
+    join_team_room(&Peer::SocketIo(socket), &io, &app_state, payload).await;
+}
+
+/// Joins a peer on either transport to a team's room, see `handle_join_team_room`.
+pub async fn join_team_room(
+    peer: &Peer,
+    io: &SocketIo,
+    app_state: &AppState,
+    payload: JoinTeamRoomEvent,
+) {
+    let peer_id = peer.participant_id(app_state);
+    info!("Recieved join team room from {}", peer_id);
+
+    let limits = &app_state.config.limits;
+    let player = match new_player(peer, app_state, &payload.name, payload.is_spectator) {
+        Ok(player) => player,
+        Err(err) => {
+            peer.emit::<ActionRejectedEvent>(&err);
+            return;
+        }
+    };
+    let mut rooms = app_state.rooms.lock().await;
+    let mut teams = app_state.teams.lock().await;
+
+    let Some(team) = Uuid::parse_str(&payload.team_id)
+        .ok()
+        .and_then(|team_id| teams.get_mut(&team_id))
+    else {
+        peer.emit::<RoomNotFoundEvent>(&());
+        return;
+    };
+
+    let room_id = if let Some(room_id) = team.room_id.filter(|id| rooms.contains_key(id)) {
+        room_id
+    } else {
+        if app_state.is_draining() {
+            info!("Server draining, refusing room for team {}", team.id);
+            peer.emit::<ActionRejectedEvent>(&ActionRejected::ServerDraining);
+            return;
+        }
+        if rooms.len() >= limits.rooms {
+            error!("Room limit reached, refusing room for team {}", team.id);
+            peer.emit::<ActionRejectedEvent>(&ActionRejected::TooManyRooms { max: limits.rooms });
+            return;
+        }
+
+        // nobody is in the room yet, joining makes the first member host
+        let room = new_room(
+            String::new(),
+            HashMap::new(),
+            team.settings.clone(),
+            team.require_login,
+        );
+        info!("Room created: {}, for team: {}", room.id, team.name);
+        app_state.webhooks.dispatch(
+            &room,
+            &WebhookEvent::RoomCreated {
+                host_name: player.name,
+                card_set: room.settings.card_set.clone(),
+            },
+        );
+
+        let room_id = room.id;
+        team.room_id = Some(room_id);
+        rooms.insert(room_id, room);
+        room_id
+    };
+    drop(teams);
+    drop(rooms);
+
+    join_room(
+        peer,
+        io,
+        app_state,
+        JoinRoomEvent {
+            name: payload.name,
+            room_id: room_id.to_string(),
+            is_spectator: payload.is_spectator,
+        },
+    )
+    .await;
+}
+
 /// Handles a player voting in a room.
+/// - Emits "actionRejected" for cards not in the room's card set,
+///   and to spectators.
 /// - Updates the player's vote and voting status.
+/// - Counts a change to an earlier vote towards the round's vote changes.
 /// - Emits "playerVoted" event to the room and the player.
 pub async fn handle_vote(
     socket: SocketRef,
+    io: SocketIo,
     Data(payload): Data<VoteEvent>,
     app_state: SocketState<Arc<AppState>>,
 ) {
-    info!("Recieved vote from {}", socket.id);
+    vote(&Peer::SocketIo(socket), &io, &app_state, payload).await;
+}
+
+/// Records a vote from a peer on either transport, see `handle_vote`.
+pub async fn vote(peer: &Peer, io: &SocketIo, app_state: &AppState, payload: VoteEvent) {
+    let peer_id = peer.participant_id(app_state);
+    info!("Recieved vote from {}", peer_id);
 
     let mut rooms = app_state.rooms.lock().await;
 
     match get_room_mut(&payload.room_id, &mut rooms) {
         Ok(room) => {
-            let Some(player) = room.players.get_mut(&socket.id.to_string()) else {
+            if room.cards_revealed && !room.settings.allow_vote_change_after_reveal {
+                info!("Voting is closed in room {}, refusing {}", room.id, peer_id);
+                peer.emit::<ActionRejectedEvent>(&ActionRejected::VotingClosed);
+                return;
+            }
+            if payload.vote != UNSURE_VOTE && !is_estimate(&room.settings.card_set, payload.vote) {
+                info!("Invalid vote {} in room {}", payload.vote, room.id);
+                peer.emit::<ActionRejectedEvent>(&ActionRejected::InvalidVote {
+                    value: payload.vote,
+                });
+                return;
+            }
+            let Some(player) = room.players.get_mut(&peer_id) else {
                 return;
             };
+            if player.is_spectator {
+                peer.emit::<ActionRejectedEvent>(&ActionRejected::SpectatorCannotVote);
+                return;
+            }
+            if player.vote.is_some_and(|vote| vote != payload.vote) {
+                room.vote_changes += 1;
+            }
             player.vote = Some(payload.vote);
             player.has_voted = true;
-            info!("Player {} voted in room {}", socket.id, room.id);
+            revise_results(room);
+            room.touch();
+            info!("Player {} voted in room {}", peer_id, room.id);
 
-            emit_event_broadcast::<PlayerVotedEvent>(
-                &socket,
-                room.id.to_string(),
-                &clean_votes(room),
-            )
-            .await;
-
-            // if all players have voted emit "cardsRevealed" event
-            if room
-                .players
-                .values()
-                .filter(|p| !p.is_spectator)
-                .all(|p| p.has_voted)
-            {
-                room.cards_revealed = true;
-                info!("All players voted in room {}", room.id);
+            broadcast_changes::<PlayerVotedEvent>(io, &app_state.hub, room).await;
 
-                emit_event_broadcast::<CardsRevealedEvent>(&socket, room.id.to_string(), room)
-                    .await;
-            }
+            apply_auto_reveal(room, io, app_state).await;
         }
 
         Err(_) => {
-            emit_event_direct::<RoomNotFoundEvent>(&socket, &());
+            peer.emit::<RoomNotFoundEvent>(&());
         }
     }
 }
 
-/// Handles revealing cards in a room.
-/// - Only the host can reveal cards.
-/// - Updates the room state and emits "cardsRevealed" event.
-pub async fn handle_reveal_cards(
+/// Handles a player taking back their vote in a room.
+/// - Emits "actionRejected" once the cards are revealed, unless the room
+///   allows changing votes after the reveal.
+/// - Holds back any reveal waiting for the grace period.
+/// - Emits "voteRetracted" to the room.
+pub async fn handle_retract_vote(
     socket: SocketRef,
-    Data(payload): Data<RevealCardsEvent>,
+    io: SocketIo,
+    Data(payload): Data<RetractVoteEvent>,
     app_state: SocketState<Arc<AppState>>,
 ) {
-    info!("Recieved reveal cards from {}", socket.id);
+    retract_vote(&Peer::SocketIo(socket), &io, &app_state, payload).await;
+}
+
+/// Retracts a vote for a peer on either transport, see `handle_retract_vote`.
+pub async fn retract_vote(
+    peer: &Peer,
+    io: &SocketIo,
+    app_state: &AppState,
+    payload: RetractVoteEvent,
+) {
+    let peer_id = peer.participant_id(app_state);
+    info!("Recieved retract vote from {}", peer_id);
 
     let mut rooms = app_state.rooms.lock().await;
 
     match get_room_mut(&payload.room_id, &mut rooms) {
         Ok(room) => {
-            let all_voted = room.players.values().any(|p| p.has_voted);
-
-            if room.host_id == socket.id.to_string() && all_voted {
-                room.cards_revealed = true;
-                info!("Cards revealed in room {}", room.id);
-
-                emit_event_broadcast::<CardsRevealedEvent>(&socket, room.id.to_string(), room)
-                    .await;
-            } else {
-                error!("Cannot reveal cards for room {}", room.id);
+            if room.cards_revealed && !room.settings.allow_vote_change_after_reveal {
+                info!("Voting is closed in room {}, refusing {}", room.id, peer_id);
+                peer.emit::<ActionRejectedEvent>(&ActionRejected::VotingClosed);
+                return;
+            }
+            let Some(player) = room.players.get_mut(&peer_id) else {
+                return;
+            };
+            if player.vote.take().is_none() {
+                return;
             }
+            player.has_voted = false;
+            room.vote_changes += 1;
+            room.reveal_at = None;
+            revise_results(room);
+            room.touch();
+            info!(
+                "Player {} retracted their vote in room {}",
+                peer_id, room.id
+            );
+
+            broadcast_changes::<VoteRetractedEvent>(io, &app_state.hub, room).await;
         }
+
         Err(_) => {
-            emit_event_direct::<RoomNotFoundEvent>(&socket, &());
+            peer.emit::<RoomNotFoundEvent>(&());
         }
     }
 }
 
-/// Handles resetting votes in a room.
-/// - Only the host can reset votes.
-/// - Clears all player votes and voting status.
-/// - Emits "votesReset" event.
-pub async fn handle_reset_votes(
+/// Handles a player switching between voting and spectating.
+/// - Emits "actionRejected" when becoming a spectator in a room that
+///   does not allow spectators.
+/// - Clears the player's vote when they become a spectator.
+/// - Holds back any reveal waiting for the grace period when they start voting.
+/// - Emits "spectatorChanged" to the room, then applies the auto-reveal
+///   policy as the remaining players may all have voted.
+pub async fn handle_set_spectator(
     socket: SocketRef,
-    Data(payload): Data<ResetVotesEvent>,
+    io: SocketIo,
+    Data(payload): Data<SetSpectatorEvent>,
     app_state: SocketState<Arc<AppState>>,
 ) {
-    info!("Recieved reset votes from {}", socket.id);
+    set_spectator(&Peer::SocketIo(socket), &io, &app_state, payload).await;
+}
+
+/// Switches a peer between voting and spectating on either transport,
+/// see `handle_set_spectator`.
+pub async fn set_spectator(
+    peer: &Peer,
+    io: &SocketIo,
+    app_state: &AppState,
+    payload: SetSpectatorEvent,
+) {
+    let peer_id = peer.participant_id(app_state);
+    info!("Recieved set spectator from {}", peer_id);
 
     let mut rooms = app_state.rooms.lock().await;
 
     match get_room_mut(&payload.room_id, &mut rooms) {
         Ok(room) => {
-            if room.host_id == socket.id.to_string() {
-                room.cards_revealed = false;
-                for player in room.players.values_mut() {
-                    player.vote = None;
-                    player.has_voted = false;
-                }
-                info!("Votes reset in room {}", room.id);
-
-                emit_event_broadcast::<VotesResetEvent>(&socket, room.id.to_string(), room).await;
+            if payload.is_spectator && !room.settings.allow_spectators {
+                peer.emit::<ActionRejectedEvent>(&ActionRejected::SpectatorsNotAllowed);
+                return;
+            }
+            let Some(player) = room.players.get_mut(&peer_id) else {
+                return;
+            };
+            if player.is_spectator == payload.is_spectator {
+                return;
+            }
+            player.is_spectator = payload.is_spectator;
+            if payload.is_spectator {
+                player.vote = None;
+                player.has_voted = false;
             } else {
-                error!("Player {} is not the host of room {}", socket.id, room.id);
+                room.reveal_at = None;
             }
+            revise_results(room);
+            room.touch();
+            info!(
+                "Player {} switched to {} in room {}",
+                peer_id,
+                if payload.is_spectator {
+                    "spectating"
+                } else {
+                    "voting"
+                },
+                room.id
+            );
+
+            broadcast_changes::<SpectatorChangedEvent>(io, &app_state.hub, room).await;
+
+            apply_auto_reveal(room, io, app_state).await;
         }
+
         Err(_) => {
-            emit_event_direct::<RoomNotFoundEvent>(&socket, &());
+            peer.emit::<RoomNotFoundEvent>(&());
         }
     }
 }
 
-/// Handles player disconnects.
-/// - Removes the player from all rooms.
-/// - Emits "playerDisconnected" event.
-/// - Elects a new host if the disconnected player was the host and notifies the room.
-pub async fn handle_disconnect(socket: SocketRef, app_state: SocketState<Arc<AppState>>) {
-    info!("Client disconnected: {}", socket.id);
+/// Handles a player changing how they appear in a room.
+/// - Emits "actionRejected" if the name or profile is invalid.
+/// - Logged in players keep the name from their login.
+/// - Emits "profileUpdated" to the room.
+pub async fn handle_update_profile(
+    socket: SocketRef,
+    io: SocketIo,
+    Data(payload): Data<UpdateProfileEvent>,
+    app_state: SocketState<Arc<AppState>>,
+) {
+    update_profile(&Peer::SocketIo(socket), &io, &app_state, payload).await;
+}
+
+/// Updates a peer's profile on either transport, see `handle_update_profile`.
+pub async fn update_profile(
+    peer: &Peer,
+    io: &SocketIo,
+    app_state: &AppState,
+    payload: UpdateProfileEvent,
+) {
+    let peer_id = peer.participant_id(app_state);
+    info!("Recieved update profile from {}", peer_id);
 
-    // Remove the player from all rooms they are in
     let mut rooms = app_state.rooms.lock().await;
-    let mut empty_room_id: Option<Uuid> = None;
 
-    for room in rooms.values_mut() {
-        if room.players.remove(&socket.id.to_string()).is_some() {
-            info!("Player {} removed from room {}", socket.id, room.id);
+    match get_room_mut(&payload.room_id, &mut rooms) {
+        Ok(room) => {
+            let Some(player) = room.players.get_mut(&peer_id) else {
+                return;
+            };
+            let name = if player.verified {
+                player.name.clone()
+            } else {
+                match validate_name(&payload.name, app_state.config.limits.name_length) {
+                    Ok(name) => name,
+                    Err(err) => {
+                        peer.emit::<ActionRejectedEvent>(&err);
+                        return;
+                    }
+                }
+            };
+            if let Err(err) = payload.profile.validate() {
+                peer.emit::<ActionRejectedEvent>(&err);
+                return;
+            }
 
-            emit_event_broadcast::<PlayerDisconnectedEvent>(&socket, room.id.to_string(), room)
-                .await;
+            player.name = name;
+            player.profile = payload.profile;
+            room.touch();
+            info!(
+                "Player {} updated their profile in room {}",
+                peer_id, room.id
+            );
 
-            // should elect a new host if the disconnected player was the host
-            if room.host_id == socket.id.to_string()
-                && matches!(elect_new_host(room, &socket).await, Err(RoomEmptyError))
-            {
-                empty_room_id = Some(room.id);
-            }
+            broadcast_changes::<ProfileUpdatedEvent>(io, &app_state.hub, room).await;
+        }
+
+        Err(_) => {
+            peer.emit::<RoomNotFoundEvent>(&());
         }
     }
+}
 
-    if let Some(room_id) = empty_room_id {
-        info!("Room {} is now empty, removing it", room_id);
-        rooms.remove(&room_id);
+/// Works out the results again when a vote changes after the reveal
+fn revise_results(room: &mut Room) {
+    if !room.cards_revealed {
+        return;
     }
+    let results = VoteStatistics::for_room(room);
+    room.revise_round(results.clone());
+    room.results = Some(results);
 }
 
-pub async fn handle_player_exit(
+/// Handles revealing cards in a room.
+/// - Only the host can reveal cards.
+/// - Emits "actionRejected" to the host if too few players have voted
+///   for the room's reveal quorum, or the cards are already revealed.
+/// - Updates the room state and emits "cardsRevealed" event.
+pub async fn handle_reveal_cards(
     socket: SocketRef,
-    Data(payload): Data<PlayerExitEvent>,
+    io: SocketIo,
+    Data(payload): Data<RevealCardsEvent>,
     app_state: SocketState<Arc<AppState>>,
 ) {
-    info!("Player {} is exiting room {}", socket.id, payload.room_id);
+    reveal(&Peer::SocketIo(socket), &io, &app_state, payload).await;
+}
+
+/// Reveals the cards for a host on either transport, see `handle_reveal_cards`.
+pub async fn reveal(peer: &Peer, io: &SocketIo, app_state: &AppState, payload: RevealCardsEvent) {
+    let peer_id = peer.participant_id(app_state);
+    info!("Recieved reveal cards from {}", peer_id);
 
     let mut rooms = app_state.rooms.lock().await;
-    let mut empty_room_id: Option<Uuid> = None;
 
     match get_room_mut(&payload.room_id, &mut rooms) {
         Ok(room) => {
-            if !room.players.contains_key(&socket.id.to_string()) {
+            if room.host_id != peer_id {
+                error!("Player {} is not the host of room {}", peer_id, room.id);
                 return;
             }
-
-            room.players.remove(&socket.id.to_string());
-            info!("Player {} exited room {}", socket.id, &room.id);
-
-            socket.leave(room.id.to_string());
-
-            emit_event_broadcast::<PlayerDisconnectedEvent>(&socket, room.id.to_string(), room)
-                .await;
-
-            // If the exiting player was the host, elect a new host
-            if room.host_id == socket.id.to_string()
-                && matches!(elect_new_host(room, &socket).await, Err(RoomEmptyError))
-            {
-                empty_room_id = Some(room.id);
+            if room.cards_revealed {
+                peer.emit::<ActionRejectedEvent>(&ActionRejected::AlreadyRevealed);
+                return;
+            }
+            let (votes, required) = room.quorum();
+            if votes < required {
+                info!(
+                    "Quorum not met in room {}, {} of {}",
+                    room.id, votes, required
+                );
+                peer.emit::<ActionRejectedEvent>(&ActionRejected::QuorumNotMet { votes, required });
+                return;
             }
+
+            room.touch();
+            info!("Cards revealed in room {}", room.id);
+            reveal_cards(room, io, app_state).await;
         }
         Err(_) => {
-            emit_event_direct::<RoomNotFoundEvent>(&socket, &());
+            peer.emit::<RoomNotFoundEvent>(&());
         }
     }
+}
 
-    if let Some(room_id) = empty_room_id {
-        info!("Room {} is now empty, removing it", room_id);
-        rooms.remove(&room_id);
+/// Handles resetting votes in a room.
+/// - Only the host can reset votes.
+/// - Clears all player votes and voting status.
+/// - Starts the room's round history over, keeping the current story's rounds.
+/// - Emits "votesReset" event.
+pub async fn handle_reset_votes(
+    socket: SocketRef,
+    io: SocketIo,
+    Data(payload): Data<ResetVotesEvent>,
+    app_state: SocketState<Arc<AppState>>,
+) {
+    reset_votes(&Peer::SocketIo(socket), &io, &app_state, payload).await;
+}
+
+/// Resets the votes for a host on either transport, see `handle_reset_votes`.
+pub async fn reset_votes(
+    peer: &Peer,
+    io: &SocketIo,
+    app_state: &AppState,
+    payload: ResetVotesEvent,
+) {
+    let peer_id = peer.participant_id(app_state);
+    info!("Recieved reset votes from {}", peer_id);
+
+    let mut rooms = app_state.rooms.lock().await;
+
+    match get_room_mut(&payload.room_id, &mut rooms) {
+        Ok(room) => {
+            if room.host_id == peer_id {
+                clear_votes(room);
+                room.restart_rounds();
+                info!("Votes reset in room {}", room.id);
+
+                broadcast_changes::<VotesResetEvent>(io, &app_state.hub, room).await;
+                app_state.webhooks.dispatch(room, &WebhookEvent::VotesReset);
+            } else {
+                error!("Player {} is not the host of room {}", peer_id, room.id);
+            }
+        }
+        Err(_) => {
+            peer.emit::<RoomNotFoundEvent>(&());
+        }
+    }
+}
+
+/// Clears the votes in a room and hides the cards for a new round,
+/// rounds already recorded are kept
+fn clear_votes(room: &mut Room) {
+    room.cards_revealed = false;
+    room.reveal_at = None;
+    room.results = None;
+    room.estimate = None;
+    room.vote_changes = 0;
+    for player in room.players.values_mut() {
+        player.vote = None;
+        player.has_voted = false;
+    }
+    room.touch();
+}
+
+/// Handles player disconnects.
+/// - Removes the player from all rooms, a client resuming its session
+///   rejoins them under the same participant ID.
+/// - Emits "playerDisconnected" event.
+/// - Elects a new host if the disconnected player was the host and notifies the room.
+pub async fn handle_disconnect(
+    socket: SocketRef,
+    io: SocketIo,
+    app_state: SocketState<Arc<AppState>>,
+) {
+    disconnect(&Peer::SocketIo(socket), &io, &app_state).await;
+}
+
+/// Removes a disconnected peer on either transport, see `handle_disconnect`.
+pub async fn disconnect(peer: &Peer, io: &SocketIo, app_state: &AppState) {
+    let peer_id = peer.participant_id(app_state);
+    info!("Client disconnected: {}", peer_id);
+    // the participant can be resumed by the client's next connection,
+    // but is not kept in any room meanwhile
+    app_state.participants.disconnect(&peer.id());
+
+    // Remove the player from all rooms they are in
+    let mut rooms = app_state.rooms.lock().await;
+    let mut empty_room_id: Option<Uuid> = None;
+
+    for room in rooms.values_mut() {
+        if let Some(player) = room.players.remove(&peer_id) {
+            info!("Player {} removed from room {}", peer_id, room.id);
+            app_state.webhooks.dispatch(
+                room,
+                &WebhookEvent::PlayerLeft {
+                    player_name: player.name,
+                    players: room.players.len(),
+                },
+            );
+
+            // should elect a new host if the disconnected player was the host
+            if room.host_id == peer_id
+                && matches!(
+                    elect_new_host(room, io, &app_state.hub).await,
+                    Err(RoomEmptyError)
+                )
+            {
+                empty_room_id = Some(room.id);
+            } else {
+                broadcast_changes::<PlayerDisconnectedEvent>(io, &app_state.hub, room).await;
+            }
+        }
+    }
+
+    if let Some(room_id) = empty_room_id {
+        info!("Room {} is now empty, removing it", room_id);
+        app_state.hub.close(room_id);
+        rooms.remove(&room_id);
+    }
+}
+
+pub async fn handle_player_exit(
+    socket: SocketRef,
+    io: SocketIo,
+    Data(payload): Data<PlayerExitEvent>,
+    app_state: SocketState<Arc<AppState>>,
+) {
+    exit_room(&Peer::SocketIo(socket), &io, &app_state, payload).await;
+}
+
+/// Removes a peer on either transport from a room, see `handle_player_exit`.
+pub async fn exit_room(peer: &Peer, io: &SocketIo, app_state: &AppState, payload: PlayerExitEvent) {
+    let peer_id = peer.participant_id(app_state);
+    info!("Player {} is exiting room {}", peer_id, payload.room_id);
+
+    let mut rooms = app_state.rooms.lock().await;
+    let mut empty_room_id: Option<Uuid> = None;
+
+    match get_room_mut(&payload.room_id, &mut rooms) {
+        Ok(room) => {
+            let Some(player) = room.players.remove(&peer_id) else {
+                return;
+            };
+            info!("Player {} exited room {}", peer_id, &room.id);
+            app_state.webhooks.dispatch(
+                room,
+                &WebhookEvent::PlayerLeft {
+                    player_name: player.name,
+                    players: room.players.len(),
+                },
+            );
+
+            peer.leave(room.id);
+
+            // If the exiting player was the host, elect a new host
+            if room.host_id == peer_id
+                && matches!(
+                    elect_new_host(room, io, &app_state.hub).await,
+                    Err(RoomEmptyError)
+                )
+            {
+                empty_room_id = Some(room.id);
+            } else {
+                broadcast_changes::<PlayerDisconnectedEvent>(io, &app_state.hub, room).await;
+            }
+        }
+        Err(_) => {
+            peer.emit::<RoomNotFoundEvent>(&());
+        }
+    }
+
+    if let Some(room_id) = empty_room_id {
+        info!("Room {} is now empty, removing it", room_id);
+        app_state.hub.close(room_id);
+        rooms.remove(&room_id);
+    }
+}
+
+/// Handles a client asking for the whole room after missing a delta.
+/// - Emits "roomSnapshot" with the room as players may see it.
+/// - Emits "roomNotFound" if the room does not exist or the client is not in it.
+pub async fn handle_resync(
+    socket: SocketRef,
+    Data(payload): Data<ResyncEvent>,
+    app_state: SocketState<Arc<AppState>>,
+) {
+    resync(&Peer::SocketIo(socket), &app_state, payload).await;
+}
+
+/// Sends the whole room to a peer on either transport, see `handle_resync`.
+pub async fn resync(peer: &Peer, app_state: &AppState, payload: ResyncEvent) {
+    let peer_id = peer.participant_id(app_state);
+    info!("Recieved resync from {}", peer_id);
+
+    let mut rooms = app_state.rooms.lock().await;
+
+    match get_room_mut(&payload.room_id, &mut rooms) {
+        Ok(room) if room.players.contains_key(&peer_id) => {
+            peer.emit::<RoomSnapshotEvent>(&visible_room(room));
+        }
+        _ => {
+            peer.emit::<RoomNotFoundEvent>(&());
+        }
+    }
+}
+
+/// Handles the host registering a webhook on a room.
+/// - Only the host can register webhooks.
+/// - Validates the URL and the room's webhook limit.
+/// - Emits "webhooksUpdated" with the URLs and signing secret to the host.
+pub async fn handle_add_webhook(
+    socket: SocketRef,
+    Data(payload): Data<AddWebhookEvent>,
+    app_state: SocketState<Arc<AppState>>,
+) {
+    add_webhook(&Peer::SocketIo(socket), &app_state, payload).await;
+}
+
+/// Registers a webhook for a host on either transport, see `handle_add_webhook`.
+pub async fn add_webhook(peer: &Peer, app_state: &AppState, payload: AddWebhookEvent) {
+    let peer_id = peer.participant_id(app_state);
+    info!("Recieved add webhook from {}", peer_id);
+
+    let mut rooms = app_state.rooms.lock().await;
+
+    match get_room_mut(&payload.room_id, &mut rooms) {
+        Ok(room) => {
+            if room.host_id != peer_id {
+                error!("Player {} is not the host of room {}", peer_id, room.id);
+                return;
+            }
+
+            let url = match webhooks::validate_room_url(&payload.url) {
+                Ok(url) => url,
+                Err(message) => {
+                    peer.emit::<ActionRejectedEvent>(&ActionRejected::InvalidWebhook { message });
+                    return;
+                }
+            };
+
+            if !room.webhooks.urls.contains(&url) {
+                let max = app_state.config.limits.webhooks_per_room;
+                if room.webhooks.urls.len() >= max {
+                    peer.emit::<ActionRejectedEvent>(&ActionRejected::TooManyWebhooks { max });
+                    return;
+                }
+                room.webhooks.urls.push(url);
+                info!("Webhook added to room {}", room.id);
+            }
+
+            peer.emit::<WebhooksUpdatedEvent>(&room.webhooks);
+        }
+        Err(_) => {
+            peer.emit::<RoomNotFoundEvent>(&());
+        }
+    }
+}
+
+/// Handles the host removing a webhook from a room.
+/// - Only the host can remove webhooks.
+/// - Emits "webhooksUpdated" to the host.
+pub async fn handle_remove_webhook(
+    socket: SocketRef,
+    Data(payload): Data<RemoveWebhookEvent>,
+    app_state: SocketState<Arc<AppState>>,
+) {
+    remove_webhook(&Peer::SocketIo(socket), &app_state, payload).await;
+}
+
+/// Removes a webhook for a host on either transport, see `handle_remove_webhook`.
+pub async fn remove_webhook(peer: &Peer, app_state: &AppState, payload: RemoveWebhookEvent) {
+    let peer_id = peer.participant_id(app_state);
+    info!("Recieved remove webhook from {}", peer_id);
+
+    let mut rooms = app_state.rooms.lock().await;
+
+    match get_room_mut(&payload.room_id, &mut rooms) {
+        Ok(room) => {
+            if room.host_id != peer_id {
+                error!("Player {} is not the host of room {}", peer_id, room.id);
+                return;
+            }
+
+            let url = webhooks::validate_room_url(&payload.url).unwrap_or(payload.url);
+            room.webhooks.urls.retain(|existing| *existing != url);
+            info!("Webhook removed from room {}", room.id);
+
+            peer.emit::<WebhooksUpdatedEvent>(&room.webhooks);
+        }
+        Err(_) => {
+            peer.emit::<RoomNotFoundEvent>(&());
+        }
+    }
+}
+
+/// Handles the host setting or clearing the room's chat webhook.
+/// - Only the host can change the chat webhook.
+/// - Emits "webhooksUpdated" to the host.
+pub async fn handle_set_chat_webhook(
+    socket: SocketRef,
+    Data(payload): Data<SetChatWebhookEvent>,
+    app_state: SocketState<Arc<AppState>>,
+) {
+    set_chat_webhook(&Peer::SocketIo(socket), &app_state, payload).await;
+}
+
+/// Changes the chat webhook for a host on either transport, see `handle_set_chat_webhook`.
+pub async fn set_chat_webhook(peer: &Peer, app_state: &AppState, payload: SetChatWebhookEvent) {
+    let peer_id = peer.participant_id(app_state);
+    info!("Recieved set chat webhook from {}", peer_id);
+
+    let mut rooms = app_state.rooms.lock().await;
+
+    match get_room_mut(&payload.room_id, &mut rooms) {
+        Ok(room) => {
+            if room.host_id != peer_id {
+                error!("Player {} is not the host of room {}", peer_id, room.id);
+                return;
+            }
+
+            let chat = match payload.chat {
+                Some(mut chat) => match webhooks::validate_room_url(&chat.url) {
+                    Ok(url) => {
+                        chat.url = url;
+                        Some(chat)
+                    }
+                    Err(message) => {
+                        peer.emit::<ActionRejectedEvent>(&ActionRejected::InvalidWebhook {
+                            message,
+                        });
+                        return;
+                    }
+                },
+                None => None,
+            };
+            room.webhooks.chat = chat;
+            info!("Chat webhook updated in room {}", room.id);
+
+            peer.emit::<WebhooksUpdatedEvent>(&room.webhooks);
+        }
+        Err(_) => {
+            peer.emit::<RoomNotFoundEvent>(&());
+        }
+    }
+}
+
+/// Checks the peer is the host of a room before a slow integration call.
+/// - Emits "roomNotFound" if the room does not exist.
+async fn check_host(peer: &Peer, app_state: &AppState, room_id: &str) -> bool {
+    let peer_id = peer.participant_id(app_state);
+    match get_room_mut(room_id, &mut *app_state.rooms.lock().await) {
+        Ok(room) if room.host_id != peer_id => {
+            error!("Player {} is not the host of room {}", peer_id, room.id);
+            false
+        }
+        Ok(_) => true,
+        Err(_) => {
+            peer.emit::<RoomNotFoundEvent>(&());
+            false
+        }
+    }
+}
+
+/// Adds imported stories to a room's queue.
+/// - Rejects the import if the integration failed.
+/// - Drops the stories if the peer stopped being host during the import.
+/// - Adds as many stories as the room's story limit allows.
+/// - Selects the first story if none is selected.
+/// - Emits "storiesUpdated" to the room.
+async fn add_stories(
+    peer: &Peer,
+    io: &SocketIo,
+    app_state: &AppState,
+    room_id: &str,
+    result: Result<Vec<Story>, IntegrationError>,
+) {
+    let stories = match result {
+        Ok(stories) => stories,
+        Err(err) => {
+            error!("Story import failed: {}", err);
+            peer.emit::<ActionRejectedEvent>(&ActionRejected::IntegrationFailed {
+                message: err.to_string(),
+            });
+            return;
+        }
+    };
+
+    let mut rooms = app_state.rooms.lock().await;
+
+    match get_room_mut(room_id, &mut rooms) {
+        // the host may have left while the tracker was searched
+        Ok(room) if room.host_id != peer.participant_id(app_state) => {
+            info!(
+                "Host changed in room {}, dropping imported stories",
+                room.id
+            );
+        }
+        Ok(room) => {
+            let max = app_state.config.limits.stories_per_room;
+            let space = max.saturating_sub(room.stories.len());
+            if space < stories.len() {
+                peer.emit::<ActionRejectedEvent>(&ActionRejected::TooManyStories { max });
+            }
+            room.stories.extend(stories.into_iter().take(space));
+            if room.current_story.is_none() {
+                room.current_story = room.stories.first().map(|story| story.id);
+                room.restart_rounds();
+            }
+            room.touch();
+            info!("Imported stories into room {}", room.id);
+
+            broadcast_changes::<StoriesUpdatedEvent>(io, &app_state.hub, room).await;
+        }
+        Err(_) => {
+            peer.emit::<RoomNotFoundEvent>(&());
+        }
+    }
+}
+
+/// Handles the host importing Jira issues into the story queue.
+/// - Only the host can import issues.
+/// - Searches Jira without holding the rooms lock.
+pub async fn handle_import_jira_issues(
+    socket: SocketRef,
+    io: SocketIo,
+    Data(payload): Data<ImportJiraIssuesEvent>,
+    app_state: SocketState<Arc<AppState>>,
+) {
+    import_jira_issues(&Peer::SocketIo(socket), &io, &app_state, payload).await;
+}
+
+/// Imports Jira issues for a host on either transport, see `handle_import_jira_issues`.
+pub async fn import_jira_issues(
+    peer: &Peer,
+    io: &SocketIo,
+    app_state: &AppState,
+    payload: ImportJiraIssuesEvent,
+) {
+    info!(
+        "Recieved import Jira issues from {}",
+        peer.participant_id(app_state)
+    );
+
+    if !check_host(peer, app_state, &payload.room_id).await {
+        return;
+    }
+
+    let result = match &app_state.integrations.jira {
+        Some(jira) => jira.search(&payload.jql).await,
+        None => Err(IntegrationError::NotConfigured("Jira")),
+    };
+    add_stories(peer, io, app_state, &payload.room_id, result).await;
+}
+
+/// Handles the host importing open GitHub issues into the story queue.
+/// - Only the host can import issues.
+/// - Fetches issues without holding the rooms lock.
+pub async fn handle_import_github_issues(
+    socket: SocketRef,
+    io: SocketIo,
+    Data(payload): Data<ImportGitHubIssuesEvent>,
+    app_state: SocketState<Arc<AppState>>,
+) {
+    import_github_issues(&Peer::SocketIo(socket), &io, &app_state, payload).await;
+}
+
+/// Imports GitHub issues for a host on either transport, see `handle_import_github_issues`.
+pub async fn import_github_issues(
+    peer: &Peer,
+    io: &SocketIo,
+    app_state: &AppState,
+    payload: ImportGitHubIssuesEvent,
+) {
+    info!(
+        "Recieved import GitHub issues from {}",
+        peer.participant_id(app_state)
+    );
+
+    if !check_host(peer, app_state, &payload.room_id).await {
+        return;
+    }
+
+    let result = match &app_state.integrations.github {
+        Some(github) => github.issues(&payload.repo, payload.milestone).await,
+        None => Err(IntegrationError::NotConfigured("GitHub")),
+    };
+    add_stories(peer, io, app_state, &payload.room_id, result).await;
+}
+
+/// Handles the host selecting the story to estimate.
+/// - Only the host can select stories.
+/// - Emits "storiesUpdated" to the room.
+pub async fn handle_select_story(
+    socket: SocketRef,
+    io: SocketIo,
+    Data(payload): Data<SelectStoryEvent>,
+    app_state: SocketState<Arc<AppState>>,
+) {
+    select_story(&Peer::SocketIo(socket), &io, &app_state, payload).await;
+}
+
+/// Selects the story to estimate for a host on either transport, see `handle_select_story`.
+pub async fn select_story(
+    peer: &Peer,
+    io: &SocketIo,
+    app_state: &AppState,
+    payload: SelectStoryEvent,
+) {
+    let peer_id = peer.participant_id(app_state);
+    info!("Recieved select story from {}", peer_id);
+
+    let mut rooms = app_state.rooms.lock().await;
+
+    match get_room_mut(&payload.room_id, &mut rooms) {
+        Ok(room) => {
+            if room.host_id != peer_id {
+                error!("Player {} is not the host of room {}", peer_id, room.id);
+                return;
+            }
+            if let Some(story_id) = payload.story_id
+                && !room.stories.iter().any(|story| story.id == story_id)
+            {
+                error!("Story {} not found in room {}", story_id, room.id);
+                return;
+            }
+
+            room.current_story = payload.story_id;
+            room.restart_rounds();
+            room.touch();
+
+            broadcast_changes::<StoriesUpdatedEvent>(io, &app_state.hub, room).await;
+        }
+        Err(_) => {
+            peer.emit::<RoomNotFoundEvent>(&());
+        }
+    }
+}
+
+/// Handles the host changing the room's settings.
+/// - Only the host can change settings.
+/// - Emits "actionRejected" if the settings are invalid.
+/// - Emits "settingsUpdated" to the room.
+/// - Applies the new auto-reveal policy if every player has already voted.
+pub async fn handle_update_settings(
+    socket: SocketRef,
+    io: SocketIo,
+    Data(payload): Data<UpdateSettingsEvent>,
+    app_state: SocketState<Arc<AppState>>,
+) {
+    update_settings(&Peer::SocketIo(socket), &io, &app_state, payload).await;
+}
+
+/// Changes a room's settings for a host on either transport, see `handle_update_settings`.
+pub async fn update_settings(
+    peer: &Peer,
+    io: &SocketIo,
+    app_state: &AppState,
+    payload: UpdateSettingsEvent,
+) {
+    let peer_id = peer.participant_id(app_state);
+    info!("Recieved update settings from {}", peer_id);
+
+    let mut rooms = app_state.rooms.lock().await;
+
+    match get_room_mut(&payload.room_id, &mut rooms) {
+        Ok(room) => {
+            if room.host_id != peer_id {
+                error!("Player {} is not the host of room {}", peer_id, room.id);
+                return;
+            }
+            if let Err(err) = payload.settings.validate() {
+                peer.emit::<ActionRejectedEvent>(&err);
+                return;
+            }
+
+            // a grace period already running follows the new policy instead
+            if room.settings.auto_reveal != payload.settings.auto_reveal {
+                room.reveal_at = None;
+            }
+            room.settings = payload.settings;
+            room.touch();
+            info!("Settings updated in room {}", room.id);
+
+            broadcast_changes::<SettingsUpdatedEvent>(io, &app_state.hub, room).await;
+
+            apply_auto_reveal(room, io, app_state).await;
+        }
+        Err(_) => {
+            peer.emit::<RoomNotFoundEvent>(&());
+        }
+    }
+}
+
+/// Handles the host accepting an estimate for the revealed round.
+/// - Only the host can accept an estimate, once the cards are revealed.
+/// - Emits "actionRejected" if there is no consensus or median to accept,
+///   or the chosen card is not an estimate.
+/// - Records the estimate on the round's results and the current story,
+///   and writes it to the story's tracker.
+/// - Emits "estimateAccepted" to the room.
+/// - Moves on to the next story still to be estimated when asked to,
+///   emitting "votesReset" to the room for the new round.
+pub async fn handle_accept_estimate(
+    socket: SocketRef,
+    io: SocketIo,
+    Data(payload): Data<AcceptEstimateEvent>,
+    app_state: SocketState<Arc<AppState>>,
+) {
+    accept_estimate(&Peer::SocketIo(socket), &io, &app_state, payload).await;
+}
+
+/// Accepts an estimate for a host on either transport, see `handle_accept_estimate`.
+pub async fn accept_estimate(
+    peer: &Peer,
+    io: &SocketIo,
+    app_state: &AppState,
+    payload: AcceptEstimateEvent,
+) {
+    let peer_id = peer.participant_id(app_state);
+    info!("Recieved accept estimate from {}", peer_id);
+
+    let mut rooms = app_state.rooms.lock().await;
+
+    match get_room_mut(&payload.room_id, &mut rooms) {
+        Ok(room) => {
+            if room.host_id != peer_id {
+                error!("Player {} is not the host of room {}", peer_id, room.id);
+                return;
+            }
+            let Some(results) = room.results.as_ref().filter(|_| room.cards_revealed) else {
+                peer.emit::<ActionRejectedEvent>(&ActionRejected::NotRevealed);
+                return;
+            };
+            let points = match payload.estimate {
+                EstimateChoice::Consensus => results.median.filter(|_| results.consensus),
+                EstimateChoice::Median => results.median,
+                EstimateChoice::Card { value } if is_estimate(&room.settings.card_set, value) => {
+                    Some(value)
+                }
+                EstimateChoice::Card { value } => {
+                    peer.emit::<ActionRejectedEvent>(&ActionRejected::InvalidEstimate { value });
+                    return;
+                }
+            };
+            let Some(points) = points else {
+                peer.emit::<ActionRejectedEvent>(&ActionRejected::NoEstimate);
+                return;
+            };
+
+            room.estimate = Some(points);
+            revise_results(room);
+            record_estimate(room, points, app_state);
+            let story_id = room.current_story;
+            app_state.webhooks.dispatch(
+                room,
+                &WebhookEvent::EstimateAccepted {
+                    story_id,
+                    estimate: points,
+                },
+            );
+            room.touch();
+            info!("Estimate {} accepted in room {}", points, room.id);
+
+            // sent before moving on so the room sees the accepted round
+            broadcast_changes::<EstimateAcceptedEvent>(io, &app_state.hub, room).await;
+
+            if payload.advance {
+                room.current_story = room.next_story();
+                room.restart_rounds();
+                clear_votes(room);
+                info!("Room {} moved on to the next story", room.id);
+
+                broadcast_changes::<VotesResetEvent>(io, &app_state.hub, room).await;
+            }
+        }
+        Err(_) => {
+            peer.emit::<RoomNotFoundEvent>(&());
+        }
+    }
+}
+
+/// Handles the host starting another round on the current story.
+/// - Only the host can start a revote, once the cards are revealed.
+/// - Clears the votes and keeps the revealed rounds on the story.
+/// - Emits "revoteStarted" to the room.
+pub async fn handle_revote(
+    socket: SocketRef,
+    io: SocketIo,
+    Data(payload): Data<RevoteEvent>,
+    app_state: SocketState<Arc<AppState>>,
+) {
+    revote(&Peer::SocketIo(socket), &io, &app_state, payload).await;
+}
+
+/// Starts another round for a host on either transport, see `handle_revote`.
+pub async fn revote(peer: &Peer, io: &SocketIo, app_state: &AppState, payload: RevoteEvent) {
+    let peer_id = peer.participant_id(app_state);
+    info!("Recieved revote from {}", peer_id);
+
+    let mut rooms = app_state.rooms.lock().await;
+
+    match get_room_mut(&payload.room_id, &mut rooms) {
+        Ok(room) => {
+            if room.host_id != peer_id {
+                error!("Player {} is not the host of room {}", peer_id, room.id);
+                return;
+            }
+            if !room.cards_revealed {
+                peer.emit::<ActionRejectedEvent>(&ActionRejected::NotRevealed);
+                return;
+            }
+
+            clear_votes(room);
+            let story_id = room.current_story;
+            let round = room.rounds.len() + 1;
+            info!("Round {} started in room {}", round, room.id);
+
+            broadcast_changes::<RevoteStartedEvent>(io, &app_state.hub, room).await;
+            app_state
+                .webhooks
+                .dispatch(room, &WebhookEvent::RevoteStarted { story_id, round });
+        }
+        Err(_) => {
+            peer.emit::<RoomNotFoundEvent>(&());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+    use tokio::sync::mpsc::UnboundedReceiver;
+
+    use super::*;
+    use crate::{
+        config::Config,
+        hub::RoomMessage,
+        profile::PlayerRole,
+        settings::RevealQuorum,
+        teams::Team,
+        test_support::{app_state, cast, events, io, peer, room},
+    };
+
+    /// A story not imported from a tracker
+    fn story(title: &str) -> Story {
+        Story {
+            id: Uuid::new_v4(),
+            title: title.to_owned(),
+            url: None,
+            source: None,
+            estimate: None,
+            rounds: Vec::new(),
+            convergence: Convergence::default(),
+        }
+    }
+
+    /// Joins a peer to a room as a voter named `name`
+    async fn join(peer: &Peer, io: &SocketIo, app_state: &AppState, room_id: Uuid, name: &str) {
+        let payload = JoinRoomEvent {
+            name: name.to_owned(),
+            room_id: room_id.to_string(),
+            is_spectator: false,
+        };
+        join_room(peer, io, app_state, payload).await;
+    }
+
+    /// A room whose only voter, the host, has voted and started the grace period
+    async fn room_waiting_to_reveal(app_state: &AppState, io: &SocketIo) -> Uuid {
+        let (host, session, _sent) = peer(app_state, None);
+        let mut room = room(&[&session.participant_id]);
+        room.settings.auto_reveal = AutoReveal::AfterGrace { grace_secs: 60 };
+        let room_id = room.id;
+        app_state.rooms.lock().await.insert(room_id, room);
+        let payload = VoteEvent {
+            room_id: room_id.to_string(),
+            vote: 3,
+        };
+        vote(&host, io, app_state, payload).await;
+        assert!(app_state.rooms.lock().await[&room_id].reveal_at.is_some());
+        room_id
+    }
+
+    #[tokio::test]
+    async fn a_voter_joining_holds_back_the_reveal() {
+        let app_state = app_state(Config::default());
+        let io = io();
+        let room_id = room_waiting_to_reveal(&app_state, &io).await;
+
+        let (spectator, _, _sent) = peer(&app_state, None);
+        let payload = JoinRoomEvent {
+            name: "sam".to_owned(),
+            room_id: room_id.to_string(),
+            is_spectator: true,
+        };
+        join_room(&spectator, &io, &app_state, payload).await;
+        assert!(app_state.rooms.lock().await[&room_id].reveal_at.is_some());
+
+        let (voter, _, _sent) = peer(&app_state, None);
+        join(&voter, &io, &app_state, room_id, "ada").await;
+        assert!(app_state.rooms.lock().await[&room_id].reveal_at.is_none());
+    }
+
+    #[tokio::test]
+    async fn a_spectator_starting_to_vote_holds_back_the_reveal() {
+        let app_state = app_state(Config::default());
+        let io = io();
+        let room_id = room_waiting_to_reveal(&app_state, &io).await;
+        let (spectator, _, _sent) = peer(&app_state, None);
+        let payload = JoinRoomEvent {
+            name: "sam".to_owned(),
+            room_id: room_id.to_string(),
+            is_spectator: true,
+        };
+        join_room(&spectator, &io, &app_state, payload).await;
+
+        let payload = SetSpectatorEvent {
+            room_id: room_id.to_string(),
+            is_spectator: false,
+        };
+        set_spectator(&spectator, &io, &app_state, payload).await;
+
+        assert!(app_state.rooms.lock().await[&room_id].reveal_at.is_none());
+    }
+
+    /// Sends a vote from a peer
+    async fn cast_vote(peer: &Peer, io: &SocketIo, app_state: &AppState, room_id: Uuid, vote: u8) {
+        let payload = VoteEvent {
+            room_id: room_id.to_string(),
+            vote,
+        };
+        super::vote(peer, io, app_state, payload).await;
+    }
+
+    /// A room with the host and a second voter, and a peer for each
+    async fn room_of_two(app_state: &AppState, settings: RoomSettings) -> (Uuid, Peer, Peer) {
+        let (host, host_session, _) = peer(app_state, None);
+        let (ada, ada_session, _) = peer(app_state, None);
+        let mut room = room(&[&host_session.participant_id, &ada_session.participant_id]);
+        room.settings = settings;
+        let room_id = room.id;
+        app_state.rooms.lock().await.insert(room_id, room);
+        (room_id, host, ada)
+    }
+
+    #[tokio::test]
+    async fn votes_outside_the_card_set_are_rejected() {
+        let app_state = app_state(Config::default());
+        let io = io();
+        let (host, session, mut sent) = peer(&app_state, None);
+        let mut room = room(&[&session.participant_id]);
+        room.settings.card_set = "tshirt".to_owned();
+        room.settings.auto_reveal = AutoReveal::Manual;
+        let room_id = room.id;
+        app_state.rooms.lock().await.insert(room_id, room);
+
+        cast_vote(&host, &io, &app_state, room_id, 8).await;
+
+        let sent_events = events(&mut sent);
+        assert_eq!(sent_events[0].event, ActionRejectedEvent::EVENT);
+        assert_eq!(
+            sent_events[0].payload,
+            json!({ "reason": "invalidVote", "value": 8 })
+        );
+        let player =
+            app_state.rooms.lock().await[&room_id].players[&session.participant_id].clone();
+        assert_eq!(player.vote, None);
+
+        cast_vote(&host, &io, &app_state, room_id, UNSURE_VOTE).await;
+        cast_vote(&host, &io, &app_state, room_id, 6).await;
+        let player =
+            app_state.rooms.lock().await[&room_id].players[&session.participant_id].clone();
+        assert_eq!(player.vote, Some(6));
+    }
+
+    #[tokio::test]
+    async fn spectators_cannot_vote() {
+        let app_state = app_state(Config::default());
+        let io = io();
+        let (host, session, mut sent) = peer(&app_state, None);
+        let mut room = room(&[&session.participant_id]);
+        room.players
+            .get_mut(&session.participant_id)
+            .expect("host is in the room")
+            .is_spectator = true;
+        let room_id = room.id;
+        app_state.rooms.lock().await.insert(room_id, room);
+
+        cast_vote(&host, &io, &app_state, room_id, 3).await;
+
+        let sent_events = events(&mut sent);
+        assert_eq!(
+            sent_events[0].payload,
+            json!({ "reason": "spectatorCannotVote" })
+        );
+        let player =
+            app_state.rooms.lock().await[&room_id].players[&session.participant_id].clone();
+        assert!(!player.has_voted);
+    }
+
+    #[tokio::test]
+    async fn changing_and_retracting_votes_count_as_vote_changes() {
+        let app_state = app_state(Config::default());
+        let io = io();
+        let settings = RoomSettings {
+            auto_reveal: AutoReveal::Manual,
+            ..RoomSettings::default()
+        };
+        let (room_id, host, _) = room_of_two(&app_state, settings).await;
+
+        cast_vote(&host, &io, &app_state, room_id, 3).await;
+        cast_vote(&host, &io, &app_state, room_id, 3).await;
+        assert_eq!(app_state.rooms.lock().await[&room_id].vote_changes, 0);
+
+        cast_vote(&host, &io, &app_state, room_id, 5).await;
+        assert_eq!(app_state.rooms.lock().await[&room_id].vote_changes, 1);
+
+        let payload = RetractVoteEvent {
+            room_id: room_id.to_string(),
+        };
+        retract_vote(&host, &io, &app_state, payload).await;
+        let room = app_state.rooms.lock().await[&room_id].clone();
+        assert_eq!(room.vote_changes, 2);
+        assert!(
+            room.players
+                .values()
+                .all(|player| player.vote.is_none() && !player.has_voted)
+        );
+    }
+
+    #[tokio::test]
+    async fn votes_change_after_the_reveal_only_when_allowed() {
+        let app_state = app_state(Config::default());
+        let io = io();
+        let (room_id, host, ada) = room_of_two(&app_state, RoomSettings::default()).await;
+        cast_vote(&host, &io, &app_state, room_id, 3).await;
+        cast_vote(&ada, &io, &app_state, room_id, 5).await;
+        assert!(app_state.rooms.lock().await[&room_id].cards_revealed);
+
+        cast_vote(&ada, &io, &app_state, room_id, 3).await;
+        let results = app_state.rooms.lock().await[&room_id].results.clone();
+        assert_eq!(results.expect("cards are revealed").max, Some(5));
+
+        app_state
+            .rooms
+            .lock()
+            .await
+            .get_mut(&room_id)
+            .expect("room exists")
+            .settings
+            .allow_vote_change_after_reveal = true;
+        cast_vote(&ada, &io, &app_state, room_id, 3).await;
+        let room = app_state.rooms.lock().await[&room_id].clone();
+        let results = room.results.expect("cards are revealed");
+        assert!(results.consensus);
+        assert_eq!(results.vote_changes, 1);
+        assert_eq!(room.rounds.last().map(|round| round.consensus), Some(true));
+    }
+
+    #[tokio::test]
+    async fn team_rooms_are_not_opened_for_invalid_names() {
+        let app_state = app_state(Config::default());
+        let io = io();
+        let team: Team = serde_json::from_value(json!({
+            "id": Uuid::new_v4(),
+            "name": "platform",
+            "members": [],
+            "settings": RoomSettings::default(),
+            "require_login": false,
+            "room_id": null,
+        }))
+        .expect("team deserializes");
+        let team_id = team.id;
+        app_state.teams.lock().await.insert(team_id, team);
+        let (peer, _, mut sent) = peer(&app_state, None);
+
+        let payload = JoinTeamRoomEvent {
+            name: "x".repeat(app_state.config.limits.name_length + 1),
+            team_id: team_id.to_string(),
+            is_spectator: false,
+        };
+        join_team_room(&peer, &io, &app_state, payload).await;
+
+        let sent_events = events(&mut sent);
+        assert_eq!(sent_events[0].payload["reason"], "invalidName");
+        assert!(app_state.rooms.lock().await.is_empty());
+        assert_eq!(app_state.teams.lock().await[&team_id].room_id, None);
+    }
+
+    #[tokio::test]
+    async fn imported_stories_are_dropped_if_the_host_changed() {
+        let app_state = app_state(Config::default());
+        let io = io();
+        let (former_host, _, _sent) = peer(&app_state, None);
+        let room = room(&["ada"]);
+        let room_id = room.id;
+        app_state.rooms.lock().await.insert(room_id, room);
+
+        let story = story("PLAT-1: Login");
+        add_stories(
+            &former_host,
+            &io,
+            &app_state,
+            &room_id.to_string(),
+            Ok(vec![story]),
+        )
+        .await;
+
+        assert!(app_state.rooms.lock().await[&room_id].stories.is_empty());
+    }
+
+    #[tokio::test]
+    async fn reconnecting_with_the_token_rejoins_as_the_same_participant() {
+        let app_state = app_state(Config::default());
+        let io = io();
+        let room = room(&["host"]);
+        let room_id = room.id;
+        app_state.rooms.lock().await.insert(room_id, room);
+
+        let (first, session, _sent) = peer(&app_state, None);
+        join(&first, &io, &app_state, room_id, "ada").await;
+        disconnect(&first, &io, &app_state).await;
+        assert!(
+            !app_state.rooms.lock().await[&room_id]
+                .players
+                .contains_key(&session.participant_id)
+        );
+
+        let (second, resumed, _sent) = peer(&app_state, Some(&session.token));
+        assert_eq!(resumed.participant_id, session.participant_id);
+        join(&second, &io, &app_state, room_id, "ada").await;
+
+        let name = app_state.rooms.lock().await[&room_id].players[&session.participant_id]
+            .name
+            .clone();
+        assert_eq!(name, "ada");
+    }
+
+    #[tokio::test]
+    async fn reveal_is_rejected_until_the_quorum_is_met() {
+        let app_state = app_state(Config::default());
+        let io = io();
+        let (host, session, mut sent) = peer(&app_state, None);
+        let host_id = session.participant_id;
+        let mut room = room(&[&host_id, "ada", "bob"]);
+        room.settings.reveal_quorum = RevealQuorum::MinVotes { votes: 2 };
+        cast(&mut room, &[(&host_id, 3)]);
+        let room_id = room.id;
+        app_state.rooms.lock().await.insert(room_id, room);
+
+        let payload = || RevealCardsEvent {
+            room_id: room_id.to_string(),
+        };
+        reveal(&host, &io, &app_state, payload()).await;
+
+        let sent_events = events(&mut sent);
+        assert_eq!(sent_events.len(), 1);
+        assert_eq!(sent_events[0].event, ActionRejectedEvent::EVENT);
+        assert_eq!(
+            sent_events[0].payload,
+            json!({ "reason": "quorumNotMet", "votes": 1, "required": 2 })
+        );
+        assert!(!app_state.rooms.lock().await[&room_id].cards_revealed);
+
+        cast(
+            app_state
+                .rooms
+                .lock()
+                .await
+                .get_mut(&room_id)
+                .expect("room exists"),
+            &[("ada", 5)],
+        );
+        reveal(&host, &io, &app_state, payload()).await;
+
+        assert!(app_state.rooms.lock().await[&room_id].cards_revealed);
+    }
+
+    #[tokio::test]
+    async fn reveal_is_allowed_in_rooms_of_spectators() {
+        let app_state = app_state(Config::default());
+        let io = io();
+        let (host, session, _sent) = peer(&app_state, None);
+        let host_id = session.participant_id;
+        let mut room = room(&[&host_id]);
+        room.players
+            .get_mut(&host_id)
+            .expect("host is in the room")
+            .is_spectator = true;
+        let room_id = room.id;
+        app_state.rooms.lock().await.insert(room_id, room);
+
+        reveal(
+            &host,
+            &io,
+            &app_state,
+            RevealCardsEvent {
+                room_id: room_id.to_string(),
+            },
+        )
+        .await;
+
+        assert!(app_state.rooms.lock().await[&room_id].cards_revealed);
+    }
+
+    #[tokio::test]
+    async fn update_settings_is_validated_and_only_taken_from_the_host() {
+        let app_state = app_state(Config::default());
+        let io = io();
+        let (host, host_session, mut sent) = peer(&app_state, None);
+        let (ada, ada_session, _ada_sent) = peer(&app_state, None);
+        let room = room(&[&host_session.participant_id, &ada_session.participant_id]);
+        let room_id = room.id;
+        app_state.rooms.lock().await.insert(room_id, room);
+        let update = |settings: RoomSettings| UpdateSettingsEvent {
+            room_id: room_id.to_string(),
+            settings,
+        };
+
+        update_settings(
+            &ada,
+            &io,
+            &app_state,
+            update(RoomSettings::with_card_set("tshirt".to_owned())),
+        )
+        .await;
+        assert_eq!(
+            app_state.rooms.lock().await[&room_id].settings,
+            RoomSettings::default()
+        );
+
+        update_settings(
+            &host,
+            &io,
+            &app_state,
+            update(RoomSettings::with_card_set("emoji".to_owned())),
+        )
+        .await;
+        assert_eq!(events(&mut sent)[0].payload["reason"], "invalidSettings");
+        assert_eq!(
+            app_state.rooms.lock().await[&room_id].settings,
+            RoomSettings::default()
+        );
+
+        update_settings(
+            &host,
+            &io,
+            &app_state,
+            update(RoomSettings::with_card_set("tshirt".to_owned())),
+        )
+        .await;
+        assert_eq!(
+            app_state.rooms.lock().await[&room_id].settings.card_set,
+            "tshirt"
+        );
+    }
+
+    #[tokio::test]
+    async fn updated_settings_apply_to_votes_already_in() {
+        let app_state = app_state(Config::default());
+        let io = io();
+        let manual = RoomSettings {
+            auto_reveal: AutoReveal::Manual,
+            ..RoomSettings::default()
+        };
+        let (room_id, host, ada) = room_of_two(&app_state, manual).await;
+        cast_vote(&host, &io, &app_state, room_id, 3).await;
+        cast_vote(&ada, &io, &app_state, room_id, 5).await;
+        assert!(!app_state.rooms.lock().await[&room_id].cards_revealed);
+
+        let payload = UpdateSettingsEvent {
+            room_id: room_id.to_string(),
+            settings: RoomSettings::default(),
+        };
+        update_settings(&host, &io, &app_state, payload).await;
+
+        assert!(app_state.rooms.lock().await[&room_id].cards_revealed);
+    }
+
+    #[test]
+    fn visible_room_hides_votes_until_revealed() {
+        let mut room = room(&["host", "ada"]);
+        cast(&mut room, &[("host", 3), ("ada", 5)]);
+
+        let visible = visible_room(&room);
+        assert!(visible.players.values().all(|player| player.vote.is_none()));
+        assert!(visible.players.values().all(|player| player.has_voted));
+
+        room.cards_revealed = true;
+        let visible = visible_room(&room);
+        assert_eq!(visible.players["ada"].vote, Some(5));
+    }
+
+    #[test]
+    fn visible_room_never_shows_votes_in_anonymous_rooms() {
+        let mut room = room(&["host", "ada"]);
+        room.settings.anonymous = true;
+        room.settings.keep_attribution = true;
+        cast(&mut room, &[("host", 3), ("ada", 5)]);
+        room.cards_revealed = true;
+        room.results = Some(VoteStatistics::for_room(&room));
+
+        let visible = visible_room(&room);
+
+        assert!(visible.players.values().all(|player| player.vote.is_none()));
+        let results = visible.results.expect("results are shown");
+        assert_eq!(results.distribution.get(&3), Some(&1));
+        assert_eq!(results.distribution.get(&5), Some(&1));
+    }
+
+    #[tokio::test]
+    async fn revote_keeps_each_round_for_the_convergence() {
+        let app_state = app_state(Config::default());
+        let io = io();
+        let (room_id, host, ada) = room_of_two(&app_state, RoomSettings::default()).await;
+        let revote_payload = || RevoteEvent {
+            room_id: room_id.to_string(),
+        };
+
+        revote(&host, &io, &app_state, revote_payload()).await;
+        assert!(app_state.rooms.lock().await[&room_id].rounds.is_empty());
+
+        cast_vote(&host, &io, &app_state, room_id, 1).await;
+        cast_vote(&ada, &io, &app_state, room_id, 8).await;
+        revote(&ada, &io, &app_state, revote_payload()).await;
+        assert!(app_state.rooms.lock().await[&room_id].cards_revealed);
+
+        revote(&host, &io, &app_state, revote_payload()).await;
+        let room = app_state.rooms.lock().await[&room_id].clone();
+        assert!(!room.cards_revealed);
+        assert!(room.players.values().all(|player| player.vote.is_none()));
+        assert_eq!(room.rounds.len(), 1);
+
+        cast_vote(&host, &io, &app_state, room_id, 5).await;
+        cast_vote(&ada, &io, &app_state, room_id, 5).await;
+        let room = app_state.rooms.lock().await[&room_id].clone();
+        assert_eq!(room.rounds.len(), 2);
+        assert_eq!(room.convergence.spreads, [Some(7), Some(0)]);
+        assert_eq!(room.convergence.consensus_round, Some(2));
+    }
+
+    /// A room of the host and a second voter who voted `votes`, with
+    /// two stories to estimate and the cards revealed
+    async fn revealed_room(
+        app_state: &AppState,
+        io: &SocketIo,
+        votes: [u8; 2],
+    ) -> (Uuid, Peer, UnboundedReceiver<Arc<RoomMessage>>) {
+        let (host, host_session, sent) = peer(app_state, None);
+        let mut room = room(&[&host_session.participant_id, "ada"]);
+        room.stories = vec![story("PLAT-1: Login"), story("PLAT-2: Logout")];
+        room.current_story = room.stories.first().map(|story| story.id);
+        cast(
+            &mut room,
+            &[(&host_session.participant_id, votes[0]), ("ada", votes[1])],
+        );
+        let room_id = room.id;
+        reveal_cards(&mut room, io, app_state).await;
+        app_state.rooms.lock().await.insert(room_id, room);
+        (room_id, host, sent)
+    }
+
+    /// Accepts an estimate as the host, without moving on
+    async fn accept(
+        host: &Peer,
+        io: &SocketIo,
+        app_state: &AppState,
+        room_id: Uuid,
+        estimate: EstimateChoice,
+    ) {
+        let payload = AcceptEstimateEvent {
+            room_id: room_id.to_string(),
+            estimate,
+            advance: false,
+        };
+        accept_estimate(host, io, app_state, payload).await;
+    }
+
+    #[tokio::test]
+    async fn accept_consensus_needs_every_estimate_to_agree() {
+        let app_state = app_state(Config::default());
+        let io = io();
+        let (room_id, host, mut sent) = revealed_room(&app_state, &io, [3, 5]).await;
+
+        accept(&host, &io, &app_state, room_id, EstimateChoice::Consensus).await;
+        assert_eq!(events(&mut sent)[0].payload["reason"], "noEstimate");
+        assert_eq!(app_state.rooms.lock().await[&room_id].estimate, None);
+
+        let (room_id, host, _sent) = revealed_room(&app_state, &io, [5, 5]).await;
+        accept(&host, &io, &app_state, room_id, EstimateChoice::Consensus).await;
+        let room = app_state.rooms.lock().await[&room_id].clone();
+        assert_eq!(room.estimate, Some(5));
+        assert_eq!(room.stories[0].estimate, Some(5));
+        assert_eq!(room.results.and_then(|results| results.estimate), Some(5));
+    }
+
+    #[tokio::test]
+    async fn accept_median_takes_the_lower_middle_estimate() {
+        let app_state = app_state(Config::default());
+        let io = io();
+        let (room_id, host, _sent) = revealed_room(&app_state, &io, [3, 8]).await;
+
+        accept(&host, &io, &app_state, room_id, EstimateChoice::Median).await;
+
+        let room = app_state.rooms.lock().await[&room_id].clone();
+        assert_eq!(room.estimate, Some(3));
+        assert_eq!(room.rounds.last().and_then(|round| round.estimate), Some(3));
+    }
+
+    #[tokio::test]
+    async fn accept_card_overrides_with_an_estimate_card() {
+        let app_state = app_state(Config::default());
+        let io = io();
+        let (room_id, host, mut sent) = revealed_room(&app_state, &io, [3, 8]).await;
+
+        accept(
+            &host,
+            &io,
+            &app_state,
+            room_id,
+            EstimateChoice::Card { value: 4 },
+        )
+        .await;
+        assert_eq!(
+            events(&mut sent)[0].payload,
+            json!({ "reason": "invalidEstimate", "value": 4 })
+        );
+
+        accept(
+            &host,
+            &io,
+            &app_state,
+            room_id,
+            EstimateChoice::Card { value: 5 },
+        )
+        .await;
+        assert_eq!(app_state.rooms.lock().await[&room_id].estimate, Some(5));
+    }
+
+    #[tokio::test]
+    async fn accept_is_rejected_before_the_reveal_and_can_move_on() {
+        let app_state = app_state(Config::default());
+        let io = io();
+        let (room_id, host, mut sent) = revealed_room(&app_state, &io, [5, 5]).await;
+        let stories: Vec<Uuid> = app_state.rooms.lock().await[&room_id]
+            .stories
+            .iter()
+            .map(|story| story.id)
+            .collect();
+
+        let payload = AcceptEstimateEvent {
+            room_id: room_id.to_string(),
+            estimate: EstimateChoice::Consensus,
+            advance: true,
+        };
+        accept_estimate(&host, &io, &app_state, payload).await;
+        let room = app_state.rooms.lock().await[&room_id].clone();
+        assert_eq!(room.stories[0].estimate, Some(5));
+        assert_eq!(room.current_story, Some(stories[1]));
+        assert!(!room.cards_revealed);
+        assert_eq!(room.estimate, None);
+        assert!(room.rounds.is_empty());
+
+        accept(&host, &io, &app_state, room_id, EstimateChoice::Median).await;
+        let sent_events = events(&mut sent);
+        assert_eq!(
+            sent_events
+                .last()
+                .map(|message| message.payload["reason"].clone()),
+            Some(json!("notRevealed"))
+        );
+    }
+
+    #[tokio::test]
+    async fn becoming_a_spectator_clears_the_vote() {
+        let app_state = app_state(Config::default());
+        let io = io();
+        let (room_id, host, ada) = room_of_two(&app_state, RoomSettings::default()).await;
+        let payload = SetSpectatorEvent {
+            room_id: room_id.to_string(),
+            is_spectator: true,
+        };
+
+        cast_vote(&ada, &io, &app_state, room_id, 5).await;
+        set_spectator(&ada, &io, &app_state, payload).await;
+
+        let room = app_state.rooms.lock().await[&room_id].clone();
+        let ada_id = ada.participant_id(&app_state);
+        assert!(room.players[&ada_id].is_spectator);
+        assert_eq!(room.players[&ada_id].vote, None);
+        assert!(!room.players[&ada_id].has_voted);
+        assert!(!room.cards_revealed);
+
+        cast_vote(&host, &io, &app_state, room_id, 3).await;
+        let room = app_state.rooms.lock().await[&room_id].clone();
+        assert!(room.cards_revealed);
+        assert_eq!(
+            room.results.map(|results| results.distribution),
+            Some([(3, 1)].into())
+        );
+    }
+
+    #[tokio::test]
+    async fn the_last_voter_becoming_a_spectator_reveals() {
+        let app_state = app_state(Config::default());
+        let io = io();
+        let (room_id, host, ada) = room_of_two(&app_state, RoomSettings::default()).await;
+        cast_vote(&host, &io, &app_state, room_id, 3).await;
+
+        let payload = SetSpectatorEvent {
+            room_id: room_id.to_string(),
+            is_spectator: true,
+        };
+        set_spectator(&ada, &io, &app_state, payload).await;
+
+        let room = app_state.rooms.lock().await[&room_id].clone();
+        assert!(room.cards_revealed);
+        assert_eq!(room.results.map(|results| results.votes), Some(1));
+    }
+
+    #[tokio::test]
+    async fn spectating_is_refused_in_rooms_without_spectators() {
+        let app_state = app_state(Config::default());
+        let io = io();
+        let (host, session, mut sent) = peer(&app_state, None);
+        let mut room = room(&[&session.participant_id]);
+        room.settings.allow_spectators = false;
+        let room_id = room.id;
+        app_state.rooms.lock().await.insert(room_id, room);
+
+        let payload = SetSpectatorEvent {
+            room_id: room_id.to_string(),
+            is_spectator: true,
+        };
+        set_spectator(&host, &io, &app_state, payload).await;
+
+        assert_eq!(
+            events(&mut sent)[0].payload["reason"],
+            "spectatorsNotAllowed"
+        );
+        let room = app_state.rooms.lock().await[&room_id].clone();
+        assert!(!room.players[&session.participant_id].is_spectator);
+    }
+
+    #[tokio::test]
+    async fn update_profile_renames_and_restyles_the_player() {
+        let app_state = app_state(Config::default());
+        let io = io();
+        let (host, session, mut sent) = peer(&app_state, None);
+        let room = room(&[&session.participant_id]);
+        let room_id = room.id;
+        app_state.rooms.lock().await.insert(room_id, room);
+        let update = |name: &str, profile: Profile| UpdateProfileEvent {
+            room_id: room_id.to_string(),
+            name: name.to_owned(),
+            profile,
+        };
+        let profile = Profile {
+            emoji: Some("🦊".to_owned()),
+            color: Some(2),
+            role: None,
+        };
+
+        update_profile(&host, &io, &app_state, update("  Ada  ", profile.clone())).await;
+        let player =
+            app_state.rooms.lock().await[&room_id].players[&session.participant_id].clone();
+        assert_eq!(player.name, "Ada");
+        assert_eq!(player.profile, profile);
+
+        update_profile(&host, &io, &app_state, update("", Profile::default())).await;
+        let invalid_colour = Profile {
+            color: Some(0),
+            ..Profile::default()
+        };
+        update_profile(&host, &io, &app_state, update("Bob", invalid_colour)).await;
+        let reasons: Vec<Value> = events(&mut sent)
+            .iter()
+            .filter(|message| message.event == ActionRejectedEvent::EVENT)
+            .map(|message| message.payload["reason"].clone())
+            .collect();
+        assert_eq!(reasons, [json!("invalidName"), json!("invalidProfile")]);
+        let player =
+            app_state.rooms.lock().await[&room_id].players[&session.participant_id].clone();
+        assert_eq!(player.name, "Ada");
+        assert_eq!(player.profile, profile);
+    }
+
+    #[tokio::test]
+    async fn logged_in_players_keep_their_login_name() {
+        let app_state = app_state(Config::default());
+        let io = io();
+        let (host, session, _sent) = peer(&app_state, None);
+        let mut room = room(&[&session.participant_id]);
+        let player = room
+            .players
+            .get_mut(&session.participant_id)
+            .expect("host is in the room");
+        player.name = "Ada Lovelace".to_owned();
+        player.verified = true;
+        let room_id = room.id;
+        app_state.rooms.lock().await.insert(room_id, room);
+
+        let payload = UpdateProfileEvent {
+            room_id: room_id.to_string(),
+            name: "Grace".to_owned(),
+            profile: Profile {
+                role: Some(PlayerRole::Qa),
+                ..Profile::default()
+            },
+        };
+        update_profile(&host, &io, &app_state, payload).await;
+
+        let player =
+            app_state.rooms.lock().await[&room_id].players[&session.participant_id].clone();
+        assert_eq!(player.name, "Ada Lovelace");
+        assert_eq!(player.profile.role, Some(PlayerRole::Qa));
     }
 }
@@ -0,0 +1,222 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    Json,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use tokio::time::timeout;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::types::AppState;
+
+/// How long readiness waits for the rooms lock before
+/// deciding it is wedged
+const LOCK_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Result of a single readiness check
+#[derive(Debug, Serialize)]
+struct Check {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+}
+
+impl Check {
+    const fn pass() -> Self {
+        Self {
+            ok: true,
+            detail: None,
+        }
+    }
+
+    fn fail(detail: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            detail: Some(detail.into()),
+        }
+    }
+}
+
+/// Body returned by the readiness endpoint
+#[derive(Debug, Serialize)]
+struct Readiness {
+    ready: bool,
+    draining: Check,
+    rooms_lock: Check,
+    store: Check,
+}
+
+/// Size of a single room in the debug summary
+#[derive(Debug, Serialize)]
+struct RoomSize {
+    id: Uuid,
+    players: usize,
+    spectators: usize,
+}
+
+/// Body returned by the debug rooms endpoint
+#[derive(Debug, Serialize)]
+struct RoomsSummary {
+    rooms: usize,
+    players: usize,
+    spectators: usize,
+    largest_room: usize,
+    sizes: Vec<RoomSize>,
+}
+
+/// Liveness probe, answers as long as the server can handle requests
+pub async fn healthz() -> &'static str {
+    "ok"
+}
+
+/// Readiness probe.
+/// - Fails while the server is draining for shutdown.
+/// - Fails if the rooms lock cannot be taken in time.
+/// - Fails if the configured store is not writable.
+pub async fn readyz(State(app_state): State<Arc<AppState>>) -> Response {
+    let draining = if app_state.is_draining() {
+        Check::fail("server is shutting down")
+    } else {
+        Check::pass()
+    };
+
+    let rooms_lock = match timeout(LOCK_TIMEOUT, app_state.rooms.lock()).await {
+        Ok(_) => Check::pass(),
+        Err(_) => Check::fail(format!("lock not acquired within {LOCK_TIMEOUT:?}")),
+    };
+
+    let store = match &app_state.store {
+        Some(store) => match store.check().await {
+            Ok(()) => Check::pass(),
+            Err(err) => Check::fail(err.to_string()),
+        },
+        None => Check::pass(),
+    };
+
+    let ready = draining.ok && rooms_lock.ok && store.ok;
+    if !ready {
+        error!("Readiness check failed");
+    }
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        status,
+        Json(Readiness {
+            ready,
+            draining,
+            rooms_lock,
+            store,
+        }),
+    )
+        .into_response()
+}
+
+/// Summary of room counts and sizes for operators.
+/// Guarded by the admin token middleware.
+pub async fn debug_rooms(State(app_state): State<Arc<AppState>>) -> Response {
+    let sizes: Vec<RoomSize> = app_state
+        .rooms
+        .lock()
+        .await
+        .values()
+        .map(|room| {
+            let spectators = room.players.values().filter(|p| p.is_spectator).count();
+            RoomSize {
+                id: room.id,
+                players: room.players.len() - spectators,
+                spectators,
+            }
+        })
+        .collect();
+
+    Json(RoomsSummary {
+        rooms: sizes.len(),
+        players: sizes.iter().map(|room| room.players).sum(),
+        spectators: sizes.iter().map(|room| room.spectators).sum(),
+        largest_room: sizes
+            .iter()
+            .map(|room| room.players + room.spectators)
+            .max()
+            .unwrap_or_default(),
+        sizes,
+    })
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use axum::body::to_bytes;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::{config::Config, test_support::app_state};
+
+    /// The status and JSON body of a readiness response
+    async fn readiness(app_state: Arc<AppState>) -> (StatusCode, Value) {
+        let response = readyz(State(app_state)).await;
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("body is read");
+        (status, serde_json::from_slice(&body).expect("body is JSON"))
+    }
+
+    #[tokio::test]
+    async fn readyz_passes_when_every_check_does() {
+        let (status, body) = readiness(app_state(Config::default())).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["ready"], true);
+        assert_eq!(body["store"], serde_json::json!({ "ok": true }));
+    }
+
+    #[tokio::test]
+    async fn readyz_fails_while_draining() {
+        let app_state = app_state(Config::default());
+        app_state.start_draining();
+
+        let (status, body) = readiness(app_state).await;
+
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["ready"], false);
+        assert_eq!(body["draining"]["ok"], false);
+        assert_eq!(body["rooms_lock"]["ok"], true);
+    }
+
+    #[tokio::test]
+    async fn readyz_fails_while_the_rooms_lock_is_held() {
+        let app_state = app_state(Config::default());
+        let rooms = app_state.rooms.lock().await;
+
+        let (status, body) = readiness(Arc::clone(&app_state)).await;
+        drop(rooms);
+
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["rooms_lock"]["ok"], false);
+    }
+
+    #[tokio::test]
+    async fn readyz_fails_when_the_store_directory_is_missing() {
+        let config = Config {
+            state_file: Some(PathBuf::from("/nonexistent/storypoint/state.json")),
+            ..Config::default()
+        };
+
+        let (status, body) = readiness(app_state(config)).await;
+
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["store"]["ok"], false);
+        assert!(body["store"]["detail"].is_string());
+    }
+}
@@ -0,0 +1,94 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::broadcast;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::types::SocketEvent;
+
+/// Messages buffered per room for slow subscribers before they lag
+const CHANNEL_CAPACITY: usize = 64;
+
+/// `RoomMessage` is an outbound event as seen by clients
+/// that are not connected over Socket.IO
+#[derive(Clone, Debug, Serialize)]
+pub struct RoomMessage {
+    /// The event name, as emitted over Socket.IO
+    #[serde(rename = "type")]
+    pub event: &'static str,
+    /// The event data
+    pub payload: Value,
+}
+
+impl RoomMessage {
+    /// Builds the message for an event, enforcing type safety for its data
+    pub fn new<E: SocketEvent>(data: &E::Data) -> Option<Self> {
+        match serde_json::to_value(data) {
+            Ok(payload) => Some(Self {
+                event: E::EVENT,
+                payload,
+            }),
+            Err(err) => {
+                error!("Failed to serialize {}: {}", E::EVENT, err);
+                None
+            }
+        }
+    }
+}
+
+/// `RoomHub` fans room broadcasts out to clients outside Socket.IO,
+/// such as plain WebSocket connections.
+/// Each room has a channel that is created on first subscription
+/// and dropped when the room is closed.
+#[derive(Debug, Default)]
+pub struct RoomHub {
+    channels: Mutex<HashMap<Uuid, broadcast::Sender<Arc<RoomMessage>>>>,
+}
+
+impl RoomHub {
+    /// Sends an event to every subscriber of a room
+    pub fn publish<E: SocketEvent>(&self, room_id: Uuid, data: &E::Data) {
+        let channels = self.channels.lock().expect("hub lock poisoned");
+        if let Some(sender) = channels.get(&room_id)
+            && let Some(message) = RoomMessage::new::<E>(data)
+        {
+            // no receivers is fine, the last subscriber may have just left
+            sender.send(Arc::new(message)).ok();
+        }
+    }
+
+    /// Sends an event to the subscribers of every room
+    pub fn publish_all<E: SocketEvent>(&self, data: &E::Data) {
+        let channels = self.channels.lock().expect("hub lock poisoned");
+        if let Some(message) = RoomMessage::new::<E>(data).map(Arc::new) {
+            for sender in channels.values() {
+                sender.send(Arc::clone(&message)).ok();
+            }
+        }
+    }
+
+    /// Subscribes to a room's broadcasts, creating its channel if needed
+    pub fn subscribe(&self, room_id: Uuid) -> broadcast::Receiver<Arc<RoomMessage>> {
+        self.channels
+            .lock()
+            .expect("hub lock poisoned")
+            .entry(room_id)
+            .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+            .subscribe()
+    }
+
+    /// Drops a room's channel, ending every subscription to it
+    pub fn close(&self, room_id: Uuid) {
+        self.channels
+            .lock()
+            .expect("hub lock poisoned")
+            .remove(&room_id);
+    }
+}
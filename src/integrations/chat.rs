@@ -0,0 +1,434 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tracing::{error, info};
+
+use super::{IntegrationError, check_status};
+use crate::{
+    protocol::{describe_enum, describe_struct},
+    stats::{Convergence, VoteStatistics, card_label},
+    types::{NUMERIC_CARD_SET, Room},
+    webhooks,
+};
+
+/// Slack limits header blocks to 150 characters
+const MAX_HEADER_LENGTH: usize = 150;
+
+/// Message format expected by the incoming webhook
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ChatFormat {
+    /// Slack Block Kit message
+    Slack,
+    /// Microsoft Teams Adaptive Card
+    Teams,
+}
+
+describe_enum!(ChatFormat { Slack, Teams });
+
+/// Incoming chat webhook registered on a room by its host
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ChatWebhook {
+    /// Format the webhook expects
+    pub format: ChatFormat,
+    /// Incoming webhook URL, it is a credential so is only shared with the host
+    pub url: String,
+}
+
+describe_struct!(ChatWebhook { format, url });
+
+/// A player whose estimate was the lowest or highest of a split round
+#[derive(Clone, Debug)]
+struct Outlier {
+    name: String,
+    card: String,
+}
+
+/// `RoundSummary` is what chat messages say about a revealed round
+#[derive(Clone, Debug)]
+pub struct RoundSummary {
+    /// Title of the story being estimated, if one is selected
+    title: Option<String>,
+    /// Agreed card, if every estimate was the same
+    consensus: Option<String>,
+    /// Median card
+    median: Option<String>,
+    /// Mean of the estimates, only for the numeric card set
+    average: Option<f64>,
+    /// Number of votes including abstentions
+    votes: usize,
+    /// Number of votes cast for each card, in card order
+    distribution: Vec<(String, usize)>,
+    /// Players at either end of a split round
+    outliers: Vec<Outlier>,
+    /// How the story's estimates moved, once it has been voted on more than once
+    convergence: Option<Convergence>,
+}
+
+/// Escapes text for Slack `mrkdwn`, so player names and story titles
+/// cannot mention the channel or link elsewhere
+fn slack_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Characters Adaptive Cards treat as markdown for emphasis, code and links
+const TEAMS_MARKDOWN: [char; 9] = ['\\', '*', '_', '~', '`', '[', ']', '<', '>'];
+
+/// Escapes the markdown Adaptive Cards render in `TextBlock`s and facts,
+/// as they have no plain text mode, so player names and story titles
+/// cannot format the card or link elsewhere
+fn teams_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if TEAMS_MARKDOWN.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Spread of each round, oldest first
+fn spreads(convergence: &Convergence) -> String {
+    convergence
+        .spreads
+        .iter()
+        .map(|spread| spread.map_or_else(|| "-".to_owned(), |spread| spread.to_string()))
+        .collect::<Vec<_>>()
+        .join(" → ")
+}
+
+impl RoundSummary {
+    /// Summarises the current votes in a room
+    pub fn for_room(room: &Room) -> Self {
+        let statistics = VoteStatistics::for_room(room);
+        let label = |value: u8| card_label(&room.settings.card_set, value);
+
+        let outliers = if statistics.consensus || !room.settings.attributed() {
+            Vec::new()
+        } else {
+            room.players
+                .values()
+                .filter(|p| !p.is_spectator)
+                .filter_map(|p| p.vote.map(|vote| (p, vote)))
+                .filter(|(_, vote)| Some(*vote) == statistics.min || Some(*vote) == statistics.max)
+                .map(|(p, vote)| Outlier {
+                    name: p.name.clone(),
+                    card: label(vote),
+                })
+                .collect()
+        };
+
+        let story = room
+            .current_story
+            .and_then(|id| room.stories.iter().find(|story| story.id == id));
+
+        Self {
+            title: story.map(|story| story.title.clone()),
+            consensus: statistics
+                .consensus
+                .then_some(statistics.median)
+                .flatten()
+                .map(label),
+            median: statistics.median.map(label),
+            average: statistics
+                .average
+                .filter(|_| room.settings.card_set == NUMERIC_CARD_SET),
+            votes: statistics.votes,
+            distribution: statistics
+                .distribution
+                .iter()
+                .map(|(&value, &count)| (label(value), count))
+                .collect(),
+            outliers,
+            convergence: (room.convergence.rounds > 1).then(|| room.convergence.clone()),
+        }
+    }
+
+    fn heading(&self) -> String {
+        let title = self.title.as_deref().unwrap_or("Round results");
+        title.chars().take(MAX_HEADER_LENGTH).collect()
+    }
+
+    fn result(&self) -> String {
+        match (&self.consensus, &self.median) {
+            (Some(consensus), _) => format!("Consensus on {consensus}"),
+            (None, Some(median)) => format!("No consensus, median {median}"),
+            (None, None) => "No estimates".to_owned(),
+        }
+    }
+
+    fn facts(&self) -> Vec<(&'static str, String)> {
+        let mut facts = vec![("Result", self.result()), ("Votes", self.votes.to_string())];
+        if let Some(average) = self.average {
+            facts.push(("Average", format!("{average:.1}")));
+        }
+        if let Some(convergence) = &self.convergence {
+            facts.push(("Round", convergence.rounds.to_string()));
+            facts.push(("Spread", spreads(convergence)));
+        }
+        facts
+    }
+
+    fn distribution(&self) -> String {
+        self.distribution
+            .iter()
+            .map(|(card, count)| format!("{card}: {count}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn outliers(&self) -> Option<String> {
+        (!self.outliers.is_empty()).then(|| {
+            self.outliers
+                .iter()
+                .map(|outlier| format!("{} ({})", outlier.name, outlier.card))
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+    }
+
+    /// Formats the summary as a Slack Block Kit message.
+    /// Everything but the plain text header is `mrkdwn`, so is escaped
+    pub fn slack_message(&self) -> Value {
+        let fields: Vec<Value> = self
+            .facts()
+            .into_iter()
+            .map(|(title, value)| {
+                let text = format!("*{title}*\n{}", slack_escape(&value));
+                json!({ "type": "mrkdwn", "text": text })
+            })
+            .collect();
+        let distribution = format!("*Distribution*\n{}", slack_escape(&self.distribution()));
+        let mut blocks = vec![
+            json!({ "type": "header", "text": { "type": "plain_text", "text": self.heading() } }),
+            json!({ "type": "section", "fields": fields }),
+            json!({ "type": "section", "text": { "type": "mrkdwn", "text": distribution } }),
+        ];
+        if let Some(outliers) = self.outliers() {
+            let text = format!("Outliers: {}", slack_escape(&outliers));
+            blocks.push(json!({
+                "type": "context",
+                "elements": [{ "type": "mrkdwn", "text": text }]
+            }));
+        }
+
+        json!({
+            "text": slack_escape(&format!("{}: {}", self.heading(), self.result())),
+            "blocks": blocks,
+        })
+    }
+
+    /// Formats the summary as a Teams message holding an Adaptive Card.
+    /// The heading and fact values are escaped markdown
+    pub fn teams_message(&self) -> Value {
+        let fact =
+            |title: &str, value: &str| json!({ "title": title, "value": teams_escape(value) });
+        let mut facts: Vec<Value> = self
+            .facts()
+            .into_iter()
+            .map(|(title, value)| fact(title, &value))
+            .collect();
+        facts.push(fact("Distribution", &self.distribution()));
+        if let Some(outliers) = self.outliers() {
+            facts.push(fact("Outliers", &outliers));
+        }
+
+        json!({
+            "type": "message",
+            "attachments": [{
+                "contentType": "application/vnd.microsoft.card.adaptive",
+                "content": {
+                    "$schema": "http://adaptivecards.io/schemas/adaptive-card.json",
+                    "type": "AdaptiveCard",
+                    "version": "1.4",
+                    "body": [
+                        {
+                            "type": "TextBlock",
+                            "size": "Large",
+                            "weight": "Bolder",
+                            "wrap": true,
+                            "text": teams_escape(&self.heading()),
+                        },
+                        { "type": "FactSet", "facts": facts },
+                    ],
+                },
+            }],
+        })
+    }
+}
+
+/// `ChatNotifier` posts round results to incoming chat webhooks
+#[derive(Clone, Debug)]
+pub struct ChatNotifier {
+    http: Client,
+}
+
+impl ChatNotifier {
+    /// Creates a notifier posting with a client from `webhooks::public_client`,
+    /// as chat webhook URLs are registered by hosts
+    pub fn new(http: &Client) -> Self {
+        Self { http: http.clone() }
+    }
+
+    /// Posts a summary in the background, failures are logged
+    /// as the round has already been revealed to the room
+    pub fn notify(&self, webhook: &ChatWebhook, summary: &RoundSummary) {
+        let body = match webhook.format {
+            ChatFormat::Slack => summary.slack_message(),
+            ChatFormat::Teams => summary.teams_message(),
+        };
+        let (http, url) = (self.http.clone(), webhook.url.clone());
+        tokio::spawn(async move {
+            // rooms restored from an older state file may hold URLs registered before they were checked
+            let result = match webhooks::validate_room_url(&url) {
+                Ok(_) => match http.post(&url).json(&body).send().await {
+                    Ok(response) => check_status(response).await.map(|_| ()),
+                    Err(err) => Err(err.into()),
+                },
+                Err(message) => Err(IntegrationError::Invalid(message)),
+            };
+            match result {
+                Ok(()) => info!("Posted round results to chat webhook"),
+                Err(err) => error!("Failed to post round results to chat webhook: {}", err),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{
+        test_support::{cast, room},
+        types::Story,
+    };
+
+    /// A split round on a story, voted on by players with hostile names
+    fn split_room() -> Room {
+        let mut room = room(&["<!channel>", "[Click](https://evil)", "bob"]);
+        cast(
+            &mut room,
+            &[("<!channel>", 1), ("[Click](https://evil)", 13), ("bob", 5)],
+        );
+        let story = Story {
+            id: Uuid::new_v4(),
+            title: "<https://evil|Login> & *more*".to_owned(),
+            url: None,
+            source: None,
+            estimate: None,
+            rounds: Vec::new(),
+            convergence: Convergence::default(),
+        };
+        room.current_story = Some(story.id);
+        room.stories.push(story);
+        room
+    }
+
+    #[test]
+    fn slack_message_escapes_player_and_story_text() {
+        let message = RoundSummary::for_room(&split_room()).slack_message();
+
+        assert_eq!(
+            message["text"],
+            "&lt;https://evil|Login&gt; &amp; *more*: No consensus, median 5"
+        );
+        let blocks = message["blocks"].as_array().expect("blocks");
+        let types: Vec<&str> = blocks
+            .iter()
+            .map(|block| block["type"].as_str().expect("block type"))
+            .collect();
+        assert_eq!(types, ["header", "section", "section", "context"]);
+        // headers are plain text so are shown as typed
+        assert_eq!(
+            blocks[0]["text"],
+            json!({ "type": "plain_text", "text": "<https://evil|Login> & *more*" })
+        );
+        assert_eq!(
+            blocks[1]["fields"][0],
+            json!({ "type": "mrkdwn", "text": "*Result*\nNo consensus, median 5" })
+        );
+        assert_eq!(blocks[1]["fields"][1]["text"], "*Votes*\n3");
+        assert_eq!(blocks[1]["fields"][2]["text"], "*Average*\n6.3");
+        assert_eq!(
+            blocks[2]["text"]["text"],
+            "*Distribution*\n1: 1, 5: 1, 13: 1"
+        );
+        let outliers = blocks[3]["elements"][0]["text"]
+            .as_str()
+            .expect("outliers are mrkdwn");
+        assert!(outliers.starts_with("Outliers: "));
+        assert!(outliers.contains("&lt;!channel&gt; (1)"));
+        assert!(outliers.contains("[Click](https://evil) (13)"));
+        assert!(!outliers.contains("bob"));
+        assert!(!outliers.contains('<'));
+    }
+
+    #[test]
+    fn teams_message_escapes_markdown_in_an_adaptive_card() {
+        let message = RoundSummary::for_room(&split_room()).teams_message();
+
+        assert_eq!(message["type"], "message");
+        let attachment = &message["attachments"][0];
+        assert_eq!(
+            attachment["contentType"],
+            "application/vnd.microsoft.card.adaptive"
+        );
+        let card = &attachment["content"];
+        assert_eq!(card["type"], "AdaptiveCard");
+        assert_eq!(card["body"][0]["type"], "TextBlock");
+        assert_eq!(
+            card["body"][0]["text"],
+            r"\<https://evil|Login\> & \*more\*"
+        );
+        let facts = card["body"][1]["facts"].as_array().expect("fact set");
+        let titles: Vec<&str> = facts
+            .iter()
+            .map(|fact| fact["title"].as_str().expect("fact title"))
+            .collect();
+        assert_eq!(
+            titles,
+            ["Result", "Votes", "Average", "Distribution", "Outliers"]
+        );
+        let outliers = facts[4]["value"].as_str().expect("outliers");
+        assert!(outliers.contains(r"\<!channel\> (1)"));
+        assert!(outliers.contains(r"\[Click\](https://evil) (13)"));
+    }
+
+    #[test]
+    fn outliers_are_hidden_without_attribution() {
+        let mut room = split_room();
+        room.settings.anonymous = true;
+
+        let slack = RoundSummary::for_room(&room).slack_message();
+        let teams = RoundSummary::for_room(&room).teams_message();
+
+        assert_eq!(slack["blocks"].as_array().expect("blocks").len(), 3);
+        let facts = teams["attachments"][0]["content"]["body"][1]["facts"]
+            .as_array()
+            .expect("fact set");
+        assert!(facts.iter().all(|fact| fact["title"] != "Outliers"));
+
+        // hosts can keep attribution for their own records
+        room.settings.keep_attribution = true;
+        let slack = RoundSummary::for_room(&room).slack_message();
+        assert_eq!(slack["blocks"].as_array().expect("blocks").len(), 4);
+    }
+
+    #[test]
+    fn consensus_rounds_have_no_outliers() {
+        let mut room = room(&["alice", "bob"]);
+        cast(&mut room, &[("alice", 3), ("bob", 3)]);
+
+        let message = RoundSummary::for_room(&room).slack_message();
+
+        assert_eq!(message["text"], "Round results: Consensus on 3");
+        assert_eq!(message["blocks"].as_array().expect("blocks").len(), 3);
+    }
+}
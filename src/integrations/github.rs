@@ -0,0 +1,536 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+
+use reqwest::{Client, RequestBuilder, Url, header};
+use serde::{Deserialize, de::DeserializeOwned, de::IgnoredAny};
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+use super::{IntegrationError, check_status};
+use crate::{
+    config::GitHubSettings,
+    stats::Convergence,
+    types::{Story, StorySource},
+};
+
+/// REST API version requested from GitHub
+const API_VERSION: &str = "2022-11-28";
+
+/// Adds an issue to the project, returning the existing item if it is already there
+const ADD_PROJECT_ITEM: &str = "mutation($project: ID!, $content: ID!) {
+  addProjectV2ItemById(input: { projectId: $project, contentId: $content }) { item { id } }
+}";
+
+/// Sets a numeric field on a project item
+const UPDATE_PROJECT_FIELD: &str =
+    "mutation($project: ID!, $item: ID!, $field: ID!, $value: Float!) {
+  updateProjectV2ItemFieldValue(input: {
+    projectId: $project, itemId: $item, fieldId: $field, value: { number: $value }
+  }) { projectV2Item { id } }
+}";
+
+#[derive(Debug, Deserialize)]
+struct Issue {
+    number: u64,
+    title: String,
+    html_url: String,
+    node_id: String,
+    /// Present when the issue is a pull request
+    pull_request: Option<IgnoredAny>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Label {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphQlResponse<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<GraphQlError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphQlError {
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AddProjectItem {
+    add_project_v2_item_by_id: ProjectItemPayload,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProjectItemPayload {
+    item: ProjectItem,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProjectItem {
+    id: String,
+}
+
+/// Numeric project field the estimate is written to
+#[derive(Clone, Debug)]
+struct ProjectField {
+    project_id: String,
+    field_id: String,
+}
+
+/// `GitHubClient` talks to the GitHub REST and GraphQL APIs
+#[derive(Clone, Debug)]
+pub struct GitHubClient {
+    http: Client,
+    api_url: Url,
+    graphql_url: Url,
+    token: String,
+    estimate_label_prefix: String,
+    /// Project field to write estimates to, labels are used when unset
+    project_field: Option<ProjectField>,
+    /// Maximum number of issues imported at once
+    max_results: u32,
+    /// Repositories issues may be imported from and written to, as `owner/name`
+    repos: Vec<String>,
+}
+
+impl GitHubClient {
+    /// Creates a client if a token is configured
+    pub fn from_settings(settings: &GitHubSettings, http: &Client) -> Option<Self> {
+        let token = settings.token.clone()?;
+        let project_field = settings
+            .project_id
+            .clone()
+            .zip(settings.project_field_id.clone())
+            .map(|(project_id, field_id)| ProjectField {
+                project_id,
+                field_id,
+            });
+
+        Some(Self {
+            http: http.clone(),
+            api_url: settings.api_url.clone(),
+            graphql_url: graphql_url(&settings.api_url),
+            token,
+            estimate_label_prefix: settings.estimate_label_prefix.clone(),
+            project_field,
+            max_results: settings.max_results,
+            repos: settings.repos.clone(),
+        })
+    }
+
+    /// Builds a REST URL from path segments, escaping each one
+    fn url<'a>(&self, segments: impl IntoIterator<Item = &'a str>) -> Url {
+        let mut url = self.api_url.clone();
+        url.path_segments_mut()
+            .expect("API URL is a valid base")
+            .pop_if_empty()
+            .extend(segments);
+        url
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        request
+            .bearer_auth(&self.token)
+            .header(header::ACCEPT, "application/vnd.github+json")
+            .header(header::USER_AGENT, env!("CARGO_PKG_NAME"))
+            .header("x-github-api-version", API_VERSION)
+    }
+
+    /// Splits a repository the operator allows imports from,
+    /// GitHub names are not case sensitive
+    fn allowed_repo<'a>(&self, repo: &'a str) -> Result<(&'a str, &'a str), IntegrationError> {
+        let (owner, name) = split_repo(repo)?;
+        let full_name = format!("{owner}/{name}");
+        if self
+            .repos
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(&full_name))
+        {
+            Ok((owner, name))
+        } else {
+            Err(IntegrationError::Invalid(format!(
+                "{full_name} is not a repository open to import"
+            )))
+        }
+    }
+
+    /// Fetches the open issues in a repository, optionally only
+    /// those in a milestone, as stories. Pull requests are skipped
+    pub async fn issues(
+        &self,
+        repo: &str,
+        milestone: Option<u64>,
+    ) -> Result<Vec<Story>, IntegrationError> {
+        let (owner, name) = self.allowed_repo(repo)?;
+        let mut query = vec![
+            ("state", "open".to_owned()),
+            ("per_page", self.max_results.to_string()),
+        ];
+        if let Some(milestone) = milestone {
+            query.push(("milestone", milestone.to_string()));
+        }
+        let request = self
+            .http
+            .get(self.url(["repos", owner, name, "issues"]))
+            .query(&query);
+        let response = check_status(self.authorize(request).send().await?).await?;
+        let issues: Vec<Issue> = response.json().await?;
+
+        Ok(issues
+            .into_iter()
+            .filter(|issue| issue.pull_request.is_none())
+            .map(|issue| Story {
+                id: Uuid::new_v4(),
+                title: format!("#{}: {}", issue.number, issue.title),
+                url: Some(issue.html_url),
+                source: Some(StorySource::GitHub {
+                    repo: format!("{owner}/{name}"),
+                    number: issue.number,
+                    node_id: issue.node_id,
+                }),
+                estimate: None,
+                rounds: Vec::new(),
+                convergence: Convergence::default(),
+            })
+            .collect())
+    }
+
+    /// Writes an estimate to the configured project field,
+    /// or labels the issue with it when no field is configured.
+    /// Issues outside the allowed repositories are refused
+    pub async fn set_estimate(
+        &self,
+        repo: &str,
+        number: u64,
+        node_id: &str,
+        points: u8,
+    ) -> Result<(), IntegrationError> {
+        self.allowed_repo(repo)?;
+        match &self.project_field {
+            Some(field) => self.set_project_field(field, node_id, points).await,
+            None => self.set_label(repo, number, points).await,
+        }
+    }
+
+    /// Replaces any previous estimate label on the issue
+    async fn set_label(&self, repo: &str, number: u64, points: u8) -> Result<(), IntegrationError> {
+        let (owner, name) = split_repo(repo)?;
+        let number = number.to_string();
+        let label = format!("{}{points}", self.estimate_label_prefix);
+        let labels_url = self.url(["repos", owner, name, "issues", &number, "labels"]);
+
+        let response = check_status(
+            self.authorize(self.http.get(labels_url.clone()))
+                .send()
+                .await?,
+        )
+        .await?;
+        let existing: Vec<Label> = response.json().await?;
+        for stale in existing.iter().filter(|existing| {
+            existing.name.starts_with(&self.estimate_label_prefix) && existing.name != label
+        }) {
+            let url = self.url([
+                "repos",
+                owner,
+                name,
+                "issues",
+                &number,
+                "labels",
+                &stale.name,
+            ]);
+            check_status(self.authorize(self.http.delete(url)).send().await?).await?;
+        }
+
+        let request = self
+            .http
+            .post(labels_url)
+            .json(&json!({ "labels": [label] }));
+        check_status(self.authorize(request).send().await?).await?;
+        Ok(())
+    }
+
+    /// Adds the issue to the project if needed and sets the estimate field
+    async fn set_project_field(
+        &self,
+        field: &ProjectField,
+        node_id: &str,
+        points: u8,
+    ) -> Result<(), IntegrationError> {
+        let added: AddProjectItem = self
+            .graphql(
+                ADD_PROJECT_ITEM,
+                json!({ "project": field.project_id, "content": node_id }),
+            )
+            .await?;
+        self.graphql::<Value>(
+            UPDATE_PROJECT_FIELD,
+            json!({
+                "project": field.project_id,
+                "item": added.add_project_v2_item_by_id.item.id,
+                "field": field.field_id,
+                "value": points,
+            }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Runs a GraphQL query, GitHub reports errors in the body with a 200
+    async fn graphql<T: DeserializeOwned>(
+        &self,
+        query: &str,
+        variables: Value,
+    ) -> Result<T, IntegrationError> {
+        let request = self
+            .http
+            .post(self.graphql_url.clone())
+            .json(&json!({ "query": query, "variables": variables }));
+        let response = check_status(self.authorize(request).send().await?).await?;
+        let body: GraphQlResponse<T> = response.json().await?;
+
+        if let Some(error) = body.errors.into_iter().next() {
+            return Err(IntegrationError::Rejected(error.message));
+        }
+        body.data
+            .ok_or_else(|| IntegrationError::Rejected("GraphQL response had no data".to_owned()))
+    }
+}
+
+/// Works out the GraphQL endpoint from the REST base URL.
+/// GitHub Enterprise Server serves REST under `/api/v3` and GraphQL
+/// at `/api/graphql`, github.com serves GraphQL at `/graphql`
+fn graphql_url(api_url: &Url) -> Url {
+    let mut url = api_url.clone();
+    let path = api_url.path().trim_end_matches('/');
+    let base = path.strip_suffix("/v3").unwrap_or(path);
+    url.set_path(&format!("{base}/graphql"));
+    url
+}
+
+/// Splits an `owner/name` repository, rejecting anything else
+fn split_repo(repo: &str) -> Result<(&str, &str), IntegrationError> {
+    let valid_part = |part: &str| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    };
+    match repo.trim().split_once('/') {
+        Some((owner, name)) if valid_part(owner) && valid_part(name) => Ok((owner, name)),
+        _ => Err(IntegrationError::Invalid(format!(
+            "{repo:?} is not a repository in owner/name form"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        Json, Router,
+        http::StatusCode,
+        routing::{delete, get, post},
+    };
+
+    use super::*;
+    use crate::{config::Config, test_support::StandIn};
+
+    fn client(github: &StandIn, project: bool) -> GitHubClient {
+        let mut settings = Config::default().github;
+        settings.api_url = Url::parse(&format!("{}/api/v3", github.url)).expect("valid URL");
+        settings.token = Some("gh-token".to_owned());
+        settings.repos = vec!["acme/app".to_owned()];
+        if project {
+            settings.project_id = Some("PROJECT_1".to_owned());
+            settings.project_field_id = Some("FIELD_1".to_owned());
+        }
+        GitHubClient::from_settings(&settings, &Client::new()).expect("GitHub is configured")
+    }
+
+    #[test]
+    fn graphql_url_follows_the_rest_url() {
+        let url = |api: &str| graphql_url(&Url::parse(api).expect("valid URL")).to_string();
+
+        assert_eq!(
+            url("https://api.github.com"),
+            "https://api.github.com/graphql"
+        );
+        assert_eq!(
+            url("https://github.example.com/api/v3/"),
+            "https://github.example.com/api/graphql"
+        );
+    }
+
+    #[tokio::test]
+    async fn issues_imports_open_issues_without_pull_requests() {
+        let github = StandIn::start(Router::new().route(
+            "/api/v3/repos/Acme/App/issues",
+            get(|| async {
+                Json(json!([
+                    {
+                        "number": 7,
+                        "title": "Login page",
+                        "html_url": "https://github.test/acme/app/issues/7",
+                        "node_id": "I_7",
+                    },
+                    {
+                        "number": 8,
+                        "title": "Fix login",
+                        "html_url": "https://github.test/acme/app/pull/8",
+                        "node_id": "PR_8",
+                        "pull_request": {},
+                    },
+                ]))
+            }),
+        ))
+        .await;
+
+        let stories = client(&github, false)
+            .issues("Acme/App", Some(3))
+            .await
+            .expect("import succeeds");
+
+        assert_eq!(stories.len(), 1);
+        assert_eq!(stories[0].title, "#7: Login page");
+        assert_eq!(
+            stories[0].url.as_deref(),
+            Some("https://github.test/acme/app/issues/7")
+        );
+        assert!(matches!(
+            &stories[0].source,
+            Some(StorySource::GitHub { repo, number: 7, node_id })
+                if repo == "Acme/App" && node_id == "I_7"
+        ));
+
+        let requests = github.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].query("state").as_deref(), Some("open"));
+        assert_eq!(requests[0].query("milestone").as_deref(), Some("3"));
+        assert_eq!(requests[0].query("per_page").as_deref(), Some("50"));
+        assert_eq!(requests[0].header("authorization"), Some("Bearer gh-token"));
+        assert_eq!(
+            requests[0].header("x-github-api-version"),
+            Some(API_VERSION)
+        );
+    }
+
+    #[tokio::test]
+    async fn issues_refuses_repositories_not_configured() {
+        let github = StandIn::start(Router::new()).await;
+
+        let err = client(&github, false)
+            .issues("acme/payroll", None)
+            .await
+            .expect_err("import is refused");
+
+        assert!(matches!(err, IntegrationError::Invalid(_)));
+        assert!(github.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn set_estimate_replaces_estimate_labels() {
+        let labels = "/api/v3/repos/acme/app/issues/7/labels";
+        let github = StandIn::start(
+            Router::new()
+                .route(
+                    labels,
+                    get(|| async {
+                        Json(json!([
+                            { "name": "bug" },
+                            { "name": "estimate: 3" },
+                            { "name": "estimate: 5" },
+                        ]))
+                    })
+                    .post(|| async { Json(json!([])) }),
+                )
+                .route(
+                    &format!("{labels}/{{name}}"),
+                    delete(|| async { Json(json!([])) }),
+                ),
+        )
+        .await;
+
+        client(&github, false)
+            .set_estimate("acme/app", 7, "I_7", 5)
+            .await
+            .expect("estimate is written");
+
+        let requests = github.requests();
+        let calls: Vec<_> = requests
+            .iter()
+            .map(|request| (request.method.as_str(), request.uri.as_str()))
+            .collect();
+        assert_eq!(
+            calls,
+            [
+                ("GET", "/api/v3/repos/acme/app/issues/7/labels"),
+                (
+                    "DELETE",
+                    "/api/v3/repos/acme/app/issues/7/labels/estimate:%203"
+                ),
+                ("POST", "/api/v3/repos/acme/app/issues/7/labels"),
+            ]
+        );
+        assert_eq!(requests[2].json(), json!({ "labels": ["estimate: 5"] }));
+    }
+
+    #[tokio::test]
+    async fn set_estimate_writes_the_project_field() {
+        let github = StandIn::start(Router::new().route(
+            "/api/graphql",
+            post(|Json(request): Json<Value>| async move {
+                let item = json!({ "id": "ITEM_1" });
+                let adding = request["query"]
+                    .as_str()
+                    .is_some_and(|query| query.contains("addProjectV2ItemById"));
+                if adding {
+                    Json(json!({ "data": { "addProjectV2ItemById": { "item": item } } }))
+                } else {
+                    let updated = json!({ "projectV2Item": item });
+                    Json(json!({ "data": { "updateProjectV2ItemFieldValue": updated } }))
+                }
+            }),
+        ))
+        .await;
+
+        client(&github, true)
+            .set_estimate("acme/app", 7, "I_7", 8)
+            .await
+            .expect("estimate is written");
+
+        let requests = github.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(
+            requests[0].json()["variables"],
+            json!({ "project": "PROJECT_1", "content": "I_7" })
+        );
+        assert_eq!(
+            requests[1].json()["variables"],
+            json!({ "project": "PROJECT_1", "item": "ITEM_1", "field": "FIELD_1", "value": 8 })
+        );
+    }
+
+    #[tokio::test]
+    async fn set_estimate_reports_graphql_errors() {
+        let github = StandIn::start(Router::new().route(
+            "/api/graphql",
+            post(|| async {
+                (
+                    StatusCode::OK,
+                    Json(json!({ "errors": [{ "message": "Could not resolve to a node" }] })),
+                )
+            }),
+        ))
+        .await;
+
+        let err = client(&github, true)
+            .set_estimate("acme/app", 7, "I_7", 8)
+            .await
+            .expect_err("estimate fails");
+
+        assert!(matches!(
+            err,
+            IntegrationError::Rejected(message) if message == "Could not resolve to a node"
+        ));
+    }
+}
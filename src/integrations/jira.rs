@@ -0,0 +1,297 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+
+use reqwest::{Client, RequestBuilder};
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use super::{IntegrationError, check_status};
+use crate::{
+    config::JiraSettings,
+    stats::Convergence,
+    types::{Story, StorySource},
+};
+
+/// How requests to Jira are authenticated
+#[derive(Clone, Debug)]
+enum Auth {
+    /// Personal access token, used by Jira Server and Data Center
+    Bearer(String),
+    /// Email and API token, used by Jira Cloud
+    Basic { email: String, token: String },
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchResponse {
+    issues: Vec<Issue>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Issue {
+    key: String,
+    fields: IssueFields,
+}
+
+#[derive(Debug, Deserialize)]
+struct IssueFields {
+    summary: String,
+}
+
+/// `JiraClient` talks to the REST API of a Jira compatible tracker
+#[derive(Clone, Debug)]
+pub struct JiraClient {
+    http: Client,
+    /// Base URL without a trailing slash
+    base_url: String,
+    auth: Auth,
+    /// Custom field holding story points, e.g. `customfield_10016`
+    story_points_field: String,
+    /// Maximum number of issues imported by one search
+    max_results: u32,
+    /// Keys of the projects issues may be imported from and written to
+    projects: Vec<String>,
+}
+
+impl JiraClient {
+    /// Creates a client if a base URL and token are configured
+    pub fn from_settings(settings: &JiraSettings, http: &Client) -> Option<Self> {
+        let base_url = settings.base_url.as_ref()?;
+        let token = settings.token.clone()?;
+        let auth = match settings.email.clone() {
+            Some(email) => Auth::Basic { email, token },
+            None => Auth::Bearer(token),
+        };
+
+        Some(Self {
+            http: http.clone(),
+            base_url: base_url.as_str().trim_end_matches('/').to_owned(),
+            auth,
+            story_points_field: settings.story_points_field.clone(),
+            max_results: settings.max_results,
+            projects: settings.projects.clone(),
+        })
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.auth {
+            Auth::Bearer(token) => request.bearer_auth(token),
+            Auth::Basic { email, token } => request.basic_auth(email, Some(token)),
+        }
+    }
+
+    /// Whether an issue belongs to one of the configured projects
+    fn allowed(&self, key: &str) -> bool {
+        key.rsplit_once('-')
+            .is_some_and(|(project, _)| self.projects.iter().any(|allowed| allowed == project))
+    }
+
+    /// Narrows a JQL query to the configured projects, keeping any
+    /// `ORDER BY` clause at the end where JQL needs it
+    fn scoped_jql(&self, jql: &str) -> String {
+        let projects = self
+            .projects
+            .iter()
+            .map(|key| format!("\"{key}\""))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let (filter, order) = jql
+            .to_ascii_lowercase()
+            .rfind("order by")
+            .map_or((jql, ""), |at| jql.split_at(at));
+
+        match filter.trim() {
+            "" => format!("project in ({projects}) {order}"),
+            filter => format!("project in ({projects}) AND ({filter}) {order}"),
+        }
+        .trim_end()
+        .to_owned()
+    }
+
+    /// Fetches the issues matching a JQL query as stories.
+    /// Only issues in the configured projects are returned, whatever the query
+    pub async fn search(&self, jql: &str) -> Result<Vec<Story>, IntegrationError> {
+        let request = self
+            .http
+            .get(format!("{}/rest/api/2/search", self.base_url))
+            .query(&[
+                ("jql", self.scoped_jql(jql).as_str()),
+                ("fields", "summary"),
+                ("maxResults", &self.max_results.to_string()),
+            ]);
+        let response = check_status(self.authorize(request).send().await?).await?;
+        let search: SearchResponse = response.json().await?;
+
+        // the query can still reach other projects with OR, so check each issue
+        Ok(search
+            .issues
+            .into_iter()
+            .filter(|issue| self.allowed(&issue.key))
+            .map(|issue| Story {
+                id: Uuid::new_v4(),
+                title: format!("{}: {}", issue.key, issue.fields.summary),
+                url: Some(format!("{}/browse/{}", self.base_url, issue.key)),
+                source: Some(StorySource::Jira { key: issue.key }),
+                estimate: None,
+                rounds: Vec::new(),
+                convergence: Convergence::default(),
+            })
+            .collect())
+    }
+
+    /// Writes an estimate to the issue's story points field,
+    /// refusing issues outside the configured projects
+    pub async fn set_estimate(&self, key: &str, points: u8) -> Result<(), IntegrationError> {
+        if !self.allowed(key) {
+            return Err(IntegrationError::Invalid(format!(
+                "{key} is not in a project open to import"
+            )));
+        }
+        let request = self
+            .http
+            .put(format!("{}/rest/api/2/issue/{key}", self.base_url))
+            .json(&json!({ "fields": { &self.story_points_field: points } }));
+        check_status(self.authorize(request).send().await?).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        Json, Router,
+        http::StatusCode,
+        routing::{get, put},
+    };
+    use reqwest::Url;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{config::Config, test_support::StandIn};
+
+    fn client(base_url: &str, email: Option<&str>) -> JiraClient {
+        let mut settings = Config::default().jira;
+        settings.base_url = Some(Url::parse(base_url).expect("stand-in URL is valid"));
+        settings.token = Some("jira-token".to_owned());
+        settings.email = email.map(str::to_owned);
+        settings.projects = vec!["ABC".to_owned(), "DEF".to_owned()];
+        JiraClient::from_settings(&settings, &Client::new()).expect("Jira is configured")
+    }
+
+    #[test]
+    fn scoped_jql_narrows_to_projects_and_keeps_ordering_last() {
+        let jira = client("http://jira.test", None);
+
+        assert_eq!(
+            jira.scoped_jql("status = Open ORDER BY rank"),
+            r#"project in ("ABC", "DEF") AND (status = Open) ORDER BY rank"#
+        );
+        assert_eq!(jira.scoped_jql("  "), r#"project in ("ABC", "DEF")"#);
+        assert_eq!(
+            jira.scoped_jql("order by created"),
+            r#"project in ("ABC", "DEF") order by created"#
+        );
+    }
+
+    #[tokio::test]
+    async fn search_imports_issues_from_configured_projects() {
+        let tracker = StandIn::start(Router::new().route(
+            "/rest/api/2/search",
+            get(|| async {
+                Json(json!({ "issues": [
+                    { "key": "ABC-1", "fields": { "summary": "Login page" } },
+                    { "key": "XYZ-9", "fields": { "summary": "Payroll" } },
+                ] }))
+            }),
+        ))
+        .await;
+
+        let stories = client(&tracker.url, None)
+            .search("project = XYZ OR status = Open")
+            .await
+            .expect("search succeeds");
+
+        assert_eq!(stories.len(), 1);
+        assert_eq!(stories[0].title, "ABC-1: Login page");
+        assert_eq!(
+            stories[0].url.as_deref(),
+            Some(format!("{}/browse/ABC-1", tracker.url).as_str())
+        );
+        assert!(matches!(
+            &stories[0].source,
+            Some(StorySource::Jira { key }) if key == "ABC-1"
+        ));
+
+        let requests = tracker.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(
+            requests[0].query("jql").as_deref(),
+            Some(r#"project in ("ABC", "DEF") AND (project = XYZ OR status = Open)"#)
+        );
+        assert_eq!(requests[0].query("maxResults").as_deref(), Some("50"));
+        assert_eq!(
+            requests[0].header("authorization"),
+            Some("Bearer jira-token")
+        );
+    }
+
+    #[tokio::test]
+    async fn search_reports_error_responses() {
+        let tracker = StandIn::start(Router::new().route(
+            "/rest/api/2/search",
+            get(|| async { (StatusCode::BAD_REQUEST, "Error in the JQL Query") }),
+        ))
+        .await;
+
+        let err = client(&tracker.url, None)
+            .search("status = ")
+            .await
+            .expect_err("search fails");
+
+        assert!(matches!(
+            err,
+            IntegrationError::Status { status, body }
+                if status == StatusCode::BAD_REQUEST && body == "Error in the JQL Query"
+        ));
+    }
+
+    #[tokio::test]
+    async fn set_estimate_writes_story_points_field() {
+        let tracker = StandIn::start(Router::new().route(
+            "/rest/api/2/issue/{key}",
+            put(|| async { StatusCode::NO_CONTENT }),
+        ))
+        .await;
+
+        client(&tracker.url, Some("dev@example.com"))
+            .set_estimate("DEF-12", 5)
+            .await
+            .expect("estimate is written");
+
+        let requests = tracker.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "PUT");
+        assert_eq!(requests[0].uri, "/rest/api/2/issue/DEF-12");
+        assert_eq!(
+            requests[0].json(),
+            json!({ "fields": { "customfield_10016": 5 } })
+        );
+        // Jira Cloud takes the email and token as basic auth
+        assert_eq!(
+            requests[0].header("authorization"),
+            Some("Basic ZGV2QGV4YW1wbGUuY29tOmppcmEtdG9rZW4=")
+        );
+    }
+
+    #[tokio::test]
+    async fn set_estimate_refuses_other_projects() {
+        let tracker = StandIn::start(Router::new()).await;
+
+        let err = client(&tracker.url, None)
+            .set_estimate("XYZ-9", 5)
+            .await
+            .expect_err("estimate is refused");
+
+        assert!(matches!(err, IntegrationError::Invalid(_)));
+        assert!(tracker.requests().is_empty());
+    }
+}
@@ -0,0 +1,135 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+
+use std::{error::Error, fmt};
+
+use reqwest::{Client, Response, StatusCode};
+use tracing::{error, info};
+
+use crate::{config::Config, types::StorySource, webhooks};
+
+/// Slack and Teams incoming webhook notifications
+pub mod chat;
+/// GitHub Issues and Projects integration
+pub mod github;
+/// Jira compatible issue tracker integration
+pub mod jira;
+
+/// `Integrations` holds a client for each configured external service
+#[derive(Debug)]
+pub struct Integrations {
+    /// Jira client, if a base URL and token are configured
+    pub jira: Option<jira::JiraClient>,
+    /// GitHub client, if a token is configured
+    pub github: Option<github::GitHubClient>,
+    /// Posts round results to the chat webhooks registered on rooms
+    pub chat: chat::ChatNotifier,
+}
+
+impl Integrations {
+    /// Builds the clients for every integration enabled in the config
+    pub fn from_config(config: &Config) -> Result<Self, reqwest::Error> {
+        let http = Client::builder()
+            .timeout(config.integration_timeout)
+            .build()?;
+        Ok(Self {
+            jira: jira::JiraClient::from_settings(&config.jira, &http),
+            github: github::GitHubClient::from_settings(&config.github, &http),
+            chat: chat::ChatNotifier::new(&webhooks::public_client(config.integration_timeout)?),
+        })
+    }
+
+    /// Writes an agreed estimate back to the tracker a story came from.
+    /// Runs in the background, failures are logged as the round has
+    /// already been revealed to the room
+    pub fn write_estimate(&self, source: &StorySource, points: u8) {
+        match source {
+            StorySource::Jira { key } => {
+                let Some(jira) = self.jira.clone() else {
+                    return;
+                };
+                let key = key.clone();
+                tokio::spawn(async move {
+                    match jira.set_estimate(&key, points).await {
+                        Ok(()) => info!("Wrote estimate {} to Jira issue {}", points, key),
+                        Err(err) => {
+                            error!("Failed to write estimate to Jira issue {}: {}", key, err);
+                        }
+                    }
+                });
+            }
+            StorySource::GitHub {
+                repo,
+                number,
+                node_id,
+            } => {
+                let Some(github) = self.github.clone() else {
+                    return;
+                };
+                let (repo, number, node_id) = (repo.clone(), *number, node_id.clone());
+                tokio::spawn(async move {
+                    match github.set_estimate(&repo, number, &node_id, points).await {
+                        Ok(()) => info!("Wrote estimate {} to {}#{}", points, repo, number),
+                        Err(err) => {
+                            error!("Failed to write estimate to {}#{}: {}", repo, number, err);
+                        }
+                    }
+                });
+            }
+        }
+    }
+}
+
+/// Turns a non success response into an error, keeping the body
+/// as external APIs explain what went wrong there
+pub async fn check_status(response: Response) -> Result<Response, IntegrationError> {
+    let status = response.status();
+    if status.is_success() {
+        Ok(response)
+    } else {
+        let body = response.text().await.unwrap_or_default();
+        Err(IntegrationError::Status { status, body })
+    }
+}
+
+#[derive(Debug)]
+pub enum IntegrationError {
+    /// The integration is not configured on this server
+    NotConfigured(&'static str),
+    /// The request was invalid before it was sent
+    Invalid(String),
+    /// The service accepted the request but reported an error
+    Rejected(String),
+    /// The request could not be sent or the response could not be read
+    Request(reqwest::Error),
+    /// The service responded with an error status
+    Status { status: StatusCode, body: String },
+}
+
+impl From<reqwest::Error> for IntegrationError {
+    fn from(err: reqwest::Error) -> Self {
+        Self::Request(err)
+    }
+}
+
+impl Error for IntegrationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Request(err) => Some(err),
+            Self::NotConfigured(_) | Self::Invalid(_) | Self::Rejected(_) | Self::Status { .. } => {
+                None
+            }
+        }
+    }
+}
+
+impl fmt::Display for IntegrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotConfigured(name) => write!(f, "{name} is not configured"),
+            Self::Invalid(message) => write!(f, "{message}"),
+            Self::Rejected(message) => write!(f, "Request rejected: {message}"),
+            Self::Request(err) => write!(f, "Request failed: {err}"),
+            Self::Status { status, body } => write!(f, "Request failed with {status}: {body}"),
+        }
+    }
+}
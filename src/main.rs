@@ -1,36 +1,93 @@
 #![warn(clippy::all, clippy::pedantic, clippy::nursery)]
-use std::{env::var, error::Error, sync::Arc};
+use std::{error::Error, sync::Arc};
 
 use axum::{
-    Router,
+    Extension, Router,
     body::Body,
-    extract::OriginalUri,
+    extract::{OriginalUri, State},
     http::{
         HeaderValue, Request, StatusCode,
         header::{self, HOST},
     },
-    middleware::{Next, from_fn},
+    middleware::{Next, from_fn, from_fn_with_state},
     response::{IntoResponse, Redirect, Response},
-    routing::get_service,
+    routing::{get, get_service, post},
     serve,
 };
 use dotenv::dotenv;
-use socketioxide::{SocketIo, extract::SocketRef};
+use socketioxide::{
+    SocketIo,
+    extract::{SocketRef, State as SocketState, TryData},
+    handler::ConnectHandler,
+};
 use tokio::net::TcpListener;
 use tower::ServiceBuilder;
 use tower_http::{
-    cors::CorsLayer,
+    cors::{AllowOrigin, Any, CorsLayer},
     services::{ServeDir, ServeFile},
     set_header::SetResponseHeaderLayer,
     trace::TraceLayer,
 };
-use tracing::{error, info, subscriber::set_global_default};
+use tracing::{error, info, subscriber::set_global_default, warn};
 use tracing_subscriber::FmtSubscriber;
 
+use crate::{
+    participants::SessionAuth,
+    protocol::ClientEvent,
+    types::{
+        AcceptEstimateEvent, AddWebhookEvent, CreateRoomEvent, ImportGitHubIssuesEvent,
+        ImportJiraIssuesEvent, JoinRoomEvent, JoinTeamRoomEvent, PlayerExitEvent,
+        RemoveWebhookEvent, ResetVotesEvent, ResyncEvent, RetractVoteEvent, RevealCardsEvent,
+        RevoteEvent, SelectStoryEvent, SetChatWebhookEvent, SetSpectatorEvent, UpdateProfileEvent,
+        UpdateSettingsEvent, VoteEvent,
+    },
+};
+
+/// Admin module containing the operator HTTP API.
+mod admin;
+/// Auth module containing optional login through an OIDC provider.
+mod auth;
+/// Config module containing the server configuration loaded at startup.
+mod config;
+/// Delta module containing the changes broadcast to rooms between snapshots.
+mod delta;
 /// Handlers module containing the logic for handling socket events.
 mod handlers;
+/// Health module containing the health, readiness and diagnostics endpoints.
+mod health;
+/// Hub module containing room broadcasts for clients outside Socket.IO.
+mod hub;
+/// Integrations module containing clients for issue trackers and chat services.
+mod integrations;
+/// Origins module containing host and origin matching.
+mod origins;
+/// Participants module containing the stable IDs players are known by.
+mod participants;
+/// Profile module containing how players appear in a room besides their name.
+mod profile;
+/// Protocol module containing the event protocol version and its schema.
+mod protocol;
+/// Settings module containing the options a host can change in a room.
+mod settings;
+/// SSE module containing the read-only event feed for room observers.
+mod sse;
+/// Stats module containing vote statistics for revealed rounds.
+mod stats;
+/// Store module containing persistence of rooms across restarts.
+mod store;
+/// Tasks module containing long running background tasks.
+mod tasks;
+/// Teams module containing persistent teams and their HTTP API.
+mod teams;
+/// Test support module containing local stand-ins for external services.
+#[cfg(test)]
+mod test_support;
 /// Types module containing the application state and data structures.
 mod types;
+/// Webhooks module containing signed delivery of room events.
+mod webhooks;
+/// WebSocket module containing the plain JSON protocol for CLIs and bots.
+mod ws;
 
 /// Middleware to log 404 Not Found responses.
 /// - Logs the request URI when a 404 response is encountered.
@@ -48,93 +105,373 @@ async fn log_404(req: Request<Body>, next: Next) -> Response {
     response
 }
 
-async fn enforce_host(req: Request<Body>, next: Next) -> Response {
-    if var("SKIP_HOST_ENFORCEMENT").ok().is_some() {
+/// Works out the scheme the client used for a request.
+/// - Prefers the `X-Forwarded-Proto` header set by the proxy in front of us.
+/// - Falls back to the request URI and finally to plain http.
+fn request_scheme(req: &Request<Body>) -> &str {
+    req.headers()
+        .get("x-forwarded-proto")
+        .and_then(|h| h.to_str().ok())
+        .filter(|proto| matches!(*proto, "http" | "https"))
+        .or_else(|| req.uri().scheme_str())
+        .unwrap_or("http")
+}
+
+/// Middleware to redirect requests for unknown hosts to the canonical host.
+/// - Hosts matching any allowed host pattern are served as is.
+/// - Redirects keep the scheme the client used.
+/// - Skipped entirely when host enforcement is disabled in the config.
+async fn enforce_host(
+    State(app_state): State<Arc<types::AppState>>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let config = &app_state.config;
+    if config.skip_host_enforcement {
         return next.run(req).await;
     }
 
-    let allowed_host =
-        var("ALLOWED_HOST").expect("The ALLOWED_HOST environment variable must be set");
     let host = req.headers().get(HOST).and_then(|h| h.to_str().ok());
 
     if let Some(host) = host
-        && host != allowed_host
+        && !config.host_allowed(host)
+        && let Some(canonical_host) = config.canonical_host()
     {
-        info!("Redirecting to allowed host: {}", allowed_host);
+        info!("Redirecting {} to allowed host: {}", host, canonical_host);
+        let scheme = request_scheme(&req);
         let uri = req.uri();
-        let location = format!("https://{allowed_host}{uri}");
+        let path = uri.path_and_query().map_or("/", |p| p.as_str());
+        let location = format!("{scheme}://{canonical_host}{path}");
         return Redirect::permanent(&location).into_response();
     }
     next.run(req).await
 }
 
 /// Called when a new client connects.
+/// - Starts the client's session, resuming its participant ID if it has one.
 /// - Initializes the connection handlers for the socket.
 /// - Logs the connection event.
-async fn on_connect(socket: SocketRef) {
+async fn on_connect(
+    socket: SocketRef,
+    TryData(auth): TryData<SessionAuth>,
+    app_state: SocketState<Arc<types::AppState>>,
+) {
     info!("Client connected: {}", socket.id);
+    handlers::start_session(
+        &handlers::Peer::SocketIo(socket.clone()),
+        &app_state,
+        &auth.unwrap_or_default(),
+        &socket.req_parts().headers,
+    );
+
+    socket.on(CreateRoomEvent::EVENT, handlers::handle_create_room);
+
+    socket.on(JoinRoomEvent::EVENT, handlers::handle_join_room);
+    socket.on(JoinTeamRoomEvent::EVENT, handlers::handle_join_team_room);
+
+    socket.on(VoteEvent::EVENT, handlers::handle_vote);
+
+    socket.on(RetractVoteEvent::EVENT, handlers::handle_retract_vote);
+
+    socket.on(SetSpectatorEvent::EVENT, handlers::handle_set_spectator);
+
+    socket.on(UpdateProfileEvent::EVENT, handlers::handle_update_profile);
+
+    socket.on(RevealCardsEvent::EVENT, handlers::handle_reveal_cards);
+
+    socket.on(ResetVotesEvent::EVENT, handlers::handle_reset_votes);
+
+    socket.on(PlayerExitEvent::EVENT, handlers::handle_player_exit);
+
+    socket.on(ResyncEvent::EVENT, handlers::handle_resync);
 
-    socket.on("createRoom", handlers::handle_create_room);
+    socket.on(
+        ImportJiraIssuesEvent::EVENT,
+        handlers::handle_import_jira_issues,
+    );
 
-    socket.on("joinRoom", handlers::handle_join_room);
+    socket.on(
+        ImportGitHubIssuesEvent::EVENT,
+        handlers::handle_import_github_issues,
+    );
 
-    socket.on("vote", handlers::handle_vote);
+    socket.on(SelectStoryEvent::EVENT, handlers::handle_select_story);
 
-    socket.on("revealCards", handlers::handle_reveal_cards);
+    socket.on(AddWebhookEvent::EVENT, handlers::handle_add_webhook);
 
-    socket.on("resetVotes", handlers::handle_reset_votes);
+    socket.on(RemoveWebhookEvent::EVENT, handlers::handle_remove_webhook);
 
-    socket.on("exitRoom", handlers::handle_player_exit);
+    socket.on(
+        SetChatWebhookEvent::EVENT,
+        handlers::handle_set_chat_webhook,
+    );
+
+    socket.on(UpdateSettingsEvent::EVENT, handlers::handle_update_settings);
+
+    socket.on(RevoteEvent::EVENT, handlers::handle_revote);
+
+    socket.on(AcceptEstimateEvent::EVENT, handlers::handle_accept_estimate);
 
     socket.on_disconnect(handlers::handle_disconnect);
 }
 
+/// Restores rooms and teams saved by the server, if a store is configured.
+async fn restore_state(app_state: &types::AppState) {
+    if let Some(store) = &app_state.store {
+        match store.load().await {
+            Ok((rooms, teams)) => {
+                info!(
+                    "Restored {} rooms and {} teams from the store",
+                    rooms.len(),
+                    teams.len()
+                );
+                *app_state.rooms.lock().await = rooms;
+                *app_state.teams.lock().await = teams;
+            }
+            Err(err) => warn!("Could not restore rooms, starting empty: {}", err),
+        }
+    }
+}
+
+/// Builds the CORS layer allowing only the configured origins.
+fn cors_layer(app_state: &Arc<types::AppState>) -> CorsLayer {
+    let app_state = Arc::<types::AppState>::clone(app_state);
+    CorsLayer::new()
+        .allow_methods(Any)
+        .allow_headers(Any)
+        .allow_origin(AllowOrigin::predicate(move |origin, _| {
+            origin
+                .to_str()
+                .is_ok_and(|origin| app_state.config.origin_allowed(origin))
+        }))
+}
+
+/// Builds the routes for clients that do not use Socket.IO,
+/// the WebSocket protocol, the observer event feed, the protocol schema and login.
+fn client_routes(app_state: &Arc<types::AppState>, io: &SocketIo) -> Router {
+    Router::new()
+        .route("/auth/login", get(auth::login))
+        .route("/auth/callback", get(auth::callback))
+        .route("/auth/me", get(auth::me))
+        .route("/auth/logout", post(auth::logout))
+        .route("/ws", get(ws::ws_handler))
+        .route("/api/rooms/{id}/events", get(sse::room_events))
+        .route("/api/protocol", get(protocol::describe))
+        .route("/api/teams", post(teams::create_team))
+        .route(
+            "/api/teams/{id}",
+            get(teams::get_team)
+                .put(teams::update_team)
+                .delete(teams::delete_team),
+        )
+        .layer(Extension(io.clone()))
+        .with_state(Arc::<types::AppState>::clone(app_state))
+}
+
+/// Builds the health probe and operator routes.
+/// - Health probes skip host enforcement as they come from inside the network.
+/// - Operator routes are guarded by the admin token and only served on allowed hosts.
+fn operator_routes(app_state: &Arc<types::AppState>, io: &SocketIo) -> Router {
+    let admin_routes = Router::new()
+        .route("/debug/rooms", get(health::debug_rooms))
+        .route("/admin/rooms", get(admin::list_rooms))
+        .route("/admin/rooms/{id}/close", post(admin::close_room))
+        .route("/admin/broadcast", post(admin::broadcast))
+        .layer(from_fn_with_state(
+            Arc::<types::AppState>::clone(app_state),
+            admin::require_admin,
+        ))
+        .layer(Extension(io.clone()))
+        .layer(from_fn_with_state(
+            Arc::<types::AppState>::clone(app_state),
+            enforce_host,
+        ));
+
+    Router::new()
+        .route("/healthz", get(health::healthz))
+        .route("/readyz", get(health::readyz))
+        .merge(admin_routes)
+        .layer(TraceLayer::new_for_http())
+        .with_state(Arc::<types::AppState>::clone(app_state))
+}
+
+/// Prints the protocol schema instead of starting the server when asked to.
+/// - `schema` prints the JSON Schema.
+/// - `typescript` prints the TypeScript definitions used by the frontend.
+fn print_protocol() -> bool {
+    match std::env::args().nth(1).as_deref() {
+        Some("schema") => {
+            println!("{:#}", protocol::json_schema());
+            true
+        }
+        Some("typescript") => {
+            print!("{}", protocol::typescript());
+            true
+        }
+        _ => false,
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
+    if print_protocol() {
+        return Ok(());
+    }
+
     dotenv().ok();
     set_global_default(FmtSubscriber::default())?;
 
-    let app_state = Arc::new(types::AppState::default());
+    let config = match config::Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            error!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    if !config.static_dir.is_dir() {
+        warn!(
+            "Static directory {} does not exist, the frontend will not be served",
+            config.static_dir.display()
+        );
+    }
+
+    let (webhooks, webhook_worker) = webhooks::channel(&config.webhooks)?;
+    tokio::spawn(webhook_worker.run());
+
+    let integrations = integrations::Integrations::from_config(&config)?;
+
+    let login = auth::Login::from_config(&config)?;
+
+    let app_state = Arc::new(types::AppState::new(config, webhooks, integrations, login));
+
+    restore_state(&app_state).await;
+
     let (layer, io) = SocketIo::builder()
         .with_state(Arc::<types::AppState>::clone(&app_state))
         .build_layer();
 
-    io.ns("/", on_connect);
+    io.ns(
+        "/",
+        on_connect
+            .with(handlers::check_protocol)
+            .with(handlers::check_origin),
+    );
 
-    let static_service =
-        get_service(ServeDir::new("dist/assets")).layer(SetResponseHeaderLayer::overriding(
+    tokio::spawn(tasks::sweep_idle_rooms(
+        io.clone(),
+        Arc::<types::AppState>::clone(&app_state),
+    ));
+    tokio::spawn(tasks::reveal_after_grace(
+        io.clone(),
+        Arc::<types::AppState>::clone(&app_state),
+    ));
+
+    let config = &app_state.config;
+    let static_dir = &config.static_dir;
+    let index = static_dir.join("index.html");
+
+    let static_service = get_service(ServeDir::new(static_dir.join("assets"))).layer(
+        SetResponseHeaderLayer::overriding(
             header::CACHE_CONTROL,
             HeaderValue::from_static("public, max-age=31536000, immutable"),
-        ));
+        ),
+    );
 
     let app = Router::new()
-        .fallback_service(get_service(ServeFile::new("dist/index.html")))
-        .route("/", get_service(ServeFile::new("dist/index.html")))
+        .fallback_service(get_service(ServeFile::new(&index)))
+        .route("/", get_service(ServeFile::new(&index)))
         .route(
             "/robots.txt",
-            get_service(ServeFile::new("dist/robots.txt")),
+            get_service(ServeFile::new(static_dir.join("robots.txt"))),
         )
         .route(
             "/sitemap.xml",
-            get_service(ServeFile::new("dist/sitemap.xml")),
+            get_service(ServeFile::new(static_dir.join("sitemap.xml"))),
         )
         .nest_service("/assets", static_service.clone())
+        .merge(client_routes(&app_state, &io))
         .layer(
             ServiceBuilder::new()
-                .layer(CorsLayer::permissive())
+                .layer(cors_layer(&app_state))
                 .layer(layer)
                 .layer(TraceLayer::new_for_http())
                 .layer(from_fn(log_404)),
         )
         .layer(TraceLayer::new_for_http())
         .layer(from_fn(log_404))
-        .layer(from_fn(enforce_host));
+        .layer(from_fn_with_state(
+            Arc::<types::AppState>::clone(&app_state),
+            enforce_host,
+        ))
+        // probes come from inside the network so skip host enforcement,
+        // operator routes enforce it themselves
+        .merge(operator_routes(&app_state, &io));
 
-    info!("Starting server");
+    let addr = config.socket_addr();
+    info!("Starting server on {}", addr);
 
-    let listener = TcpListener::bind("0.0.0.0:3333").await?;
+    let listener = TcpListener::bind(addr).await?;
 
-    serve(listener, app).await?;
+    serve(listener, app)
+        .with_graceful_shutdown(tasks::graceful_shutdown(
+            io.clone(),
+            Arc::<types::AppState>::clone(&app_state),
+        ))
+        .await?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{HeaderValue, header::LOCATION};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::test_support::app_state;
+
+    /// Operator routes for a server only serving `storypoint.example`, with an admin token
+    fn routes() -> Router {
+        let config = config::Config {
+            allowed_hosts: vec!["storypoint.example".parse().expect("valid pattern")],
+            admin_token: Some("secret".to_owned()),
+            ..config::Config::default()
+        };
+        let (_, io) = SocketIo::new_layer();
+        operator_routes(&app_state(config), &io)
+    }
+
+    /// Sends a GET with the admin token to the routes, on the given host
+    async fn get_on(host: &str, path: &str) -> Response {
+        let req = Request::get(path)
+            .header(HOST, host)
+            .header("authorization", "Bearer secret")
+            .body(Body::empty())
+            .expect("request builds");
+        routes().oneshot(req).await.expect("routes are infallible")
+    }
+
+    #[tokio::test]
+    async fn operator_routes_are_only_served_on_allowed_hosts() {
+        for path in ["/admin/rooms", "/debug/rooms"] {
+            let response = get_on("attacker.example", path).await;
+            assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+            assert_eq!(
+                response.headers().get(LOCATION),
+                Some(
+                    &HeaderValue::from_str(&format!("http://storypoint.example{path}"))
+                        .expect("valid header")
+                )
+            );
+
+            let response = get_on("storypoint.example", path).await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+    }
+
+    #[tokio::test]
+    async fn health_probes_are_served_on_any_host() {
+        let response = get_on("10.0.0.12:3333", "/healthz").await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
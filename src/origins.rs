@@ -0,0 +1,245 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+
+use std::{error::Error, fmt, str::FromStr};
+
+/// `HostPattern` matches a request host name
+/// Either an exact host or `*.` followed by a domain
+/// which matches any subdomain of that domain
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HostPattern {
+    /// Matches only this host
+    Exact(String),
+    /// Matches any subdomain of this domain, but not the domain itself
+    Subdomains(String),
+}
+
+impl HostPattern {
+    /// Checks a host name (without a port) against the pattern
+    pub fn matches(&self, host: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+        match self {
+            Self::Exact(exact) => host == *exact,
+            Self::Subdomains(domain) => host
+                .strip_suffix(domain.as_str())
+                .and_then(|sub| sub.strip_suffix('.'))
+                .is_some_and(|sub| !sub.is_empty()),
+        }
+    }
+
+    /// The host to redirect to, only exact hosts can be redirected to
+    pub fn exact(&self) -> Option<&str> {
+        match self {
+            Self::Exact(host) => Some(host),
+            Self::Subdomains(_) => None,
+        }
+    }
+}
+
+impl FromStr for HostPattern {
+    type Err = PatternError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let pattern = s.trim().to_ascii_lowercase();
+        let (domain, wildcard) = pattern
+            .strip_prefix("*.")
+            .map_or((pattern.as_str(), false), |domain| (domain, true));
+
+        let valid = !domain.is_empty()
+            && domain
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.');
+        if !valid {
+            return Err(PatternError(s.to_owned()));
+        }
+
+        Ok(if wildcard {
+            Self::Subdomains(domain.to_owned())
+        } else {
+            Self::Exact(domain.to_owned())
+        })
+    }
+}
+
+impl fmt::Display for HostPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exact(host) => write!(f, "{host}"),
+            Self::Subdomains(domain) => write!(f, "*.{domain}"),
+        }
+    }
+}
+
+/// `OriginPattern` matches the `Origin` header of cross origin requests
+/// It is a scheme, a host pattern and an optional port
+/// e.g. `https://*.example.com` or `http://localhost:5173`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OriginPattern {
+    scheme: String,
+    host: HostPattern,
+    port: Option<u16>,
+}
+
+impl OriginPattern {
+    /// Checks an `Origin` header value against the pattern
+    pub fn matches(&self, origin: &str) -> bool {
+        parse_origin(origin).is_some_and(|(scheme, host, port)| {
+            scheme.eq_ignore_ascii_case(&self.scheme)
+                && port == self.port
+                && self.host.matches(host)
+        })
+    }
+}
+
+impl FromStr for OriginPattern {
+    type Err = PatternError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || PatternError(s.to_owned());
+        let (scheme, authority) = s.trim().split_once("://").ok_or_else(err)?;
+        if !matches!(scheme, "http" | "https") {
+            return Err(err());
+        }
+
+        let (host, port) = split_port(authority).ok_or_else(err)?;
+
+        Ok(Self {
+            scheme: scheme.to_owned(),
+            host: host.parse().map_err(|_| err())?,
+            port,
+        })
+    }
+}
+
+/// Splits an authority into its host and optional port
+/// Returns `None` if a port is present but is not a valid number
+fn split_port(authority: &str) -> Option<(&str, Option<u16>)> {
+    // bracketed IPv6 literals contain colons of their own
+    if authority.ends_with(']') {
+        return Some((authority, None));
+    }
+    match authority.rsplit_once(':') {
+        Some((host, port)) => port.parse().ok().map(|port| (host, Some(port))),
+        None => Some((authority, None)),
+    }
+}
+
+/// Splits an origin into its scheme, host and port
+fn parse_origin(origin: &str) -> Option<(&str, &str, Option<u16>)> {
+    let (scheme, authority) = origin.trim().split_once("://")?;
+    split_port(authority).map(|(host, port)| (scheme, host, port))
+}
+
+/// Extracts the host name from a `Host` header value, dropping any port
+pub fn host_name(host: &str) -> Option<&str> {
+    split_port(host.trim()).map(|(host, _)| host)
+}
+
+/// Extracts the host name from an `Origin` header value
+pub fn origin_host(origin: &str) -> Option<&str> {
+    parse_origin(origin).map(|(_, host, _)| host)
+}
+
+#[derive(Debug)]
+pub struct PatternError(String);
+
+impl Error for PatternError {}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid host or origin pattern: {}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host(pattern: &str) -> HostPattern {
+        pattern.parse().expect("valid host pattern")
+    }
+
+    fn origin(pattern: &str) -> OriginPattern {
+        pattern.parse().expect("valid origin pattern")
+    }
+
+    #[test]
+    fn exact_hosts_match_only_themselves() {
+        let pattern = host("Storypoint.Example");
+
+        assert_eq!(pattern.exact(), Some("storypoint.example"));
+        assert!(pattern.matches("storypoint.example"));
+        assert!(pattern.matches("STORYPOINT.example"));
+        assert!(!pattern.matches("www.storypoint.example"));
+        assert!(!pattern.matches("storypoint.example.attacker.test"));
+    }
+
+    #[test]
+    fn wildcard_hosts_match_subdomains_only() {
+        let pattern = host("*.preview.example");
+
+        assert_eq!(pattern.exact(), None);
+        assert_eq!(pattern.to_string(), "*.preview.example");
+        assert!(pattern.matches("pr-12.preview.example"));
+        assert!(pattern.matches("a.b.preview.example"));
+        assert!(!pattern.matches("preview.example"));
+        assert!(!pattern.matches("attackerpreview.example"));
+    }
+
+    #[test]
+    fn invalid_host_patterns_are_rejected() {
+        for pattern in [
+            "",
+            "*.",
+            "https://storypoint.example",
+            "storypoint.example/",
+            "*",
+        ] {
+            assert!(pattern.parse::<HostPattern>().is_err(), "{pattern}");
+        }
+    }
+
+    #[test]
+    fn origins_match_scheme_host_and_port() {
+        let pattern = origin("http://localhost:5173");
+
+        assert!(pattern.matches("http://localhost:5173"));
+        assert!(!pattern.matches("https://localhost:5173"));
+        assert!(!pattern.matches("http://localhost"));
+        assert!(!pattern.matches("http://localhost:5174"));
+    }
+
+    #[test]
+    fn wildcard_origins_match_subdomains() {
+        let pattern = origin("https://*.storypoint.example");
+
+        assert!(pattern.matches("https://team.storypoint.example"));
+        assert!(!pattern.matches("https://storypoint.example"));
+        assert!(!pattern.matches("http://team.storypoint.example"));
+        assert!(!pattern.matches("https://team.storypoint.example:8443"));
+    }
+
+    #[test]
+    fn invalid_origin_patterns_are_rejected() {
+        for pattern in [
+            "storypoint.example",
+            "ftp://storypoint.example",
+            "https://host:port",
+        ] {
+            assert!(pattern.parse::<OriginPattern>().is_err(), "{pattern}");
+        }
+    }
+
+    #[test]
+    fn host_names_drop_the_port() {
+        assert_eq!(
+            host_name("storypoint.example:3333"),
+            Some("storypoint.example")
+        );
+        assert_eq!(host_name("[::1]"), Some("[::1]"));
+        assert_eq!(host_name("storypoint.example:x"), None);
+        assert_eq!(
+            origin_host("https://storypoint.example:8443"),
+            Some("storypoint.example")
+        );
+    }
+}
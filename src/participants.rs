@@ -0,0 +1,173 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{auth::AuthUser, protocol::describe_struct};
+
+/// Session a client resumes when it connects
+/// Clients that send no token, or one the server does not know, start a new session
+#[derive(Debug, Default, Deserialize)]
+pub struct SessionAuth {
+    pub session: Option<String>,
+}
+
+/// Sent to a client when it connects, the token is only shared with that client
+#[derive(Clone, Debug, Serialize)]
+pub struct Session {
+    /// The ID the client is known by in rooms
+    pub participant_id: String,
+    /// Secret the client sends when reconnecting to keep its participant ID
+    pub token: String,
+}
+
+describe_struct!(Session {
+    participant_id,
+    token
+});
+
+/// A participant and the resume token for it
+#[derive(Debug)]
+struct Identity {
+    participant_id: String,
+    /// How many connections currently use this identity
+    connections: usize,
+    /// When the last connection using this identity closed
+    released_at: Option<Instant>,
+}
+
+/// A connected socket or WebSocket peer
+#[derive(Debug)]
+struct Connection {
+    participant_id: String,
+    token: String,
+    /// The user the connection logged in as, checked during its handshake
+    user: Option<AuthUser>,
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    /// Participant for each connected socket or WebSocket peer
+    connections: HashMap<String, Connection>,
+    /// Identities by resume token
+    identities: HashMap<String, Identity>,
+}
+
+/// `Participants` maps transport connections to the opaque participant IDs
+/// players are known by in rooms.
+/// Participant IDs survive reconnects, unlike socket IDs, as the client
+/// presents a resume token that is never broadcast.
+/// Disconnecting still leaves every room, a resumed participant
+/// rejoins its rooms under the same ID.
+#[derive(Debug, Default)]
+pub struct Participants {
+    registry: Mutex<Registry>,
+}
+
+impl Participants {
+    /// Starts a session for a new connection.
+    /// - Resumes the participant for a known token that no other connection is using.
+    /// - Otherwise creates a new participant and token.
+    /// - Remembers the user the connection logged in as, if any.
+    pub fn connect(
+        &self,
+        connection_id: &str,
+        token: Option<&str>,
+        user: Option<AuthUser>,
+    ) -> Session {
+        let mut registry = self.registry.lock().expect("participants lock poisoned");
+        let resumable = token.filter(|token| {
+            registry
+                .identities
+                .get(*token)
+                .is_some_and(|identity| identity.connections == 0)
+        });
+
+        let token = resumable.map_or_else(|| Uuid::new_v4().simple().to_string(), str::to_owned);
+        let identity = registry
+            .identities
+            .entry(token.clone())
+            .or_insert_with(|| Identity {
+                participant_id: Uuid::new_v4().simple().to_string(),
+                connections: 0,
+                released_at: None,
+            });
+        identity.connections += 1;
+        identity.released_at = None;
+        let participant_id = identity.participant_id.clone();
+
+        registry.connections.insert(
+            connection_id.to_owned(),
+            Connection {
+                participant_id: participant_id.clone(),
+                token: token.clone(),
+                user,
+            },
+        );
+        drop(registry);
+        Session {
+            participant_id,
+            token,
+        }
+    }
+
+    /// The participant a connection is acting as, a connection
+    /// that never started a session gets a new one
+    pub fn participant_id(&self, connection_id: &str) -> String {
+        let known = self
+            .registry
+            .lock()
+            .expect("participants lock poisoned")
+            .connections
+            .get(connection_id)
+            .map(|connection| connection.participant_id.clone());
+        known.unwrap_or_else(|| self.connect(connection_id, None, None).participant_id)
+    }
+
+    /// The user a connection logged in as, if any
+    pub fn user(&self, connection_id: &str) -> Option<AuthUser> {
+        self.registry
+            .lock()
+            .expect("participants lock poisoned")
+            .connections
+            .get(connection_id)
+            .and_then(|connection| connection.user.clone())
+    }
+
+    /// Ends a connection's session, its token can be resumed until pruned
+    pub fn disconnect(&self, connection_id: &str) {
+        let mut registry = self.registry.lock().expect("participants lock poisoned");
+        let Some(connection) = registry.connections.remove(connection_id) else {
+            return;
+        };
+        if let Some(identity) = registry.identities.get_mut(&connection.token) {
+            identity.connections = identity.connections.saturating_sub(1);
+            if identity.connections == 0 {
+                identity.released_at = Some(Instant::now());
+            }
+        }
+    }
+
+    /// Forgets tokens that have not been used for longer than `ttl`
+    pub fn prune(&self, ttl: Duration) {
+        let mut registry = self.registry.lock().expect("participants lock poisoned");
+        let before = registry.identities.len();
+        registry.identities.retain(|_, identity| {
+            identity
+                .released_at
+                .is_none_or(|released_at| released_at.elapsed() < ttl)
+        });
+        let pruned = before - registry.identities.len();
+        drop(registry);
+        if pruned > 0 {
+            info!("Forgot {} expired participant sessions", pruned);
+        }
+    }
+}
@@ -0,0 +1,106 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    protocol::{describe_enum, describe_struct},
+    types::ActionRejected,
+};
+
+/// Number of player colours the frontend has, numbered from 1
+const PLAYER_COLORS: u8 = 6;
+
+/// Most characters an avatar emoji may use, enough for joined sequences
+/// such as flags and skin tones but not for text
+const MAX_EMOJI_CHARS: usize = 8;
+
+/// What a player does in the team, shown next to their name
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum PlayerRole {
+    /// Developer
+    Dev,
+    /// Quality assurance
+    Qa,
+    /// Designer
+    Design,
+}
+
+describe_enum!(PlayerRole { Dev, Qa, Design });
+
+/// `Profile` is how a player appears in a room besides their name,
+/// changed by the player with "updateProfile"
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct Profile {
+    /// Emoji shown as the player's avatar
+    pub emoji: Option<String>,
+    /// Which of the frontend's player colours to use
+    pub color: Option<u8>,
+    /// The player's role tag
+    pub role: Option<PlayerRole>,
+}
+
+describe_struct!(Profile { emoji, color, role });
+
+impl Profile {
+    /// Checks the profile is one the frontend can show
+    pub fn validate(&self) -> Result<(), ActionRejected> {
+        let invalid = |message: String| Err(ActionRejected::InvalidProfile { message });
+
+        if let Some(emoji) = &self.emoji
+            && (emoji.is_empty()
+                || emoji.chars().count() > MAX_EMOJI_CHARS
+                || emoji.chars().any(|c| c.is_ascii() || c.is_whitespace()))
+        {
+            return invalid("Avatar must be a single emoji".to_owned());
+        }
+        if let Some(color) = self.color
+            && !(1..=PLAYER_COLORS).contains(&color)
+        {
+            return invalid(format!("Colour must be between 1 and {PLAYER_COLORS}"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A profile with only an emoji
+    fn emoji(emoji: &str) -> Profile {
+        Profile {
+            emoji: Some(emoji.to_owned()),
+            ..Profile::default()
+        }
+    }
+
+    #[test]
+    fn validate_accepts_emoji_sequences() {
+        for avatar in ["🦊", "👩🏽‍💻", "🇳🇿"] {
+            assert!(emoji(avatar).validate().is_ok(), "{avatar}");
+        }
+        assert!(Profile::default().validate().is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_text_avatars() {
+        for avatar in ["", "A", "🦊 fox", "🦊🦊🦊🦊🦊🦊🦊🦊🦊"] {
+            assert!(emoji(avatar).validate().is_err(), "{avatar}");
+        }
+    }
+
+    #[test]
+    fn validate_accepts_only_the_frontend_colours() {
+        let color = |color| Profile {
+            color: Some(color),
+            ..Profile::default()
+        };
+
+        assert!(color(1).validate().is_ok());
+        assert!(color(PLAYER_COLORS).validate().is_ok());
+        assert!(color(0).validate().is_err());
+        assert!(color(PLAYER_COLORS + 1).validate().is_err());
+    }
+}
@@ -0,0 +1,666 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fmt::{self, Write},
+};
+
+use axum::Json;
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+use uuid::Uuid;
+
+use crate::types::{SocketEvent, client_events, server_events};
+
+/// Version of the event protocol spoken by this server.
+/// Bumped whenever an event is removed or a payload changes incompatibly.
+pub const PROTOCOL_VERSION: u32 = 3;
+
+/// Oldest protocol version clients may still connect with
+pub const MIN_PROTOCOL_VERSION: u32 = 3;
+
+/// Version a client asks for when connecting
+/// Clients that predate versioning send nothing and are treated as version 1
+#[derive(Debug, Deserialize)]
+pub struct ProtocolAuth {
+    #[serde(default = "first_version")]
+    pub protocol: u32,
+}
+
+const fn first_version() -> u32 {
+    1
+}
+
+/// Error returned to clients whose protocol version cannot be accepted
+#[derive(Debug)]
+pub enum ProtocolError {
+    /// The client speaks a version this server does not support
+    Unsupported(u32),
+    /// The client's handshake did not carry a readable version
+    Malformed(String),
+}
+
+impl Error for ProtocolError {}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported(version) => write!(
+                f,
+                "Unsupported protocol version {version}, server supports {}",
+                supported_versions()
+            ),
+            Self::Malformed(message) => write!(f, "Invalid protocol version: {message}"),
+        }
+    }
+}
+
+/// The range of versions accepted, as shown to clients
+fn supported_versions() -> String {
+    if MIN_PROTOCOL_VERSION == PROTOCOL_VERSION {
+        PROTOCOL_VERSION.to_string()
+    } else {
+        format!("{MIN_PROTOCOL_VERSION} to {PROTOCOL_VERSION}")
+    }
+}
+
+/// Checks a client's requested protocol version is supported
+pub const fn check_version(version: u32) -> Result<(), ProtocolError> {
+    if version >= MIN_PROTOCOL_VERSION && version <= PROTOCOL_VERSION {
+        Ok(())
+    } else {
+        Err(ProtocolError::Unsupported(version))
+    }
+}
+
+/// `Shape` describes how a type is serialized on the wire,
+/// enough to render it as JSON Schema or TypeScript
+#[derive(Clone, Debug)]
+pub enum Shape {
+    Null,
+    Boolean,
+    Integer,
+    Number,
+    String,
+    Uuid,
+    Nullable(Box<Self>),
+    Array(Box<Self>),
+    /// Object keyed by arbitrary strings
+    Map(Box<Self>),
+    Object(Vec<Field>),
+    /// Internally tagged enum, each variant's fields sit next to the tag
+    Union {
+        tag: &'static str,
+        variants: Vec<(String, Vec<Field>)>,
+    },
+    /// Enum of unit variants serialized as strings
+    Enum(Vec<String>),
+    /// A type rendered once as a definition and referenced by name
+    Named(&'static str, Box<Self>),
+}
+
+/// A field of an object or tagged enum variant
+#[derive(Clone, Debug)]
+pub struct Field {
+    pub name: &'static str,
+    pub shape: Shape,
+    /// Whether the field must be present, inbound `Option` fields may be left out
+    pub required: bool,
+}
+
+/// Types that can describe their wire format
+pub trait Describe {
+    /// Whether the type may be omitted from an inbound payload
+    const OPTIONAL: bool = false;
+
+    fn shape() -> Shape;
+}
+
+/// Builds a field description, the accessor only ties the field's type to `T`.
+/// Inbound fields that are `Option`s or have a serde default may be left out.
+pub fn field<S, T: Describe>(
+    name: &'static str,
+    inbound: bool,
+    defaulted: bool,
+    _: fn(&S) -> &T,
+) -> Field {
+    Field {
+        name,
+        shape: T::shape(),
+        required: !(inbound && (T::OPTIONAL || defaulted)),
+    }
+}
+
+/// Converts a Rust variant name to the camelCase serde uses
+pub fn camel_case(name: &str) -> String {
+    let mut chars = name.chars();
+    chars.next().map_or_else(String::new, |first| {
+        first.to_lowercase().chain(chars).collect()
+    })
+}
+
+macro_rules! describe_primitive {
+    ($shape:ident: $($ty:ty),*) => {
+        $(impl Describe for $ty {
+            fn shape() -> Shape {
+                Shape::$shape
+            }
+        })*
+    };
+}
+
+describe_primitive!(Boolean: bool);
+describe_primitive!(Integer: u8, u32, u64, usize);
+describe_primitive!(Number: f64);
+describe_primitive!(String: String);
+describe_primitive!(Uuid: Uuid);
+describe_primitive!(Null: ());
+
+impl<T: Describe> Describe for Option<T> {
+    const OPTIONAL: bool = true;
+
+    fn shape() -> Shape {
+        Shape::Nullable(Box::new(T::shape()))
+    }
+}
+
+impl<T: Describe> Describe for Vec<T> {
+    fn shape() -> Shape {
+        Shape::Array(Box::new(T::shape()))
+    }
+}
+
+impl<T: Describe, S> Describe for HashMap<String, T, S> {
+    fn shape() -> Shape {
+        Shape::Map(Box::new(T::shape()))
+    }
+}
+
+/// Maps keyed by card value, JSON object keys are always strings
+impl<T: Describe> Describe for BTreeMap<u8, T> {
+    fn shape() -> Shape {
+        Shape::Map(Box::new(T::shape()))
+    }
+}
+
+/// Implements `Describe` for a struct serialized as an object.
+/// - Prefix with `inbound` for payloads sent by clients, their `Option` fields may be omitted.
+/// - Mark inbound fields with a serde default as `field = default` so they may be omitted too.
+/// - Fields not sent over the wire are listed under `skip`.
+/// - Fails to compile if a field is neither described nor skipped.
+macro_rules! describe_struct {
+    (inbound $ty:ident { $($field:ident $(= $default:ident)?),* $(,)? } $(skip { $($skipped:ident),* $(,)? })?) => {
+        $crate::protocol::describe_struct!(@impl true, $ty { $($field $(= $default)?),* } $(skip { $($skipped),* })?);
+    };
+    ($ty:ident { $($field:ident),* $(,)? } $(skip { $($skipped:ident),* $(,)? })?) => {
+        $crate::protocol::describe_struct!(@impl false, $ty { $($field),* } $(skip { $($skipped),* })?);
+    };
+    (@defaulted default) => {
+        true
+    };
+    (@defaulted) => {
+        false
+    };
+    (@impl $inbound:literal, $ty:ident { $($field:ident $(= $default:ident)?),* } $(skip { $($skipped:ident),* })?) => {
+        impl $crate::protocol::Describe for $ty {
+            fn shape() -> $crate::protocol::Shape {
+                #[allow(dead_code)]
+                const fn exhaustive(value: &$ty) {
+                    let $ty { $($field: _,)* $($($skipped: _,)*)? } = value;
+                }
+
+                $crate::protocol::Shape::Named(
+                    stringify!($ty),
+                    Box::new($crate::protocol::Shape::Object(vec![$(
+                        $crate::protocol::field(
+                            stringify!($field),
+                            $inbound,
+                            $crate::protocol::describe_struct!(@defaulted $($default)?),
+                            |value: &$ty| &value.$field,
+                        )
+                    ),*])),
+                )
+            }
+        }
+    };
+}
+
+/// Implements `Describe` for an internally tagged enum with struct variants.
+/// - Variants are named in camelCase unless given an explicit `= "name"`.
+/// - Fails to compile if a variant or field is missing.
+macro_rules! describe_tagged {
+    ($ty:ident, $tag:literal { $($variant:ident $(= $rename:literal)? { $($field:ident),* $(,)? }),* $(,)? }) => {
+        #[allow(unreachable_patterns, clippy::match_wildcard_for_single_variants)]
+        impl $crate::protocol::Describe for $ty {
+            fn shape() -> $crate::protocol::Shape {
+                #[allow(dead_code)]
+                const fn exhaustive(value: &$ty) {
+                    match value {
+                        $($ty::$variant { $($field: _),* } => {})*
+                    }
+                }
+
+                $crate::protocol::Shape::Named(
+                    stringify!($ty),
+                    Box::new($crate::protocol::Shape::Union {
+                        tag: $tag,
+                        variants: vec![$((
+                            $crate::protocol::describe_tagged!(@name $variant $($rename)?),
+                            vec![$(
+                                $crate::protocol::field(stringify!($field), false, false, |value: &$ty| match value {
+                                    $ty::$variant { $field, .. } => $field,
+                                    _ => unreachable!(),
+                                })
+                            ),*],
+                        )),*],
+                    }),
+                )
+            }
+        }
+    };
+    (@name $variant:ident $rename:literal) => {
+        $rename.to_owned()
+    };
+    (@name $variant:ident) => {
+        $crate::protocol::camel_case(stringify!($variant))
+    };
+}
+
+/// Implements `Describe` for an enum of unit variants serialized as camelCase strings
+macro_rules! describe_enum {
+    ($ty:ident { $($variant:ident),* $(,)? }) => {
+        impl $crate::protocol::Describe for $ty {
+            fn shape() -> $crate::protocol::Shape {
+                #[allow(dead_code)]
+                const fn exhaustive(value: &$ty) {
+                    match value {
+                        $($ty::$variant => {})*
+                    }
+                }
+
+                $crate::protocol::Shape::Named(
+                    stringify!($ty),
+                    Box::new($crate::protocol::Shape::Enum(vec![
+                        $($crate::protocol::camel_case(stringify!($variant))),*
+                    ])),
+                )
+            }
+        }
+    };
+}
+
+/// Declares the events the server emits.
+/// - Defines each event's marker type and its `SocketEvent` implementation.
+/// - Defines `server_events`, listing every event for the protocol schema.
+macro_rules! declare_server_events {
+    ($($event:ident = $name:literal => $data:ty;)*) => {
+        $(
+            pub struct $event;
+            impl SocketEvent for $event {
+                const EVENT: &'static str = $name;
+                type Data = $data;
+            }
+        )*
+
+        /// Every event the server emits, for the protocol schema
+        pub fn server_events() -> Vec<$crate::protocol::EventSchema> {
+            vec![$($crate::protocol::EventSchema::server::<$event>()),*]
+        }
+    };
+}
+
+/// Declares the events clients send and the payload each carries.
+/// - Implements `ClientEvent` for each payload type.
+/// - Defines `client_events`, listing every event for the protocol schema.
+macro_rules! declare_client_events {
+    ($($payload:ident = $name:literal;)*) => {
+        $(
+            impl $crate::protocol::ClientEvent for $payload {
+                const EVENT: &'static str = $name;
+            }
+        )*
+
+        /// Every event clients send, for the protocol schema
+        pub fn client_events() -> Vec<$crate::protocol::EventSchema> {
+            vec![$($crate::protocol::EventSchema::client::<$payload>()),*]
+        }
+    };
+}
+
+pub(crate) use {
+    declare_client_events, declare_server_events, describe_enum, describe_struct, describe_tagged,
+};
+
+/// An event name and the shape of its payload
+#[derive(Debug)]
+pub struct EventSchema {
+    pub name: &'static str,
+    pub shape: Shape,
+}
+
+impl EventSchema {
+    /// Describes an event the server emits
+    pub fn server<E: SocketEvent>() -> Self {
+        Self {
+            name: E::EVENT,
+            shape: E::Data::shape(),
+        }
+    }
+
+    /// Describes an event clients send
+    pub fn client<E: ClientEvent>() -> Self {
+        Self {
+            name: E::EVENT,
+            shape: E::shape(),
+        }
+    }
+}
+
+/// Payloads clients send, named after the event they are sent with
+pub trait ClientEvent: Describe {
+    const EVENT: &'static str;
+}
+
+/// Serves the protocol version and schema so clients can check compatibility
+pub async fn describe() -> Json<Value> {
+    Json(json_schema())
+}
+
+/// Renders the protocol as a JSON Schema document.
+/// - Every named type is a definition under `$defs`.
+/// - `events.server` and `events.client` map event names to their payload.
+pub fn json_schema() -> Value {
+    let mut defs = BTreeMap::new();
+    let mut events = |list: Vec<EventSchema>| {
+        list.into_iter()
+            .map(|event| (event.name.to_owned(), schema_of(&event.shape, &mut defs)))
+            .collect::<Map<_, _>>()
+    };
+    let server = events(server_events());
+    let client = events(client_events());
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": concat!(env!("CARGO_PKG_NAME"), " protocol"),
+        "version": PROTOCOL_VERSION,
+        "minVersion": MIN_PROTOCOL_VERSION,
+        "events": {
+            "server": server,
+            "client": client,
+        },
+        "$defs": defs,
+    })
+}
+
+/// Renders a shape as a schema, registering named types as definitions
+fn schema_of(shape: &Shape, defs: &mut BTreeMap<&'static str, Value>) -> Value {
+    match shape {
+        Shape::Null => json!({ "type": "null" }),
+        Shape::Boolean => json!({ "type": "boolean" }),
+        Shape::Integer => json!({ "type": "integer", "minimum": 0 }),
+        Shape::Number => json!({ "type": "number" }),
+        Shape::String => json!({ "type": "string" }),
+        Shape::Uuid => json!({ "type": "string", "format": "uuid" }),
+        Shape::Nullable(inner) => json!({ "anyOf": [schema_of(inner, defs), { "type": "null" }] }),
+        Shape::Array(inner) => json!({ "type": "array", "items": schema_of(inner, defs) }),
+        Shape::Map(inner) => {
+            json!({ "type": "object", "additionalProperties": schema_of(inner, defs) })
+        }
+        Shape::Object(fields) => object_schema(None, fields, defs),
+        Shape::Union { tag, variants } => {
+            let variants = variants
+                .iter()
+                .map(|(name, fields)| object_schema(Some((tag, name)), fields, defs))
+                .collect::<Vec<_>>();
+            json!({ "oneOf": variants })
+        }
+        Shape::Enum(values) => json!({ "type": "string", "enum": values }),
+        Shape::Named(name, inner) => {
+            if !defs.contains_key(name) {
+                // reserve the name first so recursive types terminate
+                defs.insert(name, Value::Null);
+                let schema = schema_of(inner, defs);
+                defs.insert(name, schema);
+            }
+            json!({ "$ref": format!("#/$defs/{name}") })
+        }
+    }
+}
+
+/// Renders an object, with its enum tag if it is a tagged variant
+fn object_schema(
+    tag: Option<(&str, &str)>,
+    fields: &[Field],
+    defs: &mut BTreeMap<&'static str, Value>,
+) -> Value {
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+    if let Some((tag, value)) = tag {
+        properties.insert(tag.to_owned(), json!({ "const": value }));
+        required.push(tag.to_owned());
+    }
+    for field in fields {
+        properties.insert(field.name.to_owned(), schema_of(&field.shape, defs));
+        if field.required {
+            required.push(field.name.to_owned());
+        }
+    }
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+    })
+}
+
+/// Renders the protocol as TypeScript definitions for the frontend.
+/// - Every named type is exported as an interface or type alias.
+/// - `ServerToClientEvents` and `ClientToServerEvents` type a Socket.IO client.
+pub fn typescript() -> String {
+    let mut defs = BTreeMap::new();
+    let server = event_signatures(&server_events(), None, &mut defs);
+    let client = event_signatures(&client_events(), Some("payload"), &mut defs);
+
+    let mut out = format!(
+        "// Generated by `cargo run -- typescript`, do not edit by hand.\n\n\
+         export const PROTOCOL_VERSION = {PROTOCOL_VERSION};\n"
+    );
+    for definition in defs.values() {
+        out.push('\n');
+        out.push_str(definition);
+    }
+    for (name, signatures) in [
+        ("ServerToClientEvents", server),
+        ("ClientToServerEvents", client),
+    ] {
+        let _ = write!(out, "\nexport interface {name} {{\n{signatures}}}\n");
+    }
+    out
+}
+
+/// Renders each event as a listener signature,
+/// the parameter is named after the payload's type unless a name is given
+fn event_signatures(
+    events: &[EventSchema],
+    param: Option<&str>,
+    defs: &mut BTreeMap<&'static str, String>,
+) -> String {
+    let mut out = String::new();
+    for event in events {
+        let param = match (param, &event.shape) {
+            (Some(param), _) => param.to_owned(),
+            (None, Shape::Named(name, _)) => camel_case(name),
+            (None, _) => "data".to_owned(),
+        };
+        let signature = match &event.shape {
+            Shape::Null => "()".to_owned(),
+            shape => format!("({param}: {})", ts_of(shape, defs)),
+        };
+        let _ = writeln!(out, "  {}: {signature} => void;", event.name);
+    }
+    out
+}
+
+/// Renders a shape as a TypeScript type, registering named types as definitions
+fn ts_of(shape: &Shape, defs: &mut BTreeMap<&'static str, String>) -> String {
+    match shape {
+        Shape::Null => "null".to_owned(),
+        Shape::Boolean => "boolean".to_owned(),
+        Shape::Integer | Shape::Number => "number".to_owned(),
+        Shape::String | Shape::Uuid => "string".to_owned(),
+        Shape::Nullable(inner) => format!("{} | null", ts_of(inner, defs)),
+        Shape::Array(inner) => match **inner {
+            Shape::Nullable(_) => format!("({})[]", ts_of(inner, defs)),
+            _ => format!("{}[]", ts_of(inner, defs)),
+        },
+        Shape::Map(inner) => format!("{{ [key: string]: {} }}", ts_of(inner, defs)),
+        Shape::Object(fields) => ts_object(None, fields, defs),
+        Shape::Union { tag, variants } => variants
+            .iter()
+            .map(|(name, fields)| ts_object(Some((tag, name)), fields, defs))
+            .collect::<Vec<_>>()
+            .join(" | "),
+        Shape::Enum(values) => values
+            .iter()
+            .map(|value| format!("'{value}'"))
+            .collect::<Vec<_>>()
+            .join(" | "),
+        Shape::Named(name, inner) => {
+            if !defs.contains_key(name) {
+                defs.insert(name, String::new());
+                let definition = ts_definition(name, inner, defs);
+                defs.insert(name, definition);
+            }
+            (*name).to_owned()
+        }
+    }
+}
+
+/// Renders a named type as an exported interface or type alias
+fn ts_definition(name: &str, shape: &Shape, defs: &mut BTreeMap<&'static str, String>) -> String {
+    match shape {
+        Shape::Object(fields) => {
+            let mut out = format!("export interface {name} {{\n");
+            for field in fields {
+                let optional = if field.required { "" } else { "?" };
+                let _ = writeln!(
+                    out,
+                    "  {}{optional}: {};",
+                    field.name,
+                    ts_of(&field.shape, defs)
+                );
+            }
+            out.push_str("}\n");
+            out
+        }
+        Shape::Union { tag, variants } => {
+            let mut out = format!("export type {name} =");
+            for (variant, fields) in variants {
+                let _ = write!(
+                    out,
+                    "\n  | {}",
+                    ts_object(Some((tag, variant)), fields, defs)
+                );
+            }
+            out.push_str(";\n");
+            out
+        }
+        shape => format!("export type {name} = {};\n", ts_of(shape, defs)),
+    }
+}
+
+/// Renders an inline object type, with its enum tag if it is a tagged variant
+fn ts_object(
+    tag: Option<(&str, &str)>,
+    fields: &[Field],
+    defs: &mut BTreeMap<&'static str, String>,
+) -> String {
+    let mut members = Vec::new();
+    if let Some((tag, value)) = tag {
+        members.push(format!("{tag}: '{value}'"));
+    }
+    for field in fields {
+        let optional = if field.required { "" } else { "?" };
+        members.push(format!(
+            "{}{optional}: {}",
+            field.name,
+            ts_of(&field.shape, defs)
+        ));
+    }
+    format!("{{ {} }}", members.join("; "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_version_accepts_only_supported_versions() {
+        for version in MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION {
+            assert!(check_version(version).is_ok());
+        }
+        assert!(matches!(
+            check_version(PROTOCOL_VERSION + 1),
+            Err(ProtocolError::Unsupported(_))
+        ));
+        let unversioned: ProtocolAuth = serde_json::from_value(json!({})).expect("auth parses");
+        assert_eq!(unversioned.protocol, 1);
+        assert!(check_version(unversioned.protocol).is_err());
+    }
+
+    #[test]
+    fn json_schema_describes_events_and_payloads() {
+        let schema = json_schema();
+
+        assert_eq!(schema["version"], PROTOCOL_VERSION);
+        assert_eq!(schema["minVersion"], MIN_PROTOCOL_VERSION);
+        assert_eq!(
+            schema["events"]["client"]["vote"],
+            json!({ "$ref": "#/$defs/VoteEvent" })
+        );
+        assert_eq!(
+            schema["$defs"]["VoteEvent"]["required"],
+            json!(["room_id", "vote"])
+        );
+        // inbound options may be left out
+        assert_eq!(
+            schema["$defs"]["AcceptEstimateEvent"]["required"],
+            json!(["room_id", "estimate"])
+        );
+        let rejection = &schema["$defs"]["ActionRejected"]["oneOf"][0];
+        assert_eq!(
+            rejection["properties"]["reason"],
+            json!({ "const": "tooManyRooms" })
+        );
+        assert_eq!(rejection["required"], json!(["reason", "max"]));
+    }
+
+    #[test]
+    fn json_schema_defines_every_referenced_type() {
+        let schema = json_schema().to_string();
+        let defs = json_schema()["$defs"].clone();
+
+        for reference in schema.split("\"#/$defs/").skip(1) {
+            let name = reference.split('"').next().unwrap_or_default();
+            assert!(
+                defs.get(name).is_some_and(Value::is_object),
+                "{name} is defined"
+            );
+        }
+    }
+
+    #[test]
+    fn typescript_matches_the_frontend_definitions() {
+        let typescript = typescript();
+
+        assert!(typescript.contains(&format!(
+            "export const PROTOCOL_VERSION = {PROTOCOL_VERSION};"
+        )));
+        assert!(typescript.contains("  vote: (payload: VoteEvent) => void;"));
+        assert_eq!(
+            typescript.trim_end(),
+            include_str!("../frontend/src/protocol.ts").trim_end(),
+            "run `cargo run -- typescript > frontend/src/protocol.ts`"
+        );
+    }
+}
@@ -0,0 +1,281 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    protocol::{describe_struct, describe_tagged},
+    types::{ActionRejected, NUMERIC_CARD_SET},
+};
+
+/// Card sets the frontend can show
+pub const CARD_SETS: [&str; 2] = [NUMERIC_CARD_SET, "tshirt"];
+
+/// Longest round timer a room can default to, one hour
+const MAX_TIMER_SECS: u32 = 60 * 60;
+
+/// Longest grace period before an automatic reveal, five minutes
+const MAX_GRACE_SECS: u32 = 5 * 60;
+
+/// `AutoReveal` is what happens once every player has voted
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(tag = "mode", rename_all = "camelCase")]
+pub enum AutoReveal {
+    /// The cards are revealed straight away
+    Immediate,
+    /// The cards are revealed after a grace period, unless a voter
+    /// joins, a vote is retracted or the votes are reset in the meantime
+    AfterGrace { grace_secs: u32 },
+    /// The cards wait for the host, who is sent "allVoted"
+    Manual,
+}
+
+describe_tagged!(AutoReveal, "mode" {
+    Immediate {},
+    AfterGrace { grace_secs },
+    Manual {},
+});
+
+/// `RevealQuorum` is how many votes must be in before the host can reveal the cards
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(tag = "rule", rename_all = "camelCase")]
+pub enum RevealQuorum {
+    /// At least this many players have voted, or all of them in smaller rooms
+    MinVotes { votes: u32 },
+    /// At least this percentage of the players who are not spectators have voted
+    MinPercent { percent: u8 },
+    /// The host can reveal at any time, even before anyone has voted
+    HostOverride,
+}
+
+describe_tagged!(RevealQuorum, "rule" {
+    MinVotes { votes },
+    MinPercent { percent },
+    HostOverride {},
+});
+
+impl RevealQuorum {
+    /// The number of votes needed in a room with this many voters,
+    /// a room without voters needs none
+    pub fn required(self, voters: usize) -> usize {
+        let required = match self {
+            Self::MinVotes { votes } => usize::try_from(votes).unwrap_or(usize::MAX),
+            Self::MinPercent { percent } => (voters * usize::from(percent)).div_ceil(100),
+            Self::HostOverride => return 0,
+        };
+        let required = required.min(voters);
+        if voters > 0 {
+            required.max(1)
+        } else {
+            required
+        }
+    }
+}
+
+/// `RoomSettings` holds how a room plays, chosen when the room is
+/// created and changed by the host with "updateSettings"
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+#[allow(
+    clippy::struct_excessive_bools,
+    reason = "each flag is an independent switch the host toggles"
+)]
+pub struct RoomSettings {
+    /// Which card set is being used in the frontend
+    pub card_set: String,
+    /// What happens once every player has voted
+    pub auto_reveal: AutoReveal,
+    /// How many votes must be in before the host can reveal the cards
+    pub reveal_quorum: RevealQuorum,
+    /// Whether players may join as spectators
+    pub allow_spectators: bool,
+    /// Whether players may still vote once the cards are revealed
+    pub allow_vote_change_after_reveal: bool,
+    /// Whether revealed votes are shown only as counts per card,
+    /// without the names of who cast them
+    pub anonymous: bool,
+    /// Whether webhooks and chat still name who cast each vote in anonymous rooms
+    pub keep_attribution: bool,
+    /// Length of the round timer the frontend starts with, if any
+    pub timer_secs: Option<u32>,
+}
+
+describe_struct!(RoomSettings {
+    card_set,
+    auto_reveal,
+    reveal_quorum,
+    allow_spectators,
+    allow_vote_change_after_reveal,
+    anonymous,
+    keep_attribution,
+    timer_secs,
+});
+
+impl Default for RoomSettings {
+    fn default() -> Self {
+        Self {
+            card_set: NUMERIC_CARD_SET.to_owned(),
+            auto_reveal: AutoReveal::Immediate,
+            reveal_quorum: RevealQuorum::MinVotes { votes: 1 },
+            allow_spectators: true,
+            allow_vote_change_after_reveal: false,
+            anonymous: false,
+            keep_attribution: false,
+            timer_secs: None,
+        }
+    }
+}
+
+impl RoomSettings {
+    /// Whether records of the room's rounds may name who cast each vote
+    pub const fn attributed(&self) -> bool {
+        !self.anonymous || self.keep_attribution
+    }
+
+    /// Default settings using the given card set
+    pub fn with_card_set(card_set: String) -> Self {
+        Self {
+            card_set,
+            ..Self::default()
+        }
+    }
+
+    /// Checks the settings are ones the frontend can play with
+    pub fn validate(&self) -> Result<(), ActionRejected> {
+        let invalid = |message: String| Err(ActionRejected::InvalidSettings { message });
+
+        if !CARD_SETS.contains(&self.card_set.as_str()) {
+            return invalid(format!(
+                "Unknown card set {}, expected one of {}",
+                self.card_set,
+                CARD_SETS.join(", ")
+            ));
+        }
+        if let Some(timer_secs) = self.timer_secs
+            && !(1..=MAX_TIMER_SECS).contains(&timer_secs)
+        {
+            return invalid(format!(
+                "Timer must be between 1 and {MAX_TIMER_SECS} seconds"
+            ));
+        }
+        if let AutoReveal::AfterGrace { grace_secs } = self.auto_reveal
+            && !(1..=MAX_GRACE_SECS).contains(&grace_secs)
+        {
+            return invalid(format!(
+                "Grace period must be between 1 and {MAX_GRACE_SECS} seconds"
+            ));
+        }
+        match self.reveal_quorum {
+            RevealQuorum::MinVotes { votes: 0 } => {
+                return invalid("Quorum must be at least 1 vote".to_owned());
+            }
+            RevealQuorum::MinPercent { percent } if !(1..=100).contains(&percent) => {
+                return invalid("Quorum must be between 1 and 100 percent".to_owned());
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn min_votes_caps_at_the_voters() {
+        let quorum = RevealQuorum::MinVotes { votes: 3 };
+
+        assert_eq!(quorum.required(0), 0);
+        assert_eq!(quorum.required(1), 1);
+        assert_eq!(quorum.required(5), 3);
+    }
+
+    #[test]
+    fn min_percent_rounds_up_to_at_least_one_vote() {
+        let quorum = RevealQuorum::MinPercent { percent: 50 };
+
+        assert_eq!(quorum.required(0), 0);
+        assert_eq!(quorum.required(1), 1);
+        assert_eq!(quorum.required(5), 3);
+        assert_eq!(RevealQuorum::MinPercent { percent: 1 }.required(5), 1);
+    }
+
+    #[test]
+    fn host_override_needs_no_votes() {
+        for voters in [0, 1, 5] {
+            assert_eq!(RevealQuorum::HostOverride.required(voters), 0);
+        }
+    }
+
+    #[test]
+    fn validate_accepts_the_defaults_and_known_card_sets() {
+        assert!(RoomSettings::default().validate().is_ok());
+        assert!(
+            RoomSettings::with_card_set("tshirt".to_owned())
+                .validate()
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn validate_rejects_settings_the_frontend_cannot_play() {
+        let invalid = [
+            RoomSettings::with_card_set("emoji".to_owned()),
+            RoomSettings {
+                timer_secs: Some(0),
+                ..RoomSettings::default()
+            },
+            RoomSettings {
+                timer_secs: Some(MAX_TIMER_SECS + 1),
+                ..RoomSettings::default()
+            },
+            RoomSettings {
+                auto_reveal: AutoReveal::AfterGrace { grace_secs: 0 },
+                ..RoomSettings::default()
+            },
+            RoomSettings {
+                auto_reveal: AutoReveal::AfterGrace {
+                    grace_secs: MAX_GRACE_SECS + 1,
+                },
+                ..RoomSettings::default()
+            },
+            RoomSettings {
+                reveal_quorum: RevealQuorum::MinVotes { votes: 0 },
+                ..RoomSettings::default()
+            },
+            RoomSettings {
+                reveal_quorum: RevealQuorum::MinPercent { percent: 101 },
+                ..RoomSettings::default()
+            },
+        ];
+
+        for settings in invalid {
+            assert!(
+                matches!(
+                    settings.validate(),
+                    Err(ActionRejected::InvalidSettings { .. })
+                ),
+                "{settings:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn settings_left_out_of_an_update_keep_their_defaults() {
+        let settings: RoomSettings = serde_json::from_value(serde_json::json!({
+            "card_set": "tshirt",
+            "auto_reveal": { "mode": "afterGrace", "grace_secs": 10 },
+        }))
+        .expect("settings deserialize");
+
+        assert_eq!(
+            settings.auto_reveal,
+            AutoReveal::AfterGrace { grace_secs: 10 }
+        );
+        assert_eq!(
+            settings.reveal_quorum,
+            RoomSettings::default().reveal_quorum
+        );
+        assert!(settings.allow_spectators);
+    }
+}
@@ -0,0 +1,216 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+
+use std::{convert::Infallible, sync::Arc};
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+};
+use futures_util::{StreamExt, stream};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{
+    handlers::visible_room,
+    hub::RoomMessage,
+    types::{AppState, RoomSnapshotEvent},
+};
+
+/// Streams a room's broadcasts to a read-only observer as server-sent events.
+/// - Starts with a "roomSnapshot" event holding the current room.
+/// - Votes are hidden until the cards are revealed, as for players.
+/// - Observers are not added to the room as players or spectators.
+/// - Observers of login-only rooms must be logged in, others get 401.
+/// - The stream ends when the room is closed.
+pub async fn room_events(
+    State(app_state): State<Arc<AppState>>,
+    Path(room_id): Path<Uuid>,
+    headers: HeaderMap,
+) -> Response {
+    let rooms = app_state.rooms.lock().await;
+    let Some(room) = rooms.get(&room_id) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if room.require_login
+        && app_state
+            .login
+            .as_ref()
+            .and_then(|login| login.user(&headers))
+            .is_none()
+    {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    // subscribe while holding the lock so no update falls between
+    // the snapshot and the first broadcast
+    let receiver = app_state.hub.subscribe(room_id);
+    let snapshot = RoomMessage::new::<RoomSnapshotEvent>(&visible_room(room)).map(Arc::new);
+    drop(rooms);
+    info!("Observer subscribed to room {}", room_id);
+
+    let updates = stream::unfold(receiver, move |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(message) => return Some((message, receiver)),
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Observer missed {} messages in room {}", skipped, room_id);
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    let events = stream::iter(snapshot)
+        .chain(updates)
+        .map(|message| Ok::<_, Infallible>(to_event(&message)));
+
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+/// Formats a room message as an event named after the Socket.IO event
+fn to_event(message: &RoomMessage) -> Event {
+    Event::default()
+        .event(message.event)
+        .data(message.payload.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::BodyDataStream,
+        http::{HeaderValue, header::COOKIE},
+    };
+    use chrono::Utc;
+    use reqwest::Url;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::{
+        auth::{AuthUser, SESSION_COOKIE},
+        config::Config,
+        test_support::{app_state, cast, room},
+        types::MaintenanceNoticeEvent,
+    };
+
+    /// State with login enabled and a login-only room
+    fn login_only_room() -> (Arc<AppState>, Uuid) {
+        let mut config = Config::default();
+        config.oidc.issuer = Some(Url::parse("http://idp.test").expect("valid URL"));
+        config.oidc.client_id = Some("storypoint".to_owned());
+        config.oidc.redirect_url =
+            Some(Url::parse("http://localhost:3333/auth/callback").expect("valid URL"));
+        config.oidc.session_secret = Some("s".repeat(32));
+        let app_state = app_state(config);
+
+        let mut room = room(&["host"]);
+        room.require_login = true;
+        let room_id = room.id;
+        app_state
+            .rooms
+            .try_lock()
+            .expect("rooms are free")
+            .insert(room_id, room);
+        (app_state, room_id)
+    }
+
+    /// The next event sent on a stream, as text
+    async fn next_event(body: &mut BodyDataStream) -> Option<String> {
+        body.next()
+            .await
+            .map(|chunk| String::from_utf8_lossy(&chunk.expect("body streams")).into_owned())
+    }
+
+    #[tokio::test]
+    async fn observers_get_a_snapshot_then_the_room_updates_until_it_closes() {
+        let app_state = app_state(Config::default());
+        let mut room = room(&["host", "ada"]);
+        cast(&mut room, &[("host", 3)]);
+        let room_id = room.id;
+        app_state.rooms.lock().await.insert(room_id, room);
+
+        let response = room_events(
+            State(Arc::clone(&app_state)),
+            Path(room_id),
+            HeaderMap::new(),
+        )
+        .await;
+        let mut body = response.into_body().into_data_stream();
+
+        let snapshot = next_event(&mut body).await.expect("snapshot is sent");
+        let data = snapshot
+            .strip_prefix("event: roomSnapshot\ndata: ")
+            .expect("snapshot comes first");
+        let data: Value = serde_json::from_str(data.trim_end()).expect("data is JSON");
+        assert_eq!(data["players"]["host"]["has_voted"], true);
+        assert_eq!(data["players"]["host"]["vote"], Value::Null);
+
+        app_state
+            .hub
+            .publish::<MaintenanceNoticeEvent>(room_id, &"Restarting soon".to_owned());
+        assert_eq!(
+            next_event(&mut body).await.as_deref(),
+            Some("event: maintenanceNotice\ndata: \"Restarting soon\"\n\n")
+        );
+
+        app_state.hub.close(room_id);
+        assert_eq!(next_event(&mut body).await, None);
+    }
+
+    #[tokio::test]
+    async fn login_only_rooms_refuse_anonymous_observers() {
+        let (app_state, room_id) = login_only_room();
+
+        let response = room_events(State(app_state), Path(room_id), HeaderMap::new()).await;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn login_only_rooms_stream_to_logged_in_observers() {
+        let (app_state, room_id) = login_only_room();
+        let user = AuthUser {
+            sub: "user-1".to_owned(),
+            name: "ada".to_owned(),
+            picture: None,
+            exp: Utc::now().timestamp() + 60,
+        };
+        let session = app_state
+            .login
+            .as_ref()
+            .expect("login is configured")
+            .session(&user);
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            COOKIE,
+            HeaderValue::from_str(&format!("{SESSION_COOKIE}={session}")).expect("cookie is text"),
+        );
+
+        let response = room_events(State(app_state), Path(room_id), headers).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn open_rooms_stream_to_anyone() {
+        let app_state = app_state(Config::default());
+        let room = room(&["host"]);
+        let room_id = room.id;
+        app_state.rooms.lock().await.insert(room_id, room);
+
+        let response = room_events(
+            State(Arc::clone(&app_state)),
+            Path(room_id),
+            HeaderMap::new(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = room_events(State(app_state), Path(Uuid::new_v4()), HeaderMap::new()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
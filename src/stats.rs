@@ -0,0 +1,261 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{protocol::describe_struct, types::Room};
+
+/// Card value the frontend uses for "?", it is counted as
+/// an abstention rather than an estimate
+pub const UNSURE_VOTE: u8 = 0;
+
+/// Labels of the t-shirt card set, in value order from 1
+const TSHIRT_LABELS: [&str; 6] = ["XS", "S", "M", "L", "XL", "2XL"];
+
+/// Estimate cards of the fibonacci card set
+const FIBONACCI_VALUES: [u8; 6] = [1, 2, 3, 5, 8, 13];
+
+/// The label shown on a card, matching the frontend card sets
+pub fn card_label(card_set: &str, value: u8) -> String {
+    match (card_set, value) {
+        (_, UNSURE_VOTE) => "?".to_owned(),
+        ("tshirt", 1..=6) => TSHIRT_LABELS[usize::from(value - 1)].to_owned(),
+        _ => value.to_string(),
+    }
+}
+
+/// Whether a value is an estimate card in the set, "?" is not
+pub fn is_estimate(card_set: &str, value: u8) -> bool {
+    match card_set {
+        "tshirt" => (1..=6).contains(&value),
+        _ => FIBONACCI_VALUES.contains(&value),
+    }
+}
+
+/// `VoteStatistics` summarises the votes of a revealed round
+/// Abstentions ("?" cards) are counted but excluded from the figures
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct VoteStatistics {
+    /// Number of players who voted, including abstentions
+    pub votes: usize,
+    /// Number of "?" votes
+    pub abstentions: usize,
+    /// Mean of the estimates
+    pub average: Option<f64>,
+    /// Median of the estimates, rounded down to a cast vote
+    pub median: Option<u8>,
+    /// Lowest estimate
+    pub min: Option<u8>,
+    /// Highest estimate
+    pub max: Option<u8>,
+    /// Whether every estimate was the same
+    pub consensus: bool,
+    /// Number of votes cast for each card value
+    pub distribution: BTreeMap<u8, usize>,
+    /// Number of times players changed or retracted a vote during the round
+    #[serde(default)]
+    pub vote_changes: usize,
+    /// The estimate the host accepted for the round, if any
+    #[serde(default)]
+    pub estimate: Option<u8>,
+}
+
+describe_struct!(VoteStatistics {
+    votes,
+    abstentions,
+    average,
+    median,
+    min,
+    max,
+    consensus,
+    distribution,
+    vote_changes,
+    estimate,
+});
+
+impl VoteStatistics {
+    /// Calculates the statistics for a set of votes
+    pub fn from_votes(votes: impl IntoIterator<Item = u8>) -> Self {
+        let mut distribution = BTreeMap::new();
+        let mut estimates = Vec::new();
+        let mut total = 0;
+        for vote in votes {
+            total += 1;
+            *distribution.entry(vote).or_insert(0) += 1;
+            if vote != UNSURE_VOTE {
+                estimates.push(vote);
+            }
+        }
+        estimates.sort_unstable();
+
+        let sum: u32 = estimates.iter().map(|&v| u32::from(v)).sum();
+        #[allow(
+            clippy::cast_precision_loss,
+            reason = "vote counts are far below f64 precision"
+        )]
+        let average = (!estimates.is_empty()).then(|| f64::from(sum) / estimates.len() as f64);
+
+        Self {
+            votes: total,
+            abstentions: total - estimates.len(),
+            average,
+            median: estimates
+                .get(estimates.len().saturating_sub(1) / 2)
+                .copied(),
+            min: estimates.first().copied(),
+            max: estimates.last().copied(),
+            consensus: !estimates.is_empty() && estimates.first() == estimates.last(),
+            distribution,
+            vote_changes: 0,
+            estimate: None,
+        }
+    }
+
+    /// Calculates the statistics for the current votes in a room,
+    /// spectators never vote so are ignored
+    pub fn for_room(room: &Room) -> Self {
+        Self {
+            vote_changes: room.vote_changes,
+            estimate: room.estimate,
+            ..Self::from_votes(
+                room.players
+                    .values()
+                    .filter(|p| !p.is_spectator)
+                    .filter_map(|p| p.vote),
+            )
+        }
+    }
+}
+
+/// `Convergence` tracks how a story's estimates moved over its rounds
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Convergence {
+    /// Number of rounds revealed for the story
+    pub rounds: usize,
+    /// Highest minus lowest estimate of each round, `None` for rounds without estimates
+    pub spreads: Vec<Option<u8>>,
+    /// Whether the latest round's spread is narrower than the first round's
+    pub narrowing: bool,
+    /// The round, counting from 1, that first reached consensus
+    pub consensus_round: Option<usize>,
+}
+
+describe_struct!(Convergence {
+    rounds,
+    spreads,
+    narrowing,
+    consensus_round,
+});
+
+impl Convergence {
+    /// Works out the convergence of a story's rounds, in the order they were revealed
+    pub fn for_rounds(rounds: &[VoteStatistics]) -> Self {
+        let spreads: Vec<Option<u8>> = rounds
+            .iter()
+            .map(|round| round.max.zip(round.min).map(|(max, min)| max - min))
+            .collect();
+        let narrowing = matches!(
+            (spreads.first(), spreads.last()),
+            (Some(Some(first)), Some(Some(last))) if last < first
+        );
+
+        Self {
+            rounds: rounds.len(),
+            spreads,
+            narrowing,
+            consensus_round: rounds
+                .iter()
+                .position(|round| round.consensus)
+                .map(|index| index + 1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::NUMERIC_CARD_SET;
+
+    /// Statistics of a round with the given votes
+    fn round(votes: &[u8]) -> VoteStatistics {
+        VoteStatistics::from_votes(votes.iter().copied())
+    }
+
+    #[test]
+    fn from_votes_summarises_the_estimates() {
+        let stats = round(&[5, 1, 3, 8]);
+
+        assert_eq!(stats.votes, 4);
+        assert_eq!(stats.abstentions, 0);
+        assert_eq!(stats.average, Some(4.25));
+        assert_eq!(stats.median, Some(3));
+        assert_eq!((stats.min, stats.max), (Some(1), Some(8)));
+        assert!(!stats.consensus);
+        assert_eq!(
+            stats.distribution,
+            BTreeMap::from([(1, 1), (3, 1), (5, 1), (8, 1)])
+        );
+    }
+
+    #[test]
+    fn from_votes_counts_abstentions_but_leaves_them_out_of_the_figures() {
+        let stats = round(&[UNSURE_VOTE, 3, 3, UNSURE_VOTE]);
+
+        assert_eq!(stats.votes, 4);
+        assert_eq!(stats.abstentions, 2);
+        assert_eq!(stats.average, Some(3.0));
+        assert_eq!(stats.median, Some(3));
+        assert!(stats.consensus);
+        assert_eq!(stats.distribution.get(&UNSURE_VOTE), Some(&2));
+
+        let unsure = round(&[UNSURE_VOTE]);
+        assert_eq!(unsure.average, None);
+        assert_eq!(unsure.median, None);
+        assert!(!unsure.consensus);
+    }
+
+    #[test]
+    fn tshirt_cards_are_labelled_and_counted_by_value() {
+        let stats = round(&[2, 3, 3]);
+
+        assert_eq!(
+            stats.median.map(|value| card_label("tshirt", value)),
+            Some("M".to_owned())
+        );
+        assert_eq!(card_label("tshirt", 6), "2XL");
+        assert_eq!(card_label("tshirt", UNSURE_VOTE), "?");
+        assert_eq!(card_label(NUMERIC_CARD_SET, 13), "13");
+        assert!(is_estimate("tshirt", 6));
+        assert!(!is_estimate("tshirt", 8));
+        assert!(!is_estimate(NUMERIC_CARD_SET, 4));
+        assert!(!is_estimate(NUMERIC_CARD_SET, UNSURE_VOTE));
+    }
+
+    #[test]
+    fn convergence_tracks_spreads_and_the_first_consensus() {
+        let convergence = Convergence::for_rounds(&[
+            round(&[1, 8]),
+            round(&[UNSURE_VOTE]),
+            round(&[3, 5]),
+            round(&[5, 5]),
+        ]);
+
+        assert_eq!(convergence.rounds, 4);
+        assert_eq!(convergence.spreads, [Some(7), None, Some(2), Some(0)]);
+        assert!(convergence.narrowing);
+        assert_eq!(convergence.consensus_round, Some(4));
+    }
+
+    #[test]
+    fn convergence_needs_estimates_in_the_first_and_last_rounds() {
+        let widening = Convergence::for_rounds(&[round(&[3, 5]), round(&[1, 8])]);
+        assert!(!widening.narrowing);
+        assert_eq!(widening.consensus_round, None);
+
+        let unsure_last = Convergence::for_rounds(&[round(&[1, 8]), round(&[UNSURE_VOTE])]);
+        assert!(!unsure_last.narrowing);
+
+        assert_eq!(Convergence::for_rounds(&[]), Convergence::default());
+    }
+}
@@ -0,0 +1,211 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::{fs, sync::Mutex};
+use uuid::Uuid;
+
+use crate::{teams::Team, types::Room, webhooks::RoomWebhooks};
+
+/// A room as written to the store, including the server side
+/// state that is never sent to clients
+#[derive(Debug, Deserialize, Serialize)]
+struct StoredRoom {
+    #[serde(flatten)]
+    room: Room,
+    #[serde(default)]
+    webhooks: RoomWebhooks,
+    /// Rooms saved before settings were stored only kept their card set
+    #[serde(default, skip_serializing)]
+    card_set: Option<String>,
+}
+
+/// A team as written to the store, including its edit token
+#[derive(Debug, Deserialize, Serialize)]
+struct StoredTeam {
+    #[serde(flatten)]
+    team: Team,
+    token: String,
+}
+
+/// Everything written to the store
+#[derive(Debug, Deserialize, Serialize)]
+struct StoredState {
+    rooms: HashMap<Uuid, StoredRoom>,
+    #[serde(default)]
+    teams: HashMap<Uuid, StoredTeam>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum StoreContents {
+    State(StoredState),
+    /// Files written before teams were stored hold only rooms
+    Rooms(HashMap<Uuid, StoredRoom>),
+}
+
+/// `Store` persists rooms and teams to a JSON file so they survive a restart
+/// The file is written when the server shuts down or a team changes
+/// and read at startup
+#[derive(Debug)]
+pub struct Store {
+    /// The file rooms and teams are written to
+    path: PathBuf,
+    /// Held while writing so concurrent saves do not share the temporary file
+    writing: Mutex<()>,
+}
+
+impl Store {
+    pub const fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            writing: Mutex::const_new(()),
+        }
+    }
+
+    /// Reads the rooms and teams saved last, if any.
+    /// Players are not restored as their connections did not survive
+    /// the restart, the first player to rejoin becomes the host.
+    pub async fn load(&self) -> Result<(HashMap<Uuid, Room>, HashMap<Uuid, Team>), StoreError> {
+        let contents = match fs::read(&self.path).await {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Ok((HashMap::new(), HashMap::new()));
+            }
+            Err(err) => return Err(StoreError::Io(err)),
+        };
+
+        let stored = match serde_json::from_slice(&contents).map_err(StoreError::Json)? {
+            StoreContents::State(state) => state,
+            StoreContents::Rooms(rooms) => StoredState {
+                rooms,
+                teams: HashMap::new(),
+            },
+        };
+        let teams = stored
+            .teams
+            .into_iter()
+            .map(|(id, StoredTeam { mut team, token })| {
+                team.token = token;
+                (id, team)
+            })
+            .collect();
+        let now = Utc::now();
+        let rooms = stored
+            .rooms
+            .into_iter()
+            .map(|(id, stored)| {
+                let StoredRoom {
+                    mut room,
+                    webhooks,
+                    card_set,
+                } = stored;
+                if let Some(card_set) = card_set {
+                    room.settings.card_set = card_set;
+                }
+                room.players.clear();
+                room.host_id.clear();
+                room.cards_revealed = false;
+                room.results = None;
+                room.estimate = None;
+                room.created_at = now;
+                room.last_activity = now;
+                room.webhooks = webhooks;
+                (id, room)
+            })
+            .collect();
+        Ok((rooms, teams))
+    }
+
+    /// Checks the store file's directory exists and is writable
+    pub async fn check(&self) -> Result<(), StoreError> {
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let metadata = fs::metadata(dir).await.map_err(StoreError::Io)?;
+        if metadata.is_dir() && !metadata.permissions().readonly() {
+            Ok(())
+        } else {
+            Err(StoreError::Io(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{} is not a writable directory", dir.display()),
+            )))
+        }
+    }
+
+    /// Writes the rooms and teams to the store file.
+    /// Writes to a temporary file first so a failed write
+    /// never leaves a truncated store behind.
+    pub async fn save(
+        &self,
+        rooms: HashMap<Uuid, Room>,
+        teams: HashMap<Uuid, Team>,
+    ) -> Result<(), StoreError> {
+        let rooms = rooms
+            .into_iter()
+            .map(|(id, mut room)| {
+                let webhooks = std::mem::take(&mut room.webhooks);
+                (
+                    id,
+                    StoredRoom {
+                        room,
+                        webhooks,
+                        card_set: None,
+                    },
+                )
+            })
+            .collect();
+        let teams = teams
+            .into_iter()
+            .map(|(id, mut team)| {
+                let token = std::mem::take(&mut team.token);
+                (id, StoredTeam { team, token })
+            })
+            .collect();
+        let contents =
+            serde_json::to_vec(&StoredState { rooms, teams }).map_err(StoreError::Json)?;
+
+        let _writing = self.writing.lock().await;
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, contents)
+            .await
+            .map_err(StoreError::Io)?;
+        fs::rename(&tmp_path, &self.path)
+            .await
+            .map_err(StoreError::Io)
+    }
+}
+
+#[derive(Debug)]
+pub enum StoreError {
+    /// The store file could not be read or written
+    Io(io::Error),
+    /// The store file contents could not be (de)serialized
+    Json(serde_json::Error),
+}
+
+impl Error for StoreError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Json(err) => Some(err),
+        }
+    }
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "Store file error: {err}"),
+            Self::Json(err) => write!(f, "Store contents invalid: {err}"),
+        }
+    }
+}
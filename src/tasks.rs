@@ -0,0 +1,158 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+use std::sync::Arc;
+
+use chrono::Utc;
+use socketioxide::SocketIo;
+use tokio::{
+    signal,
+    time::{Duration, MissedTickBehavior, interval, sleep},
+};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    handlers::{emit_event_broadcast, notify_room_closed, reveal_cards},
+    settings::AutoReveal,
+    types::{AppState, ServerRestarting, ServerRestartingEvent},
+};
+
+/// How often rooms are checked for a grace period that is over
+const GRACE_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Periodically closes rooms that have had no activity for longer
+/// than the configured idle TTL.
+/// - Notifies any remaining players with a "roomClosed" event.
+/// - Removes the sockets from the room and the room from the state.
+/// - Forgets participant sessions unused for as long.
+pub async fn sweep_idle_rooms(io: SocketIo, app_state: Arc<AppState>) {
+    let ttls = &app_state.config.ttls;
+    let mut ticker = interval(ttls.sweep_interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        ticker.tick().await;
+        app_state.participants.prune(ttls.room_idle);
+
+        let now = Utc::now();
+        let mut idle: Vec<Uuid> = Vec::new();
+        app_state.rooms.lock().await.retain(|room_id, room| {
+            let expired = (now - room.last_activity)
+                .to_std()
+                .is_ok_and(|idle_for| idle_for >= ttls.room_idle);
+            if expired {
+                idle.push(*room_id);
+            }
+            !expired
+        });
+
+        for room_id in idle {
+            info!("Room {} has been idle too long, closing it", room_id);
+
+            notify_room_closed(
+                &io,
+                &app_state.hub,
+                room_id,
+                "Room closed due to inactivity",
+            )
+            .await;
+        }
+    }
+}
+
+/// Reveals the cards in rooms whose auto-reveal grace period is over.
+/// - Only if every player still has a vote in, so a player who joined
+///   during the grace period gets to vote first.
+/// - Nothing is revealed if the host switched the room to another policy.
+pub async fn reveal_after_grace(io: SocketIo, app_state: Arc<AppState>) {
+    let mut ticker = interval(GRACE_CHECK_INTERVAL);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        ticker.tick().await;
+
+        let now = Utc::now();
+        let mut rooms = app_state.rooms.lock().await;
+        for room in rooms.values_mut() {
+            if room.reveal_at.is_none_or(|reveal_at| reveal_at > now) {
+                continue;
+            }
+            room.reveal_at = None;
+
+            let grace = matches!(room.settings.auto_reveal, AutoReveal::AfterGrace { .. });
+            if grace && !room.cards_revealed && room.all_voted() {
+                info!("Grace period over in room {}, revealing", room.id);
+                room.touch();
+                reveal_cards(room, &io, &app_state).await;
+            }
+        }
+    }
+}
+
+/// Resolves once the process receives SIGINT or SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(err) = signal::ctrl_c().await {
+            error!("Failed to listen for SIGINT: {}", err);
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(err) => error!("Failed to listen for SIGTERM: {}", err),
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = ctrl_c => {},
+        () = terminate => {},
+    }
+}
+
+/// Waits for a shutdown signal then drains the server before it exits.
+/// - Stops new rooms being created.
+/// - Notifies every room with a "serverRestarting" event.
+/// - Keeps serving existing rooms for the drain window.
+/// - Flushes rooms and teams to the store, if configured, and closes all sockets.
+pub async fn graceful_shutdown(io: SocketIo, app_state: Arc<AppState>) {
+    shutdown_signal().await;
+
+    let shutdown = &app_state.config.shutdown;
+    info!(
+        "Shutdown signal received, draining for {:?}",
+        shutdown.drain
+    );
+    app_state.start_draining();
+
+    let notice = ServerRestarting {
+        reconnect_delay_secs: shutdown.reconnect_delay.as_secs(),
+    };
+    let room_ids: Vec<Uuid> = app_state.rooms.lock().await.keys().copied().collect();
+    for room_id in room_ids {
+        emit_event_broadcast::<ServerRestartingEvent>(&io, &app_state.hub, room_id, &notice).await;
+    }
+
+    sleep(shutdown.drain).await;
+
+    if let Some(store) = &app_state.store {
+        let rooms = app_state.rooms.lock().await.clone();
+        let teams = app_state.teams.lock().await.clone();
+        let (room_count, team_count) = (rooms.len(), teams.len());
+        match store.save(rooms, teams).await {
+            Ok(()) => info!(
+                "Saved {} rooms and {} teams to the store",
+                room_count, team_count
+            ),
+            Err(err) => error!("Failed to save rooms: {}", err),
+        }
+    }
+
+    io.close().await;
+    info!("All sockets closed, shutting down");
+}
@@ -0,0 +1,212 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode, header::AUTHORIZATION},
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    admin::token_matches, handlers::validate_name, settings::RoomSettings, types::AppState,
+};
+
+/// `Team` is a group that estimates together sprint after sprint.
+/// Its room is created with the team's defaults when the first member
+/// arrives at the team's link, and reused while it stays open.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Team {
+    /// Unique identifier for the team, part of its room link
+    pub id: Uuid,
+    /// The team's name
+    pub name: String,
+    /// Names of the team's members
+    pub members: Vec<String>,
+    /// Settings the team's rooms are created with
+    pub settings: RoomSettings,
+    /// Whether the team's rooms only admit logged in players
+    pub require_login: bool,
+    /// The team's current room, if one has been opened
+    pub room_id: Option<Uuid>,
+    /// Secret needed to change the team, kept server side as it
+    /// is only shared with whoever created the team
+    #[serde(skip)]
+    pub token: String,
+}
+
+/// Body for creating or updating a team
+#[derive(Debug, Deserialize)]
+pub struct TeamRequest {
+    pub name: String,
+    #[serde(default)]
+    pub members: Vec<String>,
+    #[serde(default)]
+    pub settings: RoomSettings,
+    #[serde(default)]
+    pub require_login: bool,
+}
+
+/// Sent once when a team is created, the token is needed to change it
+#[derive(Debug, Serialize)]
+pub struct CreatedTeam {
+    #[serde(flatten)]
+    team: Team,
+    token: String,
+}
+
+/// Checks a team request against the configured limits, trimming the names
+fn validate(app_state: &AppState, request: TeamRequest) -> Result<TeamRequest, String> {
+    let limits = &app_state.config.limits;
+    if request.members.len() > limits.players_per_room {
+        return Err(format!(
+            "Teams can have at most {} members",
+            limits.players_per_room
+        ));
+    }
+    let name = validate_name(&request.name, limits.name_length).map_err(|err| err.to_string())?;
+    let members = request
+        .members
+        .iter()
+        .map(|member| validate_name(member, limits.name_length))
+        .collect::<Result<_, _>>()
+        .map_err(|err| err.to_string())?;
+    request.settings.validate().map_err(|err| err.to_string())?;
+
+    Ok(TeamRequest {
+        name,
+        members,
+        ..request
+    })
+}
+
+/// Checks the request carries the team's token as a bearer token
+fn authorized(headers: &HeaderMap, team: &Team) -> bool {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .is_some_and(|provided| token_matches(provided, &team.token))
+}
+
+/// Saves rooms and teams to the store, if configured, so team
+/// changes are not lost if the server stops without draining
+async fn persist(app_state: &AppState) {
+    if let Some(store) = &app_state.store {
+        let rooms = app_state.rooms.lock().await.clone();
+        let teams = app_state.teams.lock().await.clone();
+        if let Err(err) = store.save(rooms, teams).await {
+            error!("Failed to save teams: {}", err);
+        }
+    }
+}
+
+/// Creates a team.
+/// - Responds 201 with the team and the token needed to change it.
+/// - Responds 400 for invalid names and 503 once the team limit is reached.
+pub async fn create_team(
+    State(app_state): State<Arc<AppState>>,
+    Json(request): Json<TeamRequest>,
+) -> Response {
+    let request = match validate(&app_state, request) {
+        Ok(request) => request,
+        Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+    };
+
+    let mut teams = app_state.teams.lock().await;
+    let max = app_state.config.limits.teams;
+    if teams.len() >= max {
+        error!("Team limit reached, refusing team {}", request.name);
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!("Team limit of {max} reached"),
+        )
+            .into_response();
+    }
+
+    let token = Uuid::new_v4().simple().to_string();
+    let team = Team {
+        id: Uuid::new_v4(),
+        name: request.name,
+        members: request.members,
+        settings: request.settings,
+        require_login: request.require_login,
+        room_id: None,
+        token: token.clone(),
+    };
+    teams.insert(team.id, team.clone());
+    drop(teams);
+    info!("Team created: {}, {}", team.id, team.name);
+
+    persist(&app_state).await;
+    (StatusCode::CREATED, Json(CreatedTeam { team, token })).into_response()
+}
+
+/// Shows a team, its roster and defaults
+pub async fn get_team(State(app_state): State<Arc<AppState>>, Path(id): Path<Uuid>) -> Response {
+    app_state.teams.lock().await.get(&id).cloned().map_or_else(
+        || StatusCode::NOT_FOUND.into_response(),
+        |team| Json(team).into_response(),
+    )
+}
+
+/// Replaces a team's name, roster and defaults.
+/// - Requires the team's token as a bearer token.
+/// - An open team room keeps its settings, the next one uses the new defaults.
+pub async fn update_team(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    headers: HeaderMap,
+    Json(request): Json<TeamRequest>,
+) -> Response {
+    let request = match validate(&app_state, request) {
+        Ok(request) => request,
+        Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+    };
+
+    let mut teams = app_state.teams.lock().await;
+    let Some(team) = teams.get_mut(&id) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if !authorized(&headers, team) {
+        error!("Unauthorized update of team {}", id);
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    team.name = request.name;
+    team.members = request.members;
+    team.settings = request.settings;
+    team.require_login = request.require_login;
+    let team = team.clone();
+    drop(teams);
+    info!("Team updated: {}", id);
+
+    persist(&app_state).await;
+    Json(team).into_response()
+}
+
+/// Deletes a team, requires the team's token as a bearer token.
+/// An open team room stays open until it empties.
+pub async fn delete_team(
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    headers: HeaderMap,
+) -> Response {
+    let mut teams = app_state.teams.lock().await;
+    let Some(team) = teams.get(&id) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if !authorized(&headers, team) {
+        error!("Unauthorized delete of team {}", id);
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    teams.remove(&id);
+    drop(teams);
+    info!("Team deleted: {}", id);
+
+    persist(&app_state).await;
+    StatusCode::NO_CONTENT.into_response()
+}
@@ -0,0 +1,197 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+
+use std::sync::{Arc, Mutex};
+
+use axum::{
+    Router,
+    body::{Body, Bytes, to_bytes},
+    extract::{Request, State},
+    http::{HeaderMap, Method, StatusCode},
+    middleware::{Next, from_fn_with_state},
+    response::{IntoResponse, Response},
+    serve,
+};
+use reqwest::Url;
+use serde_json::{Map, json};
+use socketioxide::SocketIo;
+use tokio::{
+    net::TcpListener,
+    sync::mpsc::{self, UnboundedReceiver},
+};
+use uuid::Uuid;
+
+use crate::{
+    auth::Login,
+    config::Config,
+    handlers::Peer,
+    hub::RoomMessage,
+    integrations::Integrations,
+    participants::Session,
+    types::{AppState, Room},
+    webhooks,
+    ws::WsPeer,
+};
+
+/// A request received by a stand-in
+#[derive(Clone, Debug)]
+pub struct Recorded {
+    pub method: Method,
+    /// Path and query as sent
+    pub uri: String,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl Recorded {
+    /// The value of a query parameter, decoded
+    pub fn query(&self, name: &str) -> Option<String> {
+        Url::parse(&format!("http://stand-in{}", self.uri))
+            .ok()?
+            .query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    }
+
+    /// The value of a header as text
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)?.to_str().ok()
+    }
+
+    /// The value of a field in a form encoded body, decoded
+    pub fn form(&self, name: &str) -> Option<String> {
+        Url::parse(&format!(
+            "http://stand-in/?{}",
+            String::from_utf8_lossy(&self.body)
+        ))
+        .ok()?
+        .query_pairs()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+    }
+
+    /// The body parsed as JSON
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_slice(&self.body).expect("request body is JSON")
+    }
+}
+
+type Requests = Arc<Mutex<Vec<Recorded>>>;
+
+/// `StandIn` is a local HTTP server playing an external service in tests.
+/// It answers with the given router and records every request it receives.
+#[derive(Debug)]
+pub struct StandIn {
+    /// Base URL of the server, without a trailing slash
+    pub url: String,
+    requests: Requests,
+}
+
+impl StandIn {
+    /// Starts serving the router on a free local port
+    pub async fn start(router: Router) -> Self {
+        let requests = Requests::default();
+        let app = router.layer(from_fn_with_state(Arc::clone(&requests), record));
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("stand-in can bind a local port");
+        let url = format!(
+            "http://{}",
+            listener.local_addr().expect("stand-in has an address")
+        );
+        tokio::spawn(async move { serve(listener, app).await });
+
+        Self { url, requests }
+    }
+
+    /// The requests received so far, oldest first
+    pub fn requests(&self) -> Vec<Recorded> {
+        self.requests
+            .lock()
+            .expect("stand-in lock poisoned")
+            .clone()
+    }
+}
+
+/// Records a request before passing it on to the router
+async fn record(State(requests): State<Requests>, request: Request, next: Next) -> Response {
+    let (parts, body) = request.into_parts();
+    let Ok(body) = to_bytes(body, usize::MAX).await else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    requests
+        .lock()
+        .expect("stand-in lock poisoned")
+        .push(Recorded {
+            method: parts.method.clone(),
+            uri: parts.uri.to_string(),
+            headers: parts.headers.clone(),
+            body: body.clone(),
+        });
+
+    next.run(Request::from_parts(parts, Body::from(body))).await
+}
+
+/// A room with a player for each ID, named after it, the first one hosting
+pub fn room(ids: &[&str]) -> Room {
+    let players: Map<String, serde_json::Value> = ids
+        .iter()
+        .map(|&id| {
+            let player = json!({
+                "has_voted": false,
+                "id": id,
+                "name": id,
+                "vote": null,
+                "is_spectator": false,
+            });
+            (id.to_owned(), player)
+        })
+        .collect();
+    serde_json::from_value(json!({
+        "cards_revealed": false,
+        "host_id": ids.first().copied().unwrap_or_default(),
+        "id": Uuid::new_v4(),
+        "players": players,
+    }))
+    .expect("room deserializes")
+}
+
+/// Records votes in a room as if the players had cast them
+pub fn cast(room: &mut Room, votes: &[(&str, u8)]) {
+    for &(id, vote) in votes {
+        let player = room.players.get_mut(id).expect("player is in the room");
+        player.vote = Some(vote);
+        player.has_voted = true;
+    }
+}
+
+/// Application state for a config, webhook deliveries are dropped
+pub fn app_state(config: Config) -> Arc<AppState> {
+    let (webhooks, _) = webhooks::channel(&config.webhooks).expect("client builds");
+    let integrations = Integrations::from_config(&config).expect("clients build");
+    let login = Login::from_config(&config).expect("client builds");
+    Arc::new(AppState::new(config, webhooks, integrations, login))
+}
+
+/// A WebSocket peer with a session, resuming `token` if given,
+/// and the events sent to it
+pub fn peer(
+    app_state: &AppState,
+    token: Option<&str>,
+) -> (Peer, Session, UnboundedReceiver<Arc<RoomMessage>>) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let peer = Peer::WebSocket(Arc::new(WsPeer::new(sender)));
+    let session = app_state.participants.connect(&peer.id(), token, None);
+    (peer, session, receiver)
+}
+
+/// The events queued for a peer so far
+pub fn events(receiver: &mut UnboundedReceiver<Arc<RoomMessage>>) -> Vec<Arc<RoomMessage>> {
+    std::iter::from_fn(|| receiver.try_recv().ok()).collect()
+}
+
+/// A Socket.IO server with the default namespace and no connections
+pub fn io() -> SocketIo {
+    let (_, io) = SocketIo::new_layer();
+    io.ns("/", async || {});
+    io
+}
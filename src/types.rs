@@ -1,14 +1,40 @@
 #![warn(clippy::all, clippy::pedantic, clippy::nursery)]
 
-use std::{collections::HashMap, error::Error, fmt};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
 use uuid::Uuid;
 
+use crate::{
+    auth::Login,
+    config::Config,
+    hub::RoomHub,
+    integrations::{Integrations, chat::ChatWebhook},
+    participants::{Participants, Session},
+    profile::Profile,
+    protocol::{
+        Describe, declare_client_events, declare_server_events, describe_struct, describe_tagged,
+    },
+    settings::RoomSettings,
+    stats::{Convergence, VoteStatistics},
+    store::Store,
+    teams::Team,
+    webhooks::{RoomWebhooks, WebhookDispatcher},
+};
+
 /// Player represents a connected user
 /// It contains their ID, name, vote, and whether they have voted
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
 pub struct Player {
     /// Indicates if the player has voted
     pub has_voted: bool,
@@ -20,6 +46,101 @@ pub struct Player {
     pub vote: Option<u8>,
     /// Indicates if the player is a spectator
     pub is_spectator: bool,
+    /// Whether the name comes from a verified login rather than the player
+    #[serde(default)]
+    pub verified: bool,
+    /// Avatar picture URL from the player's login, if any
+    #[serde(default)]
+    pub avatar_url: Option<String>,
+    /// The player's avatar emoji, colour and role
+    #[serde(default)]
+    pub profile: Profile,
+}
+
+describe_struct!(Player {
+    has_voted,
+    id,
+    name,
+    vote,
+    is_spectator,
+    verified,
+    avatar_url,
+    profile
+});
+
+/// Card set whose card values are story points, other card sets
+/// (e.g. t-shirt sizes) use card values only as indexes
+pub const NUMERIC_CARD_SET: &str = "fibonacci";
+
+/// Where a story in the queue was imported from
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum StorySource {
+    /// An issue from a Jira compatible tracker
+    Jira { key: String },
+    /// An issue from a GitHub repository
+    #[serde(rename = "github")]
+    GitHub {
+        /// Repository in `owner/name` form
+        repo: String,
+        /// Issue number within the repository
+        number: u64,
+        /// GraphQL node ID, used to update project fields
+        node_id: String,
+    },
+}
+
+describe_tagged!(StorySource, "kind" {
+    Jira { key },
+    GitHub = "github" { repo, number, node_id },
+});
+
+/// `Story` is an item in the room's queue of work to estimate
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Story {
+    /// Unique identifier for the story within the room
+    pub id: Uuid,
+    /// The story's title
+    pub title: String,
+    /// Link to the story in its tracker, if any
+    pub url: Option<String>,
+    /// The tracker the story was imported from, if any
+    pub source: Option<StorySource>,
+    /// The estimate agreed for the story, if any
+    pub estimate: Option<u8>,
+    /// Results of each round revealed for the story, oldest first
+    #[serde(default)]
+    pub rounds: Vec<VoteStatistics>,
+    /// How the estimates moved over the rounds
+    #[serde(default)]
+    pub convergence: Convergence,
+}
+
+describe_struct!(Story {
+    id,
+    title,
+    url,
+    source,
+    estimate,
+    rounds,
+    convergence
+});
+
+impl Story {
+    /// Adds a revealed round to the story
+    pub fn add_round(&mut self, results: VoteStatistics) {
+        self.rounds.push(results);
+        self.convergence = Convergence::for_rounds(&self.rounds);
+    }
+
+    /// Replaces the latest round's results after a vote changed once revealed
+    pub fn revise_round(&mut self, results: VoteStatistics) {
+        match self.rounds.last_mut() {
+            Some(round) => *round = results,
+            None => self.rounds.push(results),
+        }
+        self.convergence = Convergence::for_rounds(&self.rounds);
+    }
 }
 
 /// `Room` represents a game room
@@ -35,18 +156,263 @@ pub struct Room {
     pub id: Uuid,
     /// A list of players in the room
     pub players: HashMap<String, Player>,
-    /// Which card set is being used in the frontend
-    pub card_set: String,
+    /// How the room plays, changed by the host
+    #[serde(default)]
+    pub settings: RoomSettings,
+    /// Counts and statistics of the revealed votes, `None` until the cards are revealed
+    #[serde(default)]
+    pub results: Option<VoteStatistics>,
+    /// The estimate the host accepted for the revealed round, if any
+    #[serde(default)]
+    pub estimate: Option<u8>,
+    /// Results of each round revealed since the votes were last reset,
+    /// or of the current story's rounds when one is selected, oldest first
+    #[serde(default)]
+    pub rounds: Vec<VoteStatistics>,
+    /// How the estimates converged over the rounds
+    #[serde(default)]
+    pub convergence: Convergence,
+    /// Queue of stories to estimate
+    #[serde(default)]
+    pub stories: Vec<Story>,
+    /// The story currently being estimated, if any
+    #[serde(default)]
+    pub current_story: Option<Uuid>,
+    /// Whether only logged in users may join
+    #[serde(default)]
+    pub require_login: bool,
+    /// Sequence number of the last delta broadcast to the room
+    #[serde(default)]
+    pub seq: u64,
+    /// The room as last broadcast, deltas are worked out against it
+    #[serde(skip)]
+    pub published: Option<Arc<Self>>,
+    /// When the cards are due to be revealed after a grace period, kept server side
+    #[serde(skip)]
+    pub reveal_at: Option<DateTime<Utc>>,
+    /// Times players changed or retracted a vote this round, kept server
+    /// side until the cards are revealed
+    #[serde(skip)]
+    pub vote_changes: usize,
+    /// When the room was created, kept server side
+    #[serde(skip)]
+    pub created_at: DateTime<Utc>,
+    /// When the room last saw any player activity, kept server side
+    #[serde(skip)]
+    pub last_activity: DateTime<Utc>,
+    /// Webhooks registered by the host, kept server side as the
+    /// URLs and signing secret are only shared with the host
+    #[serde(skip)]
+    pub webhooks: RoomWebhooks,
+}
+
+describe_struct!(Room {
+    cards_revealed,
+    host_id,
+    id,
+    players,
+    settings,
+    results,
+    estimate,
+    rounds,
+    convergence,
+    stories,
+    current_story,
+    require_login,
+    seq,
+} skip { published, reveal_at, vote_changes, created_at, last_activity, webhooks });
+
+/// `RoomDelta` is a sequenced set of changes to a room, sent instead of the
+/// whole room. Clients apply deltas in sequence order and send "resync"
+/// when they see a gap to get a fresh "roomSnapshot".
+#[derive(Clone, Debug, Serialize)]
+pub struct RoomDelta {
+    /// The room that changed
+    pub room_id: Uuid,
+    /// Follows on from the room's previous delta or snapshot
+    pub seq: u64,
+    /// What changed, each change replaces the value it names
+    pub changes: Vec<RoomChange>,
+}
+
+describe_struct!(RoomDelta {
+    room_id,
+    seq,
+    changes
+});
+
+/// A single change to a room as seen by its players
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "op", rename_all = "camelCase")]
+pub enum RoomChange {
+    /// A player joined the room
+    PlayerAdded { player: Player },
+    /// A player voted or otherwise changed
+    PlayerUpdated { player: Player },
+    /// A player left the room
+    PlayerRemoved { player_id: String },
+    /// The room's own fields changed
+    RoomUpdated {
+        cards_revealed: bool,
+        host_id: String,
+        current_story: Option<Uuid>,
+        estimate: Option<u8>,
+    },
+    /// The host changed the room's settings
+    SettingsUpdated { settings: RoomSettings },
+    /// The cards were revealed or the votes reset
+    ResultsUpdated { results: Option<VoteStatistics> },
+    /// The story queue changed
+    StoriesUpdated { stories: Vec<Story> },
+    /// A round was revealed or revised, or the round history started over
+    RoundsUpdated {
+        rounds: Vec<VoteStatistics>,
+        convergence: Convergence,
+    },
+}
+
+describe_tagged!(RoomChange, "op" {
+    PlayerAdded { player },
+    PlayerUpdated { player },
+    PlayerRemoved { player_id },
+    RoomUpdated { cards_revealed, host_id, current_story, estimate },
+    SettingsUpdated { settings },
+    ResultsUpdated { results },
+    StoriesUpdated { stories },
+    RoundsUpdated { rounds, convergence },
+});
+
+impl Room {
+    /// Marks the room as active now
+    pub fn touch(&mut self) {
+        self.last_activity = Utc::now();
+    }
+
+    /// Whether every player who is not a spectator has voted
+    pub fn all_voted(&self) -> bool {
+        self.players
+            .values()
+            .filter(|p| !p.is_spectator)
+            .all(|p| p.has_voted)
+    }
+
+    /// The number of votes in and the number the host needs to reveal the cards
+    pub fn quorum(&self) -> (usize, usize) {
+        let players: Vec<&Player> = self.players.values().filter(|p| !p.is_spectator).collect();
+        let votes = players.iter().filter(|p| p.has_voted).count();
+        (votes, self.settings.reveal_quorum.required(players.len()))
+    }
+
+    /// The story currently being estimated, if any
+    pub fn current_story_mut(&mut self) -> Option<&mut Story> {
+        let current = self.current_story?;
+        self.stories.iter_mut().find(|story| story.id == current)
+    }
+
+    /// Adds a revealed round to the room and the current story
+    pub fn add_round(&mut self, results: VoteStatistics) {
+        if let Some(story) = self.current_story_mut() {
+            story.add_round(results.clone());
+        }
+        self.rounds.push(results);
+        self.convergence = Convergence::for_rounds(&self.rounds);
+    }
+
+    /// Replaces the latest round's results in the room and the current story
+    pub fn revise_round(&mut self, results: VoteStatistics) {
+        if let Some(story) = self.current_story_mut() {
+            story.revise_round(results.clone());
+        }
+        match self.rounds.last_mut() {
+            Some(round) => *round = results,
+            None => self.rounds.push(results),
+        }
+        self.convergence = Convergence::for_rounds(&self.rounds);
+    }
+
+    /// Starts the round history over, picking up the rounds the current
+    /// story already has if one is selected
+    pub fn restart_rounds(&mut self) {
+        self.rounds = self
+            .current_story_mut()
+            .map(|story| story.rounds.clone())
+            .unwrap_or_default();
+        self.convergence = Convergence::for_rounds(&self.rounds);
+    }
+
+    /// The next story in the queue after the current one still to be estimated
+    pub fn next_story(&self) -> Option<Uuid> {
+        let start = self
+            .current_story
+            .and_then(|current| self.stories.iter().position(|story| story.id == current))
+            .map_or(0, |index| index + 1);
+        self.stories[start..]
+            .iter()
+            .find(|story| story.estimate.is_none())
+            .map(|story| story.id)
+    }
 }
 
 /// `AppState` holds the global application state
-/// It contains a map of room IDs to Room objects
-/// and is protected by a Mutex for thread safety
-#[derive(Debug, Default)]
+/// It contains the server configuration and a map of room IDs
+/// to Room objects which is protected by a Mutex for thread safety
+#[derive(Debug)]
 pub struct AppState {
+    /// The configuration loaded at startup
+    pub config: Config,
     /// A map of room IDs to Room objects
     /// Protected by a Mutex for thread safety
     pub rooms: Mutex<HashMap<Uuid, Room>>,
+    /// A map of team IDs to Team objects
+    /// Always locked after rooms when both are needed
+    pub teams: Mutex<HashMap<Uuid, Team>>,
+    /// Where rooms are persisted across restarts, if configured
+    pub store: Option<Store>,
+    /// Queues webhook deliveries for room events
+    pub webhooks: WebhookDispatcher,
+    /// Clients for the configured issue tracker and chat integrations
+    pub integrations: Integrations,
+    /// Room broadcasts for clients outside Socket.IO
+    pub hub: RoomHub,
+    /// Participant IDs for each connection
+    pub participants: Participants,
+    /// Login through the OIDC provider, if configured
+    pub login: Option<Login>,
+    /// Set once a shutdown has started, no new rooms are created while draining
+    draining: AtomicBool,
+}
+
+impl AppState {
+    pub fn new(
+        config: Config,
+        webhooks: WebhookDispatcher,
+        integrations: Integrations,
+        login: Option<Login>,
+    ) -> Self {
+        let store = config.state_file.clone().map(Store::new);
+        Self {
+            config,
+            rooms: Mutex::default(),
+            teams: Mutex::default(),
+            store,
+            webhooks,
+            integrations,
+            hub: RoomHub::default(),
+            participants: Participants::default(),
+            login,
+            draining: AtomicBool::new(false),
+        }
+    }
+
+    /// Whether the server is draining before shutting down
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+
+    /// Marks the server as draining
+    pub fn start_draining(&self) {
+        self.draining.store(true, Ordering::Relaxed);
+    }
 }
 
 /// `Room` created event
@@ -58,8 +424,13 @@ pub struct CreateRoomEvent {
     pub is_spectator: bool,
     // the cardset used in the room
     pub card_set: String,
+    /// Whether only logged in users may join the room
+    #[serde(default)]
+    pub require_login: bool,
 }
 
+describe_struct!(inbound CreateRoomEvent { name, is_spectator, card_set, require_login = default });
+
 /// Join room event
 #[derive(Debug, Deserialize)]
 pub struct JoinRoomEvent {
@@ -71,6 +442,21 @@ pub struct JoinRoomEvent {
     pub is_spectator: bool,
 }
 
+describe_struct!(inbound JoinRoomEvent { name, room_id, is_spectator });
+
+/// Join a team's room event, the room is opened if the team has none
+#[derive(Debug, Deserialize)]
+pub struct JoinTeamRoomEvent {
+    /// The name of the player joining the room
+    pub name: String,
+    /// The ID of the team whose room to join
+    pub team_id: String,
+    /// whether the player is a spectator
+    pub is_spectator: bool,
+}
+
+describe_struct!(inbound JoinTeamRoomEvent { name, team_id, is_spectator });
+
 /// User voted in a room
 #[derive(Debug, Deserialize)]
 pub struct VoteEvent {
@@ -80,6 +466,42 @@ pub struct VoteEvent {
     pub vote: u8,
 }
 
+describe_struct!(inbound VoteEvent { room_id, vote });
+
+/// User took back their vote in a room
+#[derive(Debug, Deserialize)]
+pub struct RetractVoteEvent {
+    /// The ID of the room where the vote was cast
+    pub room_id: String,
+}
+
+describe_struct!(inbound RetractVoteEvent { room_id });
+
+/// User switched between voting and spectating in a room
+#[derive(Debug, Deserialize)]
+pub struct SetSpectatorEvent {
+    /// The ID of the room
+    pub room_id: String,
+    /// Whether the player now spectates rather than votes
+    pub is_spectator: bool,
+}
+
+describe_struct!(inbound SetSpectatorEvent { room_id, is_spectator });
+
+/// User changed how they appear in a room
+#[derive(Debug, Deserialize)]
+pub struct UpdateProfileEvent {
+    /// The ID of the room
+    pub room_id: String,
+    /// The player's new name, ignored for logged in players
+    pub name: String,
+    /// The player's new avatar emoji, colour and role
+    #[serde(default)]
+    pub profile: Profile,
+}
+
+describe_struct!(inbound UpdateProfileEvent { room_id, name, profile = default });
+
 /// Card values reveled to players
 #[derive(Debug, Deserialize)]
 pub struct RevealCardsEvent {
@@ -87,6 +509,8 @@ pub struct RevealCardsEvent {
     pub room_id: String,
 }
 
+describe_struct!(inbound RevealCardsEvent { room_id });
+
 /// Votes reset in a room
 #[derive(Debug, Deserialize)]
 pub struct ResetVotesEvent {
@@ -94,6 +518,8 @@ pub struct ResetVotesEvent {
     pub room_id: String,
 }
 
+describe_struct!(inbound ResetVotesEvent { room_id });
+
 /// Player exits a room
 #[derive(Debug, Deserialize)]
 pub struct PlayerExitEvent {
@@ -101,63 +527,321 @@ pub struct PlayerExitEvent {
     pub room_id: String,
 }
 
-pub trait SocketEvent {
-    const EVENT: &'static str;
-    type Data: serde::Serialize;
+describe_struct!(inbound PlayerExitEvent { room_id });
+
+/// Client missed a delta and asks for the whole room
+#[derive(Debug, Deserialize)]
+pub struct ResyncEvent {
+    /// The ID of the room to resync
+    pub room_id: String,
+}
+
+describe_struct!(inbound ResyncEvent { room_id });
+
+/// Host imports issues matching a JQL query into the story queue
+#[derive(Debug, Deserialize)]
+pub struct ImportJiraIssuesEvent {
+    /// The ID of the room to import into
+    pub room_id: String,
+    /// The JQL query selecting the issues
+    pub jql: String,
+}
+
+describe_struct!(inbound ImportJiraIssuesEvent { room_id, jql });
+
+/// Host imports open issues from a GitHub repository into the story queue
+#[derive(Debug, Deserialize)]
+pub struct ImportGitHubIssuesEvent {
+    /// The ID of the room to import into
+    pub room_id: String,
+    /// Repository in `owner/name` form
+    pub repo: String,
+    /// Only import issues in this milestone number, if set
+    pub milestone: Option<u64>,
+}
+
+describe_struct!(inbound ImportGitHubIssuesEvent { room_id, repo, milestone });
+
+/// Host selects the story being estimated
+#[derive(Debug, Deserialize)]
+pub struct SelectStoryEvent {
+    /// The ID of the room
+    pub room_id: String,
+    /// The ID of the story to estimate, `None` to clear the selection
+    pub story_id: Option<Uuid>,
+}
+
+describe_struct!(inbound SelectStoryEvent { room_id, story_id });
+
+/// Host registers a webhook on a room
+#[derive(Debug, Deserialize)]
+pub struct AddWebhookEvent {
+    /// The ID of the room to register the webhook on
+    pub room_id: String,
+    /// The URL events are posted to
+    pub url: String,
+}
+
+describe_struct!(inbound AddWebhookEvent { room_id, url });
+
+/// Host removes a webhook from a room
+#[derive(Debug, Deserialize)]
+pub struct RemoveWebhookEvent {
+    /// The ID of the room to remove the webhook from
+    pub room_id: String,
+    /// The URL to stop posting events to
+    pub url: String,
 }
 
-pub struct RoomCreatedEvent;
-impl SocketEvent for RoomCreatedEvent {
-    const EVENT: &'static str = "roomCreated";
-    type Data = Room;
+describe_struct!(inbound RemoveWebhookEvent { room_id, url });
+
+/// Host sets or clears the room's chat webhook
+#[derive(Debug, Deserialize)]
+pub struct SetChatWebhookEvent {
+    /// The ID of the room
+    pub room_id: String,
+    /// The webhook to post round results to, `None` to stop posting
+    pub chat: Option<ChatWebhook>,
+}
+
+describe_struct!(inbound SetChatWebhookEvent { room_id, chat });
+
+/// The host changed the room's settings
+#[derive(Debug, Deserialize)]
+pub struct UpdateSettingsEvent {
+    /// The ID of the room
+    pub room_id: String,
+    /// The room's new settings, replacing all of the current ones
+    pub settings: RoomSettings,
+}
+
+describe_struct!(inbound UpdateSettingsEvent { room_id, settings });
+
+/// The host started another round on the current story
+#[derive(Debug, Deserialize)]
+pub struct RevoteEvent {
+    /// The ID of the room
+    pub room_id: String,
 }
 
-pub struct PlayerJoinedEvent;
-impl SocketEvent for PlayerJoinedEvent {
-    const EVENT: &'static str = "playerJoined";
-    type Data = Room;
+describe_struct!(inbound RevoteEvent { room_id });
+
+/// Which estimate the host accepts for a revealed round
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(tag = "from", rename_all = "camelCase")]
+pub enum EstimateChoice {
+    /// The card every estimate agreed on
+    Consensus,
+    /// The median of the estimates
+    Median,
+    /// A card chosen by the host
+    Card { value: u8 },
+}
+
+describe_tagged!(EstimateChoice, "from" {
+    Consensus {},
+    Median {},
+    Card { value },
+});
+
+/// The host accepted an estimate for the revealed round
+#[derive(Debug, Deserialize)]
+pub struct AcceptEstimateEvent {
+    /// The ID of the room
+    pub room_id: String,
+    /// Which estimate to accept
+    pub estimate: EstimateChoice,
+    /// Whether to move on to the next story still to be estimated
+    #[serde(default)]
+    pub advance: bool,
 }
 
-pub struct PlayerVotedEvent;
-impl SocketEvent for PlayerVotedEvent {
-    const EVENT: &'static str = "playerVoted";
-    type Data = Room;
+describe_struct!(inbound AcceptEstimateEvent { room_id, estimate, advance = default });
+
+declare_client_events! {
+    CreateRoomEvent = "createRoom";
+    JoinRoomEvent = "joinRoom";
+    JoinTeamRoomEvent = "joinTeamRoom";
+    VoteEvent = "vote";
+    RetractVoteEvent = "retractVote";
+    SetSpectatorEvent = "setSpectator";
+    UpdateProfileEvent = "updateProfile";
+    RevealCardsEvent = "revealCards";
+    ResetVotesEvent = "resetVotes";
+    PlayerExitEvent = "exitRoom";
+    ResyncEvent = "resync";
+    ImportJiraIssuesEvent = "importJiraIssues";
+    ImportGitHubIssuesEvent = "importGitHubIssues";
+    SelectStoryEvent = "selectStory";
+    AddWebhookEvent = "addWebhook";
+    RemoveWebhookEvent = "removeWebhook";
+    SetChatWebhookEvent = "setChatWebhook";
+    UpdateSettingsEvent = "updateSettings";
+    RevoteEvent = "revote";
+    AcceptEstimateEvent = "acceptEstimate";
 }
 
-pub struct CardsRevealedEvent;
-impl SocketEvent for CardsRevealedEvent {
-    const EVENT: &'static str = "cardsRevealed";
-    type Data = Room;
+/// An event the server emits, with the type of the data it carries
+pub trait SocketEvent {
+    const EVENT: &'static str;
+    type Data: serde::Serialize + Describe;
 }
 
-pub struct VotesResetEvent;
-impl SocketEvent for VotesResetEvent {
-    const EVENT: &'static str = "votesReset";
-    type Data = Room;
+declare_server_events! {
+    RoomCreatedEvent = "roomCreated" => Room;
+    PlayerJoinedEvent = "playerJoined" => RoomDelta;
+    PlayerVotedEvent = "playerVoted" => RoomDelta;
+    VoteRetractedEvent = "voteRetracted" => RoomDelta;
+    SpectatorChangedEvent = "spectatorChanged" => RoomDelta;
+    ProfileUpdatedEvent = "profileUpdated" => RoomDelta;
+    CardsRevealedEvent = "cardsRevealed" => RoomDelta;
+    VotesResetEvent = "votesReset" => RoomDelta;
+    PlayerDisconnectedEvent = "playerDisconnected" => RoomDelta;
+    NewHostElectedEvent = "newHostElected" => String;
+    RoomNotFoundEvent = "roomNotFound" => ();
+    MoveToRoomEvent = "moveToRoom" => String;
+    RoomClosedEvent = "roomClosed" => String;
+    MaintenanceNoticeEvent = "maintenanceNotice" => String;
+    ServerRestartingEvent = "serverRestarting" => ServerRestarting;
+    StoriesUpdatedEvent = "storiesUpdated" => RoomDelta;
+    SettingsUpdatedEvent = "settingsUpdated" => RoomDelta;
+    RevoteStartedEvent = "revoteStarted" => RoomDelta;
+    EstimateAcceptedEvent = "estimateAccepted" => RoomDelta;
+    AllVotedEvent = "allVoted" => AllVoted;
+    WebhooksUpdatedEvent = "webhooksUpdated" => RoomWebhooks;
+    RoomSnapshotEvent = "roomSnapshot" => Room;
+    InvalidMessageEvent = "invalidMessage" => String;
+    ActionRejectedEvent = "actionRejected" => ActionRejected;
+    SessionEvent = "session" => Session;
 }
 
-pub struct PlayerDisconnectedEvent;
-impl SocketEvent for PlayerDisconnectedEvent {
-    const EVENT: &'static str = "playerDisconnected";
-    type Data = Room;
+/// `AllVoted` tells a room every player has voted but the
+/// cards are waiting for the host or a grace period
+#[derive(Clone, Debug, Serialize)]
+pub struct AllVoted {
+    /// The room whose players have all voted
+    pub room_id: Uuid,
+    /// Seconds until the cards are revealed, `None` when waiting for the host
+    pub reveal_in_secs: Option<u32>,
 }
 
-pub struct NewHostElectedEvent;
-impl SocketEvent for NewHostElectedEvent {
-    const EVENT: &'static str = "newHostElected";
-    type Data = String;
+describe_struct!(AllVoted {
+    room_id,
+    reveal_in_secs
+});
+
+/// Sent to every room when the server is about to restart
+#[derive(Debug, Serialize)]
+pub struct ServerRestarting {
+    /// How long clients should wait before reconnecting
+    pub reconnect_delay_secs: u64,
 }
 
-pub struct RoomNotFoundEvent;
-impl SocketEvent for RoomNotFoundEvent {
-    const EVENT: &'static str = "roomNotFound";
-    type Data = ();
+describe_struct!(ServerRestarting {
+    reconnect_delay_secs
+});
+
+/// Reasons the server can refuse a player's request
+/// Sent to the requesting player with an `actionRejected` event
+#[derive(Debug, Serialize)]
+#[serde(tag = "reason", rename_all = "camelCase")]
+pub enum ActionRejected {
+    /// The server already has as many rooms as it allows
+    TooManyRooms { max: usize },
+    /// The room already has as many players as it allows
+    RoomFull { max: usize },
+    /// The player's name is empty or too long
+    InvalidName { max_length: usize },
+    /// The server is shutting down and not accepting new rooms
+    ServerDraining,
+    /// The webhook URL could not be accepted
+    InvalidWebhook { message: String },
+    /// The room already has as many webhooks as it allows
+    TooManyWebhooks { max: usize },
+    /// The room's story queue is full
+    TooManyStories { max: usize },
+    /// An issue tracker or chat integration failed
+    IntegrationFailed { message: String },
+    /// The room only admits logged in users
+    LoginRequired,
+    /// The room's settings could not be accepted
+    InvalidSettings { message: String },
+    /// The room does not allow players to join as spectators
+    SpectatorsNotAllowed,
+    /// The cards are revealed and the room does not allow changing votes
+    VotingClosed,
+    /// Too few players have voted for the host to reveal the cards
+    QuorumNotMet { votes: usize, required: usize },
+    /// The cards must be revealed first
+    NotRevealed,
+    /// The cards are already revealed for this round
+    AlreadyRevealed,
+    /// The revealed round has no consensus or median to accept
+    NoEstimate,
+    /// The chosen card is not an estimate in the room's card set
+    InvalidEstimate { value: u8 },
+    /// The player's avatar emoji or colour could not be accepted
+    InvalidProfile { message: String },
+    /// The card voted is not in the room's card set
+    InvalidVote { value: u8 },
+    /// Spectators cannot vote until they switch to voting
+    SpectatorCannotVote,
 }
 
-pub struct MoveToRoomEvent;
-impl SocketEvent for MoveToRoomEvent {
-    const EVENT: &'static str = "moveToRoom";
-    type Data = String;
+describe_tagged!(ActionRejected, "reason" {
+    TooManyRooms { max },
+    RoomFull { max },
+    InvalidName { max_length },
+    ServerDraining {},
+    InvalidWebhook { message },
+    TooManyWebhooks { max },
+    TooManyStories { max },
+    IntegrationFailed { message },
+    LoginRequired {},
+    InvalidSettings { message },
+    SpectatorsNotAllowed {},
+    VotingClosed {},
+    QuorumNotMet { votes, required },
+    NotRevealed {},
+    AlreadyRevealed {},
+    NoEstimate {},
+    InvalidEstimate { value },
+    InvalidProfile { message },
+    InvalidVote { value },
+    SpectatorCannotVote {},
+});
+
+impl Error for ActionRejected {}
+
+impl fmt::Display for ActionRejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooManyRooms { max } => write!(f, "Room limit of {max} reached"),
+            Self::RoomFull { max } => write!(f, "Room is full ({max} players)"),
+            Self::InvalidName { max_length } => {
+                write!(f, "Name must be between 1 and {max_length} characters")
+            }
+            Self::ServerDraining => write!(f, "Server is shutting down"),
+            Self::InvalidWebhook { message } => write!(f, "Invalid webhook: {message}"),
+            Self::TooManyWebhooks { max } => write!(f, "Webhook limit of {max} reached"),
+            Self::TooManyStories { max } => write!(f, "Story limit of {max} reached"),
+            Self::IntegrationFailed { message } => write!(f, "Integration failed: {message}"),
+            Self::LoginRequired => write!(f, "Login required"),
+            Self::InvalidSettings { message } => write!(f, "Invalid settings: {message}"),
+            Self::SpectatorsNotAllowed => write!(f, "Spectators are not allowed"),
+            Self::VotingClosed => write!(f, "Voting is closed"),
+            Self::QuorumNotMet { votes, required } => {
+                write!(f, "{votes} of {required} votes needed to reveal")
+            }
+            Self::NotRevealed => write!(f, "The cards have not been revealed"),
+            Self::AlreadyRevealed => write!(f, "The cards are already revealed"),
+            Self::NoEstimate => write!(f, "There is no estimate to accept"),
+            Self::InvalidEstimate { value } => write!(f, "{value} is not an estimate card"),
+            Self::InvalidProfile { message } => write!(f, "Invalid profile: {message}"),
+            Self::InvalidVote { value } => write!(f, "{value} is not a card in this room"),
+            Self::SpectatorCannotVote => write!(f, "Spectators cannot vote"),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -173,6 +857,19 @@ impl fmt::Display for RoomNotFoundError {
     }
 }
 
+#[derive(Debug)]
+pub struct OriginNotAllowedError {
+    pub origin: String,
+}
+
+impl Error for OriginNotAllowedError {}
+
+impl fmt::Display for OriginNotAllowedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Origin not allowed: {}", self.origin)
+    }
+}
+
 #[derive(Debug)]
 pub struct RoomEmptyError;
 
@@ -0,0 +1,702 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::{
+    Client, StatusCode, Url,
+    dns::{Addrs, Name, Resolve, Resolving},
+    redirect::Policy,
+};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tokio::{net::lookup_host, sync::mpsc, time::sleep};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    config::WebhookSettings,
+    integrations::chat::ChatWebhook,
+    protocol::describe_struct,
+    stats::{Convergence, VoteStatistics},
+    types::Room,
+};
+
+/// Header carrying the event name
+const EVENT_HEADER: &str = "x-storypoint-event";
+/// Header carrying the unique delivery ID, repeated on retries
+const DELIVERY_HEADER: &str = "x-storypoint-delivery";
+/// Header carrying the HMAC-SHA256 signature of the body
+const SIGNATURE_HEADER: &str = "x-storypoint-signature";
+
+/// Webhooks registered on a room by its host
+/// Deliveries are signed with the room's secret which is shared with the host
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct RoomWebhooks {
+    /// Secret used to sign deliveries for this room
+    pub secret: String,
+    /// URLs deliveries are posted to
+    pub urls: Vec<String>,
+    /// Incoming chat webhook round results are posted to, if any
+    #[serde(default)]
+    pub chat: Option<ChatWebhook>,
+}
+
+describe_struct!(RoomWebhooks { secret, urls, chat });
+
+impl RoomWebhooks {
+    /// Creates an empty set of webhooks with a fresh signing secret
+    pub fn new() -> Self {
+        Self {
+            secret: Uuid::new_v4().simple().to_string(),
+            urls: Vec::new(),
+            chat: None,
+        }
+    }
+}
+
+/// A player's revealed vote
+#[derive(Clone, Debug, Serialize)]
+pub struct CastVote {
+    /// The player's name, `None` in anonymous rooms that do not keep attribution
+    pub name: Option<String>,
+    pub vote: Option<u8>,
+}
+
+/// Events delivered to webhooks
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", content = "data", rename_all = "camelCase")]
+pub enum WebhookEvent {
+    RoomCreated {
+        host_name: String,
+        card_set: String,
+    },
+    PlayerJoined {
+        player_name: String,
+        is_spectator: bool,
+        players: usize,
+    },
+    PlayerLeft {
+        player_name: String,
+        players: usize,
+    },
+    CardsRevealed {
+        votes: Vec<CastVote>,
+        statistics: VoteStatistics,
+        story_id: Option<Uuid>,
+        convergence: Convergence,
+    },
+    VotesReset,
+    RevoteStarted {
+        story_id: Option<Uuid>,
+        round: usize,
+    },
+    EstimateAccepted {
+        story_id: Option<Uuid>,
+        estimate: u8,
+    },
+}
+
+impl WebhookEvent {
+    /// The event name sent in the event header
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::RoomCreated { .. } => "roomCreated",
+            Self::PlayerJoined { .. } => "playerJoined",
+            Self::PlayerLeft { .. } => "playerLeft",
+            Self::CardsRevealed { .. } => "cardsRevealed",
+            Self::VotesReset => "votesReset",
+            Self::RevoteStarted { .. } => "revoteStarted",
+            Self::EstimateAccepted { .. } => "estimateAccepted",
+        }
+    }
+
+    /// Builds the revealed event from the current votes in a room,
+    /// with the room's convergence over its rounds
+    pub fn cards_revealed(room: &Room) -> Self {
+        Self::CardsRevealed {
+            votes: room
+                .players
+                .values()
+                .filter(|p| !p.is_spectator)
+                .map(|p| CastVote {
+                    name: room.settings.attributed().then(|| p.name.clone()),
+                    vote: p.vote,
+                })
+                .collect(),
+            statistics: VoteStatistics::for_room(room),
+            story_id: room.current_story,
+            convergence: room.convergence.clone(),
+        }
+    }
+}
+
+/// Body posted to webhooks
+#[derive(Debug, Serialize)]
+struct Envelope<'a> {
+    id: Uuid,
+    room_id: Uuid,
+    timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    event: &'a WebhookEvent,
+}
+
+/// A single queued delivery to one URL
+#[derive(Debug)]
+struct Delivery {
+    id: Uuid,
+    url: String,
+    event: &'static str,
+    body: Arc<[u8]>,
+    signature: Option<String>,
+    /// Whether the URL was registered by a host, so may only reach public addresses
+    restricted: bool,
+}
+
+/// Checks a URL a host wants to register, operators can configure any URL.
+/// Only https URLs are accepted, and only when their host is not `localhost`
+/// or an address on the server's own networks. Names are checked again
+/// each time they are resolved for a delivery, see `public_client`.
+pub fn validate_room_url(url: &str) -> Result<String, String> {
+    let parsed = Url::parse(url.trim()).map_err(|err| err.to_string())?;
+    if parsed.scheme() != "https" {
+        return Err("webhook URLs must use https".to_owned());
+    }
+    let Some(host) = parsed.host_str() else {
+        return Err("webhook URLs must have a host".to_owned());
+    };
+    let internal = host.trim_matches(['[', ']']).parse::<IpAddr>().map_or_else(
+        |_| {
+            let domain = host.trim_end_matches('.');
+            domain == "localhost" || domain.ends_with(".localhost")
+        },
+        |ip| !is_public(ip),
+    );
+    if internal {
+        return Err("webhook URLs must not point at internal addresses".to_owned());
+    }
+    Ok(parsed.to_string())
+}
+
+/// Whether an address is outside the server's own networks.
+/// Loopback, private, shared, link-local, unique-local, unspecified,
+/// broadcast and multicast addresses are all internal.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || first == 0
+                // shared address space used by carrier grade NAT, 100.64.0.0/10
+                || (first == 100 && second & 0b1100_0000 == 64))
+        }
+        IpAddr::V6(ip) => ip.to_ipv4_mapped().map_or_else(
+            || {
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local()
+                    || ip.is_multicast())
+            },
+            |ip| is_public(IpAddr::V4(ip)),
+        ),
+    }
+}
+
+/// Resolves names to their public addresses only, failing when there are none
+#[derive(Debug)]
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// Builds the client for URLs registered by hosts.
+/// It never follows redirects and only connects to public addresses,
+/// checked whenever a name is resolved so a name cannot be pointed
+/// at an internal address after it was registered.
+pub fn public_client(timeout: Duration) -> Result<Client, reqwest::Error> {
+    Client::builder()
+        .timeout(timeout)
+        .redirect(Policy::none())
+        .dns_resolver(Arc::new(PublicResolver))
+        .build()
+}
+
+/// Signs a body with HMAC-SHA256, formatted as `sha256=<hex>`
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// `WebhookDispatcher` queues deliveries for the background worker
+/// Dispatching never blocks the socket handlers
+#[derive(Debug)]
+pub struct WebhookDispatcher {
+    sender: mpsc::UnboundedSender<Delivery>,
+    /// Operator configured URLs that receive every room's events
+    global_urls: Vec<String>,
+    /// Secret used to sign deliveries to the operator URLs
+    global_secret: Option<String>,
+}
+
+impl WebhookDispatcher {
+    /// Queues an event for the room's webhooks and the operator webhooks
+    pub fn dispatch(&self, room: &Room, event: &WebhookEvent) {
+        if room.webhooks.urls.is_empty() && self.global_urls.is_empty() {
+            return;
+        }
+
+        let envelope = Envelope {
+            id: Uuid::new_v4(),
+            room_id: room.id,
+            timestamp: Utc::now(),
+            event,
+        };
+        let body: Arc<[u8]> = match serde_json::to_vec(&envelope) {
+            Ok(body) => body.into(),
+            Err(err) => {
+                error!("Failed to serialize webhook {}: {}", event.name(), err);
+                return;
+            }
+        };
+
+        let room_targets = room
+            .webhooks
+            .urls
+            .iter()
+            .map(|url| (url, Some(room.webhooks.secret.as_str()), true));
+        let global_targets = self
+            .global_urls
+            .iter()
+            .map(|url| (url, self.global_secret.as_deref(), false));
+
+        for (url, secret, restricted) in room_targets.chain(global_targets) {
+            let delivery = Delivery {
+                id: envelope.id,
+                url: url.clone(),
+                event: event.name(),
+                body: Arc::clone(&body),
+                signature: secret.map(|secret| sign(secret, &body)),
+                restricted,
+            };
+            if self.sender.send(delivery).is_err() {
+                error!("Webhook worker has stopped, dropping {}", event.name());
+            }
+        }
+    }
+}
+
+/// `WebhookWorker` posts queued deliveries, retrying failures with backoff
+#[derive(Debug)]
+pub struct WebhookWorker {
+    receiver: mpsc::UnboundedReceiver<Delivery>,
+    /// Client for the operator URLs
+    client: Client,
+    /// Client for the URLs registered by hosts, see `public_client`
+    room_client: Client,
+    max_attempts: u32,
+    backoff: Duration,
+}
+
+impl WebhookWorker {
+    /// Runs until every dispatcher has been dropped.
+    /// Each delivery runs in its own task so a slow endpoint
+    /// does not hold up the others.
+    pub async fn run(mut self) {
+        while let Some(delivery) = self.receiver.recv().await {
+            let client = if delivery.restricted {
+                &self.room_client
+            } else {
+                &self.client
+            };
+            tokio::spawn(deliver(
+                client.clone(),
+                delivery,
+                self.max_attempts,
+                self.backoff,
+            ));
+        }
+    }
+}
+
+/// Posts a delivery, retrying network errors, 429s and 5xx responses
+/// with exponential backoff until the attempts run out.
+/// Redirects are not followed and count as failures.
+async fn deliver(client: Client, delivery: Delivery, max_attempts: u32, backoff: Duration) {
+    // rooms restored from an older state file may hold URLs registered before they were checked
+    if delivery.restricted
+        && let Err(message) = validate_room_url(&delivery.url)
+    {
+        error!(
+            "Not delivering {} webhook to {}: {}",
+            delivery.event, delivery.url, message
+        );
+        return;
+    }
+    for attempt in 1..=max_attempts {
+        let mut request = client
+            .post(&delivery.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, delivery.event)
+            .header(DELIVERY_HEADER, delivery.id.to_string())
+            .body(delivery.body.to_vec());
+        if let Some(signature) = &delivery.signature {
+            request = request.header(SIGNATURE_HEADER, signature);
+        }
+
+        let retryable = match request.send().await {
+            Ok(response) if response.status().is_success() => {
+                info!("Delivered {} webhook to {}", delivery.event, delivery.url);
+                return;
+            }
+            Ok(response) => {
+                let status = response.status();
+                warn!(
+                    "Webhook {} to {} failed with {} (attempt {})",
+                    delivery.event, delivery.url, status, attempt
+                );
+                status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+            }
+            Err(err) => {
+                warn!(
+                    "Webhook {} to {} failed: {} (attempt {})",
+                    delivery.event, delivery.url, err, attempt
+                );
+                true
+            }
+        };
+
+        if !retryable {
+            break;
+        }
+        if attempt < max_attempts {
+            sleep(backoff.saturating_mul(2_u32.saturating_pow(attempt - 1))).await;
+        }
+    }
+    error!(
+        "Giving up on {} webhook to {}",
+        delivery.event, delivery.url
+    );
+}
+
+/// Creates the dispatcher used by the handlers and the worker that
+/// delivers its events, the worker must be spawned by the caller
+pub fn channel(
+    settings: &WebhookSettings,
+) -> Result<(WebhookDispatcher, WebhookWorker), reqwest::Error> {
+    let client = Client::builder()
+        .timeout(settings.timeout)
+        .redirect(Policy::none())
+        .build()?;
+    let (sender, receiver) = mpsc::unbounded_channel();
+    Ok((
+        WebhookDispatcher {
+            sender,
+            global_urls: settings.urls.clone(),
+            global_secret: settings.secret.clone(),
+        },
+        WebhookWorker {
+            receiver,
+            client,
+            room_client: public_client(settings.timeout)?,
+            max_attempts: settings.max_attempts,
+            backoff: settings.backoff,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Instant,
+    };
+
+    use axum::{
+        Router,
+        http::{StatusCode, header::LOCATION},
+        routing::post,
+    };
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        config::Config,
+        test_support::{self, StandIn},
+    };
+
+    /// Answers with each status in turn, then 200 once they run out
+    fn answering(statuses: &'static [StatusCode]) -> Router {
+        let calls = Arc::new(AtomicUsize::new(0));
+        Router::new().route(
+            "/hook",
+            post(move || async move {
+                let call = calls.fetch_add(1, Ordering::SeqCst);
+                statuses.get(call).copied().unwrap_or(StatusCode::OK)
+            }),
+        )
+    }
+
+    fn delivery(url: String) -> Delivery {
+        Delivery {
+            id: Uuid::new_v4(),
+            url,
+            event: "votesReset",
+            body: Arc::from(&b"{}"[..]),
+            signature: None,
+            restricted: false,
+        }
+    }
+
+    fn verify(secret: &str, body: &[u8], signature: &str) -> bool {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("any key length");
+        mac.update(body);
+        signature
+            .strip_prefix("sha256=")
+            .and_then(|digest| hex::decode(digest).ok())
+            .is_some_and(|expected| mac.verify_slice(&expected).is_ok())
+    }
+
+    #[test]
+    fn sign_formats_hmac_sha256_as_hex() {
+        assert_eq!(
+            sign("key", b"The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[test]
+    fn dispatch_signs_room_deliveries_with_the_room_secret() {
+        let mut settings = Config::default().webhooks;
+        settings.urls = vec!["http://hooks.internal/operator".to_owned()];
+        let (dispatcher, mut worker) = channel(&settings).expect("client builds");
+
+        let mut room: Room = serde_json::from_value(json!({
+            "cards_revealed": false,
+            "host_id": "host",
+            "id": Uuid::new_v4(),
+            "players": {},
+            "card_set": "fibonacci",
+        }))
+        .expect("room deserializes");
+        room.webhooks = RoomWebhooks {
+            secret: "room-secret".to_owned(),
+            urls: vec!["https://hooks.example.com/room".to_owned()],
+            chat: None,
+        };
+        dispatcher.dispatch(&room, &WebhookEvent::VotesReset);
+
+        let signed = worker.receiver.try_recv().expect("room delivery is queued");
+        let unsigned = worker
+            .receiver
+            .try_recv()
+            .expect("operator delivery is queued");
+
+        assert_eq!(signed.url, "https://hooks.example.com/room");
+        assert_eq!(signed.event, "votesReset");
+        let body: serde_json::Value = serde_json::from_slice(&signed.body).expect("body is JSON");
+        assert_eq!(body["event"], "votesReset");
+        assert_eq!(body["room_id"], json!(room.id));
+        let signature = signed
+            .signature
+            .as_deref()
+            .expect("room delivery is signed");
+        assert!(verify("room-secret", &signed.body, signature));
+        // host registered URLs may only reach public addresses, the operator's any
+        assert!(signed.restricted);
+        assert!(!unsigned.restricted);
+        // both targets get the same delivery, the operator's unsigned without a secret
+        assert_eq!(signed.id, unsigned.id);
+        assert_eq!(signed.body, unsigned.body);
+        assert_eq!(unsigned.signature, None);
+    }
+
+    #[test]
+    fn validate_room_url_rejects_internal_targets() {
+        for url in [
+            "http://hooks.example.com/",
+            "https://10.0.0.5/",
+            "https://172.16.3.4/",
+            "https://192.168.1.1/",
+            "https://100.64.0.1/",
+            "https://169.254.169.254/latest/meta-data",
+            "https://127.0.0.1:8443/",
+            "https://0x7f.1/",
+            "https://0.0.0.0/",
+            "https://localhost/",
+            "https://admin.localhost./",
+            "https://[::1]/",
+            "https://[::]/",
+            "https://[fd00::1]/",
+            "https://[fe80::1]/",
+            "https://[::ffff:10.0.0.1]/",
+        ] {
+            assert!(validate_room_url(url).is_err(), "{url} is rejected");
+        }
+    }
+
+    #[test]
+    fn validate_room_url_accepts_public_https() {
+        assert_eq!(
+            validate_room_url(" https://hooks.example.com/room ").as_deref(),
+            Ok("https://hooks.example.com/room")
+        );
+        assert!(validate_room_url("https://93.184.215.14/").is_ok());
+        assert!(validate_room_url("https://[2606:4700::1111]/").is_ok());
+    }
+
+    #[tokio::test]
+    async fn public_resolver_drops_internal_addresses() {
+        let name: Name = "localhost".parse().expect("valid name");
+
+        assert!(PublicResolver.resolve(name).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn room_client_does_not_connect_to_names_resolving_internally() {
+        let hook = StandIn::start(answering(&[])).await;
+        let port = hook.url.rsplit(':').next().expect("stand-in has a port");
+        let (_, worker) = channel(&Config::default().webhooks).expect("client builds");
+
+        deliver(
+            worker.room_client,
+            delivery(format!("http://localhost:{port}/hook")),
+            2,
+            Duration::from_millis(1),
+        )
+        .await;
+
+        assert!(hook.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn deliver_refuses_restricted_internal_urls() {
+        let hook = StandIn::start(answering(&[])).await;
+        let mut delivery = delivery(format!("{}/hook", hook.url));
+        delivery.restricted = true;
+
+        deliver(Client::new(), delivery, 2, Duration::from_millis(1)).await;
+
+        assert!(hook.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn deliver_does_not_follow_redirects() {
+        let internal = StandIn::start(answering(&[])).await;
+        let location = format!("{}/hook", internal.url);
+        let hook = StandIn::start(Router::new().route(
+            "/hook",
+            post(move || async move { (StatusCode::FOUND, [(LOCATION, location)]) }),
+        ))
+        .await;
+        let (_, worker) = channel(&Config::default().webhooks).expect("client builds");
+
+        deliver(
+            worker.client,
+            delivery(format!("{}/hook", hook.url)),
+            3,
+            Duration::from_millis(1),
+        )
+        .await;
+
+        assert_eq!(hook.requests().len(), 1);
+        assert!(internal.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn deliver_retries_with_exponential_backoff() {
+        let hook = StandIn::start(answering(&[
+            StatusCode::SERVICE_UNAVAILABLE,
+            StatusCode::TOO_MANY_REQUESTS,
+        ]))
+        .await;
+        let delivery = delivery(format!("{}/hook", hook.url));
+        let id = delivery.id.to_string();
+
+        let started = Instant::now();
+        deliver(Client::new(), delivery, 5, Duration::from_millis(50)).await;
+
+        // waits 50ms then 100ms before succeeding on the third attempt
+        assert!(started.elapsed() >= Duration::from_millis(150));
+        let requests = hook.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(
+            requests
+                .iter()
+                .all(|request| request.header(DELIVERY_HEADER) == Some(id.as_str()))
+        );
+    }
+
+    #[tokio::test]
+    async fn deliver_gives_up_after_max_attempts() {
+        let hook = StandIn::start(answering(&[StatusCode::INTERNAL_SERVER_ERROR; 5])).await;
+
+        deliver(
+            Client::new(),
+            delivery(format!("{}/hook", hook.url)),
+            3,
+            Duration::from_millis(1),
+        )
+        .await;
+
+        assert_eq!(hook.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn deliver_does_not_retry_client_errors() {
+        let hook = StandIn::start(answering(&[StatusCode::GONE])).await;
+
+        deliver(
+            Client::new(),
+            delivery(format!("{}/hook", hook.url)),
+            3,
+            Duration::from_millis(1),
+        )
+        .await;
+
+        assert_eq!(hook.requests().len(), 1);
+    }
+
+    #[test]
+    fn cards_revealed_names_voters_only_with_attribution() {
+        let mut room = test_support::room(&["host"]);
+        test_support::cast(&mut room, &[("host", 3)]);
+        let names = |room: &Room| match WebhookEvent::cards_revealed(room) {
+            WebhookEvent::CardsRevealed { votes, .. } => {
+                votes.into_iter().map(|vote| vote.name).collect::<Vec<_>>()
+            }
+            _ => Vec::new(),
+        };
+
+        assert_eq!(names(&room), [Some("host".to_owned())]);
+        room.settings.anonymous = true;
+        assert_eq!(names(&room), [None]);
+        room.settings.keep_attribution = true;
+        assert_eq!(names(&room), [Some("host".to_owned())]);
+    }
+}
@@ -0,0 +1,321 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    Extension,
+    extract::{
+        Query, State, WebSocketUpgrade,
+        ws::{Message, WebSocket},
+    },
+    http::{HeaderMap, StatusCode, header::ORIGIN},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use socketioxide::SocketIo;
+use tokio::{
+    sync::{broadcast::error::RecvError, mpsc},
+    task::AbortHandle,
+};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    handlers::{self, Peer},
+    hub::{RoomHub, RoomMessage},
+    participants::SessionAuth,
+    protocol::{self, ProtocolAuth},
+    types::{
+        AcceptEstimateEvent, AddWebhookEvent, AppState, CreateRoomEvent, ImportGitHubIssuesEvent,
+        ImportJiraIssuesEvent, InvalidMessageEvent, JoinRoomEvent, JoinTeamRoomEvent,
+        PlayerExitEvent, RemoveWebhookEvent, ResetVotesEvent, ResyncEvent, RetractVoteEvent,
+        RevealCardsEvent, RevoteEvent, SelectStoryEvent, SetChatWebhookEvent, SetSpectatorEvent,
+        SocketEvent, UpdateProfileEvent, UpdateSettingsEvent, VoteEvent,
+    },
+};
+
+/// Messages accepted from WebSocket clients, each maps to the
+/// Socket.IO event of the same name
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", content = "payload", rename_all = "camelCase")]
+enum ClientMessage {
+    CreateRoom(CreateRoomEvent),
+    JoinRoom(JoinRoomEvent),
+    JoinTeamRoom(JoinTeamRoomEvent),
+    Vote(VoteEvent),
+    RetractVote(RetractVoteEvent),
+    SetSpectator(SetSpectatorEvent),
+    UpdateProfile(UpdateProfileEvent),
+    RevealCards(RevealCardsEvent),
+    Revote(RevoteEvent),
+    AcceptEstimate(AcceptEstimateEvent),
+    ResetVotes(ResetVotesEvent),
+    ExitRoom(PlayerExitEvent),
+    Resync(ResyncEvent),
+    AddWebhook(AddWebhookEvent),
+    RemoveWebhook(RemoveWebhookEvent),
+    SetChatWebhook(SetChatWebhookEvent),
+    ImportJiraIssues(ImportJiraIssuesEvent),
+    ImportGitHubIssues(ImportGitHubIssuesEvent),
+    SelectStory(SelectStoryEvent),
+    UpdateSettings(UpdateSettingsEvent),
+}
+
+/// `WsPeer` is a client connected over the plain WebSocket protocol.
+/// Outbound events are queued for the connection task to send,
+/// room broadcasts are forwarded from the hub while the peer is in the room.
+#[derive(Debug)]
+pub struct WsPeer {
+    /// The ID of the peer's connection
+    pub id: String,
+    sender: mpsc::UnboundedSender<Arc<RoomMessage>>,
+    /// Forwarding tasks for the rooms the peer is in
+    rooms: Mutex<HashMap<Uuid, AbortHandle>>,
+}
+
+impl WsPeer {
+    /// A peer whose outbound events are queued on `sender`
+    pub fn new(sender: mpsc::UnboundedSender<Arc<RoomMessage>>) -> Self {
+        Self {
+            id: Uuid::new_v4().simple().to_string(),
+            sender,
+            rooms: Mutex::default(),
+        }
+    }
+
+    /// Queues an event for this peer alone
+    pub fn emit<E: SocketEvent>(&self, data: &E::Data) {
+        if let Some(message) = RoomMessage::new::<E>(data) {
+            // the connection is closing if the receiver has gone
+            self.sender.send(Arc::new(message)).ok();
+        }
+    }
+
+    /// Starts forwarding a room's broadcasts to this peer
+    pub fn join(&self, hub: &RoomHub, room_id: Uuid) {
+        let mut rooms = self.rooms.lock().expect("peer lock poisoned");
+        if rooms.contains_key(&room_id) {
+            return;
+        }
+
+        let mut receiver = hub.subscribe(room_id);
+        let sender = self.sender.clone();
+        let id = self.id.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(message) => {
+                        if sender.send(message).is_err() {
+                            break;
+                        }
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!(
+                            "Peer {} missed {} messages in room {}",
+                            id, skipped, room_id
+                        );
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+        rooms.insert(room_id, task.abort_handle());
+    }
+
+    /// Stops forwarding a room's broadcasts to this peer
+    pub fn leave(&self, room_id: Uuid) {
+        let task = self
+            .rooms
+            .lock()
+            .expect("peer lock poisoned")
+            .remove(&room_id);
+        if let Some(task) = task {
+            task.abort();
+        }
+    }
+}
+
+impl Drop for WsPeer {
+    fn drop(&mut self) {
+        if let Ok(rooms) = self.rooms.get_mut() {
+            for task in rooms.values() {
+                task.abort();
+            }
+        }
+    }
+}
+
+/// Upgrades a request to the plain WebSocket protocol.
+/// - Browsers on disallowed origins are refused like Socket.IO handshakes.
+/// - Clients pass the protocol version they speak as `?protocol=`,
+///   unsupported versions are refused with a 400 explaining why.
+pub async fn ws_handler(
+    ws: WebSocketUpgrade,
+    headers: HeaderMap,
+    Query(auth): Query<ProtocolAuth>,
+    Query(session): Query<SessionAuth>,
+    State(app_state): State<Arc<AppState>>,
+    Extension(io): Extension<SocketIo>,
+) -> Response {
+    let origin = headers
+        .get(ORIGIN)
+        .map(|origin| origin.to_str().unwrap_or_default());
+    if let Some(origin) = origin
+        && !app_state.config.origin_allowed(origin)
+    {
+        error!("Rejecting WebSocket from origin {}", origin);
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    if let Err(err) = protocol::check_version(auth.protocol) {
+        error!("Rejecting WebSocket: {}", err);
+        return (StatusCode::BAD_REQUEST, err.to_string()).into_response();
+    }
+
+    ws.on_upgrade(move |socket| run(socket, app_state, io, session, headers))
+}
+
+/// Runs a WebSocket connection until either side closes it.
+/// - Starts the client's session, resuming `?session=` if given
+///   and checking the login cookie sent with the upgrade.
+/// - Dispatches each inbound message to the shared handlers.
+/// - Sends queued outbound events as `{ "type", "payload" }` text frames.
+/// - Removes the peer from its rooms when the connection ends.
+async fn run(
+    mut socket: WebSocket,
+    app_state: Arc<AppState>,
+    io: SocketIo,
+    session: SessionAuth,
+    headers: HeaderMap,
+) {
+    let (sender, mut outbound) = mpsc::unbounded_channel();
+    let ws_peer = Arc::new(WsPeer::new(sender));
+    let peer = Peer::WebSocket(Arc::clone(&ws_peer));
+    info!("WebSocket client connected: {}", ws_peer.id);
+    handlers::start_session(&peer, &app_state, &session, &headers);
+
+    loop {
+        tokio::select! {
+            inbound = socket.recv() => match inbound {
+                Some(Ok(Message::Text(text))) => {
+                    dispatch(&peer, &io, &app_state, &text).await;
+                }
+                Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+            Some(message) = outbound.recv() => {
+                let text = match serde_json::to_string(&*message) {
+                    Ok(text) => text,
+                    Err(err) => {
+                        error!("Failed to serialize {}: {}", message.event, err);
+                        continue;
+                    }
+                };
+                if socket.send(Message::Text(text.into())).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    handlers::disconnect(&peer, &io, &app_state).await;
+}
+
+/// Parses an inbound message and runs the matching handler
+async fn dispatch(peer: &Peer, io: &SocketIo, app_state: &AppState, text: &str) {
+    let message = match serde_json::from_str::<ClientMessage>(text) {
+        Ok(message) => message,
+        Err(err) => {
+            warn!("Invalid message from {}: {}", peer.id(), err);
+            peer.emit::<InvalidMessageEvent>(&err.to_string());
+            return;
+        }
+    };
+
+    match message {
+        ClientMessage::CreateRoom(payload) => handlers::create_room(peer, app_state, payload).await,
+        ClientMessage::JoinRoom(payload) => handlers::join_room(peer, io, app_state, payload).await,
+        ClientMessage::JoinTeamRoom(payload) => {
+            handlers::join_team_room(peer, io, app_state, payload).await;
+        }
+        ClientMessage::Vote(payload) => handlers::vote(peer, io, app_state, payload).await,
+        ClientMessage::RetractVote(payload) => {
+            handlers::retract_vote(peer, io, app_state, payload).await;
+        }
+        ClientMessage::SetSpectator(payload) => {
+            handlers::set_spectator(peer, io, app_state, payload).await;
+        }
+        ClientMessage::UpdateProfile(payload) => {
+            handlers::update_profile(peer, io, app_state, payload).await;
+        }
+        ClientMessage::RevealCards(payload) => handlers::reveal(peer, io, app_state, payload).await,
+        ClientMessage::Revote(payload) => handlers::revote(peer, io, app_state, payload).await,
+        ClientMessage::AcceptEstimate(payload) => {
+            handlers::accept_estimate(peer, io, app_state, payload).await;
+        }
+        ClientMessage::ResetVotes(payload) => {
+            handlers::reset_votes(peer, io, app_state, payload).await;
+        }
+        ClientMessage::ExitRoom(payload) => handlers::exit_room(peer, io, app_state, payload).await,
+        ClientMessage::Resync(payload) => handlers::resync(peer, app_state, payload).await,
+        ClientMessage::AddWebhook(payload) => handlers::add_webhook(peer, app_state, payload).await,
+        ClientMessage::RemoveWebhook(payload) => {
+            handlers::remove_webhook(peer, app_state, payload).await;
+        }
+        ClientMessage::SetChatWebhook(payload) => {
+            handlers::set_chat_webhook(peer, app_state, payload).await;
+        }
+        ClientMessage::ImportJiraIssues(payload) => {
+            handlers::import_jira_issues(peer, io, app_state, payload).await;
+        }
+        ClientMessage::ImportGitHubIssues(payload) => {
+            handlers::import_github_issues(peer, io, app_state, payload).await;
+        }
+        ClientMessage::SelectStory(payload) => {
+            handlers::select_story(peer, io, app_state, payload).await;
+        }
+        ClientMessage::UpdateSettings(payload) => {
+            handlers::update_settings(peer, io, app_state, payload).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::types::client_events;
+
+    #[test]
+    fn every_client_event_has_a_message() {
+        for event in client_events() {
+            let message = json!({ "type": event.name, "payload": {} });
+            if let Err(err) = serde_json::from_value::<ClientMessage>(message) {
+                assert!(
+                    !err.to_string().contains("unknown variant"),
+                    "{} has no ClientMessage variant",
+                    event.name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn messages_carry_the_socket_io_payloads() {
+        let message = serde_json::from_value(json!({
+            "type": "vote",
+            "payload": { "room_id": "room", "vote": 5 },
+        }));
+
+        assert!(matches!(
+            message,
+            Ok(ClientMessage::Vote(VoteEvent { vote: 5, .. }))
+        ));
+        let unknown =
+            serde_json::from_value::<ClientMessage>(json!({ "type": "shuffle", "payload": {} }));
+        assert!(unknown.is_err_and(|err| err.to_string().contains("unknown variant")));
+    }
+}